@@ -1,217 +1,40 @@
-use std::collections::VecDeque;
-use std::fs::read_to_string;
 use std::path::Path;
 
-trait AutoExpand {
-    type Item;
-    fn expandable_get(&mut self, pos: usize) -> &Self::Item;
-    fn expandable_set(&mut self, pos: usize, item: Self::Item);
-}
-
-impl<T: Default + Clone> AutoExpand for Vec<T> {
-    type Item = T;
-
-    fn expandable_get(&mut self, pos: usize) -> &Self::Item {
-        let len = self.len();
-        if pos + 1 > len {
-            self.extend(vec![T::default(); pos + 1 - len].into_iter());
-        }
-        &self[pos]
-    }
-
-    fn expandable_set(&mut self, pos: usize, item: Self::Item) {
-        let len = self.len();
-        if pos + 1 > len {
-            self.extend(vec![T::default(); pos + 1 - len].into_iter());
-        }
-        self[pos] = item;
-    }
-}
-
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
-enum ResultCode {
-    Output(i64),
-    Terminated,
-}
-
-enum Mode {
-    Position,
-    Immediate,
-    Relative,
-}
-
-impl Mode {
-    fn from_i64(val: i64) -> Self {
-        match val {
-            0 => Self::Position,
-            1 => Self::Immediate,
-            2 => Self::Relative,
-            mode => panic!("Mode must be either 0, 1 or 2, receive {}", mode),
-        }
-    }
-}
+use intcode::{load_program, IntCodeComputer, Prologue};
 
-struct IntCodeComputer {
-    program: Vec<i64>,
-    input: VecDeque<i64>,
-    output: Vec<i64>,
-    inst_pointer: usize,
-    relative_base: i64,
+/// The drone system, run up to the point where it asks for the coordinates to deploy a drone
+/// to, so that each deployment only runs the rest of the program.
+struct Drones {
+    prologue: Prologue,
+    computer: IntCodeComputer,
 }
 
-impl IntCodeComputer {
+impl Drones {
     fn new(program: &[i64]) -> Self {
-        Self {
-            program: program.to_vec(),
-            input: VecDeque::new(),
-            output: Vec::new(),
-            inst_pointer: 0,
-            relative_base: 0,
-        }
-    }
-
-    fn add_input(&mut self, new_input: i64) {
-        self.input.push_front(new_input)
+        let prologue = Prologue::new(program);
+        let computer = prologue.fork();
+        Self { prologue, computer }
     }
 
-    fn parse_instruction(&self) -> (i64, Mode, Mode, Mode) {
-        let inst = self.program[self.inst_pointer];
-        let opcode = inst % 100;
-        let mut inst = inst / 100;
-        let mode_1 = inst % 10;
-        inst /= 10;
-        let mode_2 = inst % 10;
-        inst /= 10;
-        let mode_3 = inst % 10;
-        (
-            opcode,
-            Mode::from_i64(mode_1),
-            Mode::from_i64(mode_2),
-            Mode::from_i64(mode_3),
-        )
+    fn in_range(&mut self, x: i64, y: i64) -> bool {
+        self.prologue.fork_into(&mut self.computer);
+        self.computer.add_input(x);
+        self.computer.add_input(y);
+        self.computer.run_program().last() == Some(&1)
     }
-
-    fn get_val(&mut self, pos: usize, mode: Mode) -> i64 {
-        let res = *self.program.expandable_get(pos);
-        match mode {
-            Mode::Immediate => res,
-            Mode::Position => *self.program.expandable_get(res as usize),
-            Mode::Relative => *self
-                .program
-                .expandable_get((res + self.relative_base) as usize),
-        }
-    }
-
-    fn set_val(&mut self, pos: usize, val: i64, mode: Mode) {
-        let offset = *self.program.expandable_get(pos);
-        let res = match mode {
-            Mode::Position => offset,
-            Mode::Relative => offset + self.relative_base,
-            _ => panic!(),
-        };
-        self.program.expandable_set(res as usize, val)
-    }
-
-    fn run_one_turn(&mut self) -> ResultCode {
-        while self.inst_pointer < self.program.len() {
-            let (opcode, mode_1, mode_2, mode_3) = self.parse_instruction();
-            match opcode {
-                1 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    self.set_val(self.inst_pointer + 3, fst + snd, mode_3);
-                    self.inst_pointer += 4;
-                }
-                2 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    self.set_val(self.inst_pointer + 3, fst * snd, mode_3);
-                    self.inst_pointer += 4;
-                }
-                3 => {
-                    let val = self.input.pop_back().unwrap();
-                    self.set_val(self.inst_pointer + 1, val, mode_1);
-                    self.inst_pointer += 2;
-                }
-                4 => {
-                    let output = self.get_val(self.inst_pointer + 1, mode_1);
-                    self.inst_pointer += 2;
-                    self.output.push(output);
-                    return ResultCode::Output(output);
-                }
-                5 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    if fst != 0 {
-                        self.inst_pointer = snd as usize
-                    } else {
-                        self.inst_pointer += 3
-                    }
-                }
-                6 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    if fst == 0 {
-                        self.inst_pointer = snd as usize
-                    } else {
-                        self.inst_pointer += 3
-                    }
-                }
-                7 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    let val = if fst < snd { 1 } else { 0 };
-                    self.set_val(self.inst_pointer + 3, val, mode_3);
-                    self.inst_pointer += 4;
-                }
-                8 => {
-                    let fst = self.get_val(self.inst_pointer + 1, mode_1);
-                    let snd = self.get_val(self.inst_pointer + 2, mode_2);
-                    let val = if fst == snd { 1 } else { 0 };
-                    self.set_val(self.inst_pointer + 3, val, mode_3);
-                    self.inst_pointer += 4;
-                }
-                9 => {
-                    self.relative_base += self.get_val(self.inst_pointer + 1, mode_1);
-                    self.inst_pointer += 2;
-                }
-                99 => return ResultCode::Terminated,
-                opcode => panic!(
-                    "Opcode must be 1, 2, 3, 4, 5, 6, 7, 8, 9 or 99, receive {} at {}",
-                    opcode, self.inst_pointer,
-                ),
-            }
-        }
-        unreachable!()
-    }
-
-    fn run_program(&mut self) -> Vec<i64> {
-        while let ResultCode::Output(_) = self.run_one_turn() {
-            // self.add_input(i)
-        }
-        self.output.to_owned()
-    }
-}
-
-fn read_input(filepath: &Path) -> std::io::Result<Vec<i64>> {
-    Ok(read_to_string(filepath)?
-        .split(',')
-        .filter_map(|s| s.trim().parse::<i64>().ok())
-        .collect())
-}
-
-fn in_range(program: &[i64], x: i64, y: i64) -> bool {
-    let mut computer = IntCodeComputer::new(program);
-    computer.add_input(x);
-    computer.add_input(y);
-    computer.run_program().last() == Some(&1)
 }
 
 fn part1(input: &[i64]) -> i64 {
+    let drones = Drones::new(input);
+    let mut computer = drones.prologue.fork();
     let mut result = 0;
     for x in 0..50 {
+        // Every y in a column shares the work done on x.
+        let column = drones.prologue.then(&[x]);
         for y in 0..50 {
-            if in_range(input, x, y) {
+            column.fork_into(&mut computer);
+            computer.add_input(y);
+            if computer.run_program().last() == Some(&1) {
                 result += 1;
             }
         }
@@ -220,10 +43,11 @@ fn part1(input: &[i64]) -> i64 {
 }
 
 fn part2(input: &[i64]) -> i64 {
+    let mut drones = Drones::new(input);
     let (mut x, mut y) = (0, 0);
-    while !in_range(input, x + 99, y) {
+    while !drones.in_range(x + 99, y) {
         y += 1;
-        while !in_range(input, x, y + 99) {
+        while !drones.in_range(x, y + 99) {
             x += 1;
         }
     }
@@ -232,8 +56,26 @@ fn part2(input: &[i64]) -> i64 {
 
 fn main() -> std::io::Result<()> {
     let filepath = Path::new("./input/input.txt");
-    let input = read_input(filepath)?;
+    let input = load_program(filepath)?;
     println!("part 1: {}", part1(&input));
     println!("part 2: {}", part2(&input));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forks_agree_with_fresh_computers() {
+        let program = load_program(Path::new("./input/input.txt")).unwrap();
+        let mut drones = Drones::new(&program);
+        for (x, y) in [(0, 0), (3, 4), (12, 15), (40, 49), (700, 900)] {
+            let mut computer = IntCodeComputer::new(&program);
+            computer.add_input(x);
+            computer.add_input(y);
+            let expected = computer.run_program().last() == Some(&1);
+            assert_eq!(drones.in_range(x, y), expected, "({}, {})", x, y);
+        }
+    }
+}
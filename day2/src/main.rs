@@ -1,58 +1,50 @@
-use std::fs::read_to_string;
+use std::io;
 use std::path::Path;
 
-fn run_program(input: &[i32], fst: i32, snd: i32) -> i32 {
-    let mut input = input.to_vec();
-    input[1] = fst;
-    input[2] = snd;
+use intcode::load_program;
+use intcode::solver::Problem;
 
-    let len = input.len();
-    for i in (0..len).step_by(4) {
-        if i + 3 > len {
-            break;
-        }
-        match input[i] {
-            1 => {
-                let (fst, snd, dst) = (input[i + 1], input[i + 2], input[i + 3]);
-                input[dst as usize] = input[fst as usize] + input[snd as usize]
-            }
-            2 => {
-                let (fst, snd, dst) = (input[i + 1], input[i + 2], input[i + 3]);
-                input[dst as usize] = input[fst as usize] * input[snd as usize]
-            }
-            99 => break,
-            _ => panic!("Opcode must be 1, 2 or 99"),
-        }
-    }
-    input[0]
-}
-
-fn read_input(filepath: &Path) -> std::io::Result<Vec<i32>> {
-    Ok(read_to_string(filepath)?
-        .split(',')
-        .filter_map(|s| s.parse::<i32>().ok())
-        .collect())
+/// The program with the noun and verb, in cells 1 and 2, left to be chosen. Its result is left
+/// in cell 0.
+fn gravity_assist(input: &[i64]) -> Problem<'_> {
+    Problem::new(input, 0).input(1, 0..=99).input(2, 0..=99)
 }
 
-fn part1(input: &[i32]) -> i32 {
-    run_program(input, 12, 2)
+fn part1(input: &[i64]) -> i64 {
+    gravity_assist(input)
+        .eval(&[12, 2])
+        .expect("the program did not halt")
 }
 
-fn part2(input: &[i32]) -> i32 {
-    for noun in 0..100 {
-        for verb in 0..100 {
-            if run_program(input, noun, verb) == 19_690_720 {
-                return 100 * noun + verb;
-            }
-        }
+fn part2(input: &[i64]) -> i64 {
+    let problem = gravity_assist(input);
+    let solutions = problem
+        .solve_affine(19_690_720)
+        .unwrap_or_else(|| problem.solve(19_690_720));
+    match solutions.first().map(Vec::as_slice) {
+        Some(&[noun, verb]) => 100 * noun + verb,
+        _ => panic!("no noun and verb produce 19690720"),
     }
-    unreachable!()
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> io::Result<()> {
     let filepath = Path::new("./input/input.txt");
-    let input = read_input(filepath)?;
+    let input = load_program(filepath)?;
     println!("part 1: {}", part1(&input));
     println!("part 2: {}", part2(&input));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affine_and_exhaustive_solutions_agree() {
+        let input = load_program(Path::new("./input/input.txt")).unwrap();
+        let problem = gravity_assist(&input);
+        let solutions = problem.solve(19_690_720);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(problem.solve_affine(19_690_720), Some(solutions));
+    }
+}
@@ -1,6 +1,9 @@
-use std::fs::read_to_string;
+use std::convert::TryFrom;
+use std::io;
 use std::path::Path;
 
+use intcode::{load_program, Diagnostic, TestReport};
+
 enum Mode {
     Position,
     Intermediate,
@@ -33,17 +36,20 @@ fn get_val(program: &[i32], val: usize, mode: Mode) -> i32 {
     }
 }
 
-fn run_program(program: &[i32], input: i32) -> i32 {
-    let mut program = program.to_vec();
+// Runs `program` in place and returns its outputs, with the address of the instruction that
+// output each one. Each input instruction reads the last value output so far, or `input` before
+// the first output.
+fn execute(program: &mut [i32], input: i32) -> Vec<Diagnostic> {
     let (mut i, mut input) = (0, input);
+    let mut outputs = Vec::new();
 
     while i < program.len() {
         let (opcode, mode_1, mode_2) = parse_instruction(program[i]);
         match opcode {
             1 => {
                 let (fst, snd, dst) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                     program[i + 3],
                 );
                 program[dst as usize] = fst + snd;
@@ -51,8 +57,8 @@ fn run_program(program: &[i32], input: i32) -> i32 {
             }
             2 => {
                 let (fst, snd, dst) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                     program[i + 3],
                 );
                 program[dst as usize] = fst * snd;
@@ -64,27 +70,31 @@ fn run_program(program: &[i32], input: i32) -> i32 {
                 i += 2;
             }
             4 => {
-                input = get_val(&program, i + 1, mode_1);
+                input = get_val(program, i + 1, mode_1);
+                outputs.push(Diagnostic {
+                    addr: i,
+                    value: input.into(),
+                });
                 i += 2;
             }
             5 => {
                 let (fst, snd) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                 );
                 i = if fst != 0 { snd as usize } else { i + 3 }
             }
             6 => {
                 let (fst, snd) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                 );
                 i = if fst == 0 { snd as usize } else { i + 3 }
             }
             7 => {
                 let (fst, snd, dst) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                     program[i + 3],
                 );
                 program[dst as usize] = if fst < snd { 1 } else { 0 };
@@ -92,8 +102,8 @@ fn run_program(program: &[i32], input: i32) -> i32 {
             }
             8 => {
                 let (fst, snd, dst) = (
-                    get_val(&program, i + 1, mode_1),
-                    get_val(&program, i + 2, mode_2),
+                    get_val(program, i + 1, mode_1),
+                    get_val(program, i + 2, mode_2),
                     program[i + 3],
                 );
                 program[dst as usize] = if fst == snd { 1 } else { 0 };
@@ -106,28 +116,79 @@ fn run_program(program: &[i32], input: i32) -> i32 {
             ),
         }
     }
-    input
+    outputs
+}
+
+// Panics with the report if any test failed.
+fn run_program(program: &[i32], input: i32) -> i64 {
+    let report = TestReport::decode(&execute(&mut program.to_vec(), input));
+    assert!(report.is_pass(), "{}", report);
+    report.code.unwrap().value
 }
 
-fn read_input(filepath: &Path) -> std::io::Result<Vec<i32>> {
-    Ok(read_to_string(filepath)?
-        .split(',')
-        .filter_map(|s| s.trim().parse::<i32>().ok())
-        .collect())
+fn read_input(filepath: &Path) -> io::Result<Vec<i32>> {
+    load_program::<i64>(filepath)?
+        .into_iter()
+        .map(|val| {
+            i32::try_from(val).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+        .collect()
 }
 
-fn part1(input: &[i32]) -> i32 {
+fn part1(input: &[i32]) -> i64 {
     run_program(input, 1)
 }
 
-fn part2(input: &[i32]) -> i32 {
+fn part2(input: &[i32]) -> i64 {
     run_program(input, 5)
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> io::Result<()> {
     let filepath = Path::new("./input/input.txt");
     let input = read_input(filepath)?;
     println!("part 1: {}", part1(&input));
     println!("part 2: {}", part2(&input));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::conformance::{certify, Execution};
+    use intcode::fuzz::{self, Config};
+
+    fn run(program: &[i64], inputs: &[i64]) -> Execution {
+        let mut memory: Vec<_> = program.iter().map(|&val| val as i32).collect();
+        let input = inputs.first().map_or(0, |&val| val as i32);
+        let outputs = execute(&mut memory, input);
+        Execution {
+            outputs: outputs.into_iter().map(|out| out.value).collect(),
+            memory: memory.into_iter().map(i64::from).collect(),
+        }
+    }
+
+    // Every input reads the last output, so a second input is never taken from the caller.
+    const EXPECTED: &[&str] = &[
+        "aoc/day7-a-first-amplifier",
+        "aoc/day7-c-first-amplifier",
+        "opcodes/input-order",
+    ];
+
+    #[test]
+    fn conformance() {
+        let report = certify(&run, 5).unwrap();
+        assert_eq!(report.failed_names(), EXPECTED, "{}", report);
+    }
+
+    #[test]
+    fn differential_fuzzing() {
+        // Reads after an output get the last output, so programs that loop on them may never
+        // halt here.
+        let config = Config {
+            reads_after_output: false,
+            ..Config::new(5)
+        };
+        let report = fuzz::run(&config, &[("day5", &run)]).unwrap();
+        assert!(report.is_clean(), "{}", report);
+    }
+}
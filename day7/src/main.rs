@@ -1,203 +1,89 @@
-use std::collections::VecDeque;
-use std::fs::read_to_string;
+use std::env;
 use std::path::Path;
 
+use intcode::{load_program, IntCodeComputer, Merge, Routing, ThreadedNetwork, Topology};
 use itertools::Itertools;
 
-enum ResultCode {
-    Output(i32),
-    Terminated,
-}
-
-enum Mode {
-    Position,
-    Intermediate,
-}
+type Runner = fn(&Topology, &[i64]) -> i64;
 
-impl Mode {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            0 => Self::Position,
-            1 => Self::Intermediate,
-            mode => panic!("MOde must be either 0 or 1, receive {}", mode),
-        }
-    }
+fn run_amplifiers(topology: &Topology, program: &[i64]) -> i64 {
+    topology.run(program).signal.unwrap()
 }
 
-struct IntCodeComputer {
-    program: Vec<i32>,
-    input: VecDeque<i32>,
-    inst_pointer: usize,
-}
-
-impl IntCodeComputer {
-    fn new(program: &[i32], phase: i32) -> Self {
-        Self {
-            program: program.to_vec(),
-            input: VecDeque::from(vec![phase]),
-            inst_pointer: 0,
-        }
-    }
-
-    fn parse_instruction(&self) -> (i32, Mode, Mode) {
-        let inst = self.program[self.inst_pointer];
-        let opcode = inst % 100;
-        let mut inst = inst / 100;
-        let mode_1 = inst % 10;
-        inst /= 10;
-        let mode_2 = inst % 10;
-        (opcode, Mode::from_i32(mode_1), Mode::from_i32(mode_2))
-    }
-
-    fn get_val(&self, loc: usize, mode: Mode) -> i32 {
-        let res = self.program[loc];
-        match mode {
-            Mode::Intermediate => res,
-            Mode::Position => self.program[res as usize],
-        }
-    }
-
-    fn run_one_turn(&mut self, input: i32) -> ResultCode {
-        self.input.push_back(input);
-        while self.inst_pointer < self.program.len() {
-            let (opcode, mode_1, mode_2) = self.parse_instruction();
-            match opcode {
-                1 => {
-                    let (fst, snd, dst) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                        self.program[self.inst_pointer + 3],
-                    );
-                    self.program[dst as usize] = fst + snd;
-                    self.inst_pointer += 4;
-                }
-                2 => {
-                    let (fst, snd, dst) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                        self.program[self.inst_pointer + 3],
-                    );
-                    self.program[dst as usize] = fst * snd;
-                    self.inst_pointer += 4;
-                }
-                3 => {
-                    let dst = self.program[self.inst_pointer + 1];
-                    self.program[dst as usize] = self.input.pop_front().unwrap();
-                    self.inst_pointer += 2;
-                }
-                4 => {
-                    let output = self.get_val(self.inst_pointer + 1, mode_1);
-                    self.inst_pointer += 2;
-                    return ResultCode::Output(output);
-                }
-                5 => {
-                    let (fst, snd) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                    );
-                    self.inst_pointer = if fst != 0 {
-                        snd as usize
-                    } else {
-                        self.inst_pointer + 3
-                    }
-                }
-                6 => {
-                    let (fst, snd) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                    );
-                    self.inst_pointer = if fst == 0 {
-                        snd as usize
-                    } else {
-                        self.inst_pointer + 3
-                    }
-                }
-                7 => {
-                    let (fst, snd, dst) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                        self.program[self.inst_pointer + 3],
-                    );
-                    self.program[dst as usize] = if fst < snd { 1 } else { 0 };
-                    self.inst_pointer += 4;
-                }
-                8 => {
-                    let (fst, snd, dst) = (
-                        self.get_val(self.inst_pointer + 1, mode_1),
-                        self.get_val(self.inst_pointer + 2, mode_2),
-                        self.program[self.inst_pointer + 3],
-                    );
-                    self.program[dst as usize] = if fst == snd { 1 } else { 0 };
-                    self.inst_pointer += 4;
-                }
-                99 => return ResultCode::Terminated,
-                opcode => panic!(
-                    "Opcode must be 1, 2, 3, 4, 5, 6, 7, 8 or 99, receive {} at {}",
-                    opcode, self.inst_pointer,
-                ),
-            }
+fn run_amplifiers_threaded(topology: &Topology, program: &[i64]) -> i64 {
+    assert!(
+        topology
+            .amplifiers()
+            .iter()
+            .all(|amp| amp.merge == Merge::Interleave),
+        "threaded amplifiers cannot merge their inputs"
+    );
+    let mut amplifiers = ThreadedNetwork::new(Routing::Pipes(topology.edges().to_vec()));
+    for amp in topology.amplifiers() {
+        let mut computer = IntCodeComputer::new(program);
+        if let Some(phase) = amp.phase {
+            computer.add_input(phase);
         }
-        unreachable!()
+        amplifiers.add_machine(computer);
     }
-
-    fn run_program(&mut self, input: i32) -> i32 {
-        let mut input = input;
-        while let ResultCode::Output(i) = self.run_one_turn(input) {
-            input = i;
-        }
-        self.input[0]
+    let mut amplifiers = amplifiers.start();
+    for &(id, val) in topology.signals() {
+        amplifiers.send(id, val);
     }
+    amplifiers.wait_until_idle();
+    *amplifiers.shutdown()[topology.output()]
+        .output()
+        .last()
+        .unwrap()
 }
 
-fn cal_normal_thrust(phases: &[i32], program: &[i32]) -> i32 {
-    phases.iter().fold(0, |state, &phase| {
-        IntCodeComputer::new(program, phase).run_program(state)
-    })
+/// The highest final signal over every ordering of `phases` in `topology`.
+fn max_thrust(topology: &Topology, phases: &[i64], program: &[i64], run: Runner) -> i64 {
+    phases
+        .iter()
+        .copied()
+        .permutations(phases.len())
+        .map(|perm| run(&topology.clone().with_phases(&perm), program))
+        .max()
+        .unwrap()
 }
 
-fn cal_thurst_with_feedback(phases: &[i32], program: &[i32]) -> i32 {
-    let mut computers: Vec<_> = phases
-        .iter()
-        .map(|&phase| IntCodeComputer::new(program, phase))
-        .collect();
-    let mut input = 0;
-    for i in (0..computers.len()).cycle() {
-        match computers[i].run_one_turn(input) {
-            ResultCode::Output(i) => input = i,
-            ResultCode::Terminated => return input,
-        }
-    }
-    unreachable!()
+fn max_normal_thrust(input: &[i64], run: Runner) -> i64 {
+    max_thrust(&Topology::chain(&[0; 5]), &[0, 1, 2, 3, 4], input, run)
 }
 
-fn read_input(filepath: &Path) -> std::io::Result<Vec<i32>> {
-    Ok(read_to_string(filepath)?
-        .split(',')
-        .filter_map(|s| s.trim().parse::<i32>().ok())
-        .collect())
+fn max_thrust_with_feedback(input: &[i64], run: Runner) -> i64 {
+    max_thrust(&Topology::ring(&[0; 5]), &[5, 6, 7, 8, 9], input, run)
 }
 
-fn part1(input: &[i32]) -> i32 {
-    (0..5)
-        .permutations(5)
-        .map(|perm| cal_normal_thrust(&perm, input))
-        .max()
-        .unwrap()
+fn part1(input: &[i64]) -> i64 {
+    max_normal_thrust(input, run_amplifiers)
 }
 
-fn part2(input: &[i32]) -> i32 {
-    (5..10)
-        .permutations(5)
-        .map(|perm| cal_thurst_with_feedback(&perm, input))
-        .max()
-        .unwrap()
+fn part2(input: &[i64]) -> i64 {
+    max_thrust_with_feedback(input, run_amplifiers)
 }
 
 fn main() -> std::io::Result<()> {
     let filepath = Path::new("./input/input.txt");
-    let input = read_input(filepath)?;
-    println!("part 1: {}", part1(&input));
-    println!("part 2: {}", part2(&input));
+    let input = load_program(filepath)?;
+    let args: Vec<_> = env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--topology") {
+        let path = args.get(pos + 1).expect("--topology needs a file");
+        print!("{}", Topology::load(Path::new(path))?.run(&input));
+    } else if args.iter().any(|arg| arg == "--threaded") {
+        println!(
+            "part 1: {}",
+            max_normal_thrust(&input, run_amplifiers_threaded)
+        );
+        println!(
+            "part 2: {}",
+            max_thrust_with_feedback(&input, run_amplifiers_threaded)
+        );
+    } else {
+        println!("part 1: {}", part1(&input));
+        println!("part 2: {}", part2(&input));
+    }
     Ok(())
 }
 
@@ -254,4 +140,36 @@ mod tests {
         let res = part2(&code);
         assert_eq!(18216, res);
     }
+
+    #[test]
+    fn day7_threaded() {
+        let code = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        assert_eq!(
+            max_normal_thrust(&code, run_amplifiers_threaded),
+            part1(&code)
+        );
+        assert_eq!(
+            max_thrust_with_feedback(&code, run_amplifiers_threaded),
+            part2(&code)
+        );
+    }
+
+    #[test]
+    fn day7_topology_file() {
+        let code = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let topology = Topology::load(Path::new("input/feedback.txt")).unwrap();
+        assert_eq!(topology, Topology::ring(&[9, 8, 7, 6, 5]));
+        let report = topology.run(&code);
+        assert_eq!(report.signal, Some(139_629_729));
+        // A also gets the initial signal, and E's last output after A has halted.
+        let received: Vec<_> = report.traffic.iter().map(|t| t.received).collect();
+        assert_eq!(received, [6, 5, 5, 5, 5]);
+        assert!(report.traffic.iter().all(|t| t.sent == 5 && t.halted));
+    }
 }
@@ -1,6 +1,7 @@
-use std::fs::read_to_string;
 use std::path::Path;
 
+use intcode::{load_program, BoostReport, Diagnostic};
+
 trait AutoExpand {
     type Item;
     fn expandable_get(&mut self, pos: usize) -> &Self::Item;
@@ -177,47 +178,58 @@ impl IntCodeComputer {
         unreachable!()
     }
 
-    fn run_program(&mut self, input: i64) -> Vec<i64> {
+    // Feeds `input`, then each value output, to the program until it halts. Returns every
+    // output with the address of the instruction that output it.
+    fn run_program(&mut self, input: i64) -> Vec<Diagnostic> {
         let mut input = input;
-        while let ResultCode::Output(i) = self.run_one_turn(input) {
-            input = i;
+        let mut outputs = Vec::new();
+        while let ResultCode::Output(value) = self.run_one_turn(input) {
+            let addr = self.inst_pointer - 2;
+            outputs.push(Diagnostic { addr, value });
+            input = value;
         }
-        self.input.to_owned()
+        outputs
     }
 }
 
-fn read_input(filepath: &Path) -> std::io::Result<Vec<i64>> {
-    Ok(read_to_string(filepath)?
-        .split(',')
-        .filter_map(|s| s.trim().parse::<i64>().ok())
-        .collect())
+// Panics with the report if BOOST finds a malfunctioning opcode.
+fn run_boost(input: &[i64], mode: i64) -> i64 {
+    let report = BoostReport::decode(&IntCodeComputer::new(input).run_program(mode));
+    assert!(report.is_pass(), "{}", report);
+    report.keycode.unwrap().value
 }
 
-fn part1(input: &[i64]) -> Vec<i64> {
-    IntCodeComputer::new(input).run_program(1)
+fn part1(input: &[i64]) -> i64 {
+    run_boost(input, 1)
 }
 
-fn part2(input: &[i64]) -> Vec<i64> {
-    IntCodeComputer::new(input).run_program(2)
+fn part2(input: &[i64]) -> i64 {
+    run_boost(input, 2)
 }
 
 fn main() -> std::io::Result<()> {
     let filepath = Path::new("./input/input.txt");
-    let input = read_input(filepath)?;
-    println!("part 1: {:?}", part1(&input));
-    println!("part 2: {:?}", part2(&input));
+    let input = load_program(filepath)?;
+    println!("part 1: {}", part1(&input));
+    println!("part 2: {}", part2(&input));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use intcode::conformance::{certify, Execution};
+    use intcode::fuzz::{self, Config};
+
+    fn outputs(code: &[i64]) -> Vec<i64> {
+        let outputs = IntCodeComputer::new(code).run_program(1);
+        outputs.into_iter().map(|out| out.value).collect()
+    }
 
     #[test]
     fn day9_test1() {
         let code = vec![104, 1_125_899_906_842_624, 99];
-        let res = part1(&code);
-        assert_eq!(res, [1, 1_125_899_906_842_624]);
+        assert_eq!(part1(&code), 1_125_899_906_842_624);
     }
 
     #[test]
@@ -225,17 +237,64 @@ mod tests {
         let code = vec![
             9, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
         ];
-        let res = part1(&code);
-        assert_eq!(
-            res,
-            [1, 9, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
-        );
+        assert_eq!(outputs(&code), code);
     }
 
     #[test]
     fn day9_test3() {
         let code = vec![1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0];
-        let res = part1(&code);
-        assert_eq!(res, [1, 1_219_070_632_396_864]);
+        assert_eq!(part1(&code), 1_219_070_632_396_864);
+    }
+
+    #[test]
+    fn reports_output_addresses() {
+        let code = vec![104, 0, 1102, 2, 3, 9, 4, 9, 99, 0];
+        let outputs = IntCodeComputer::new(&code).run_program(1);
+        let addrs: Vec<_> = outputs.iter().map(|out| out.addr).collect();
+        assert_eq!(addrs, [0, 6]);
+    }
+
+    fn run(program: &[i64], inputs: &[i64]) -> Execution {
+        let mut computer = IntCodeComputer::new(program);
+        let mut inputs = inputs.iter();
+        let mut outputs = Vec::new();
+        while let ResultCode::Output(val) =
+            computer.run_one_turn(inputs.next().copied().unwrap_or(0))
+        {
+            outputs.push(val);
+        }
+        Execution {
+            outputs,
+            memory: computer.program,
+        }
+    }
+
+    // One input is supplied per output, so programs that read two inputs before their first
+    // output run out of input.
+    const EXPECTED: &[&str] = &[
+        "aoc/day7-a-first-amplifier",
+        "aoc/day7-b-first-amplifier",
+        "aoc/day7-c-first-amplifier",
+        "opcodes/input-order",
+    ];
+
+    #[test]
+    fn conformance() {
+        let report = certify(&run, 9).unwrap();
+        assert_eq!(report.failed_names(), EXPECTED, "{}", report);
+    }
+
+    #[test]
+    fn differential_fuzzing() {
+        // Like the conformance failures: reads after an output get the input for that output,
+        // not the next one queued.
+        let report = fuzz::run(&Config::new(9), &[("day9", &run)]).unwrap();
+        assert!(!report.is_clean());
+        let config = Config {
+            reads_after_output: false,
+            ..Config::new(9)
+        };
+        let report = fuzz::run(&config, &[("day9", &run)]).unwrap();
+        assert!(report.is_clean(), "{}", report);
     }
 }
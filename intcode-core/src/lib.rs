@@ -0,0 +1,587 @@
+//! An Intcode computer that never allocates, for `no_std` targets and const evaluation.
+//!
+//! The computer runs in memory the caller provides and buffers input and output in rings of a
+//! fixed capacity. Running out of any of them is a fault rather than a reason to grow. Every
+//! method is a `const fn`, so programs can be run at compile time:
+//!
+//! ```
+//! use intcode_core::{Computer, Stop};
+//!
+//! const SUM: i64 = {
+//!     let mut memory = [3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+//!     let mut computer = Computer::<2, 1>::new(&mut memory);
+//!     assert!(computer.add_input(20).is_ok() && computer.add_input(22).is_ok());
+//!     assert!(matches!(computer.run(), Stop::Halted));
+//!     match computer.pop_output() {
+//!         Some(sum) => sum,
+//!         None => panic!("no output"),
+//!     }
+//! };
+//! assert_eq!(SUM, 42);
+//! ```
+#![no_std]
+
+use core::fmt;
+
+/// Why a program stopped without halting. The computer is left as it was before the faulting
+/// instruction, so a program stopped by `OutputFull` carries on once some output is popped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// ADD or MUL, a relative address or a relative base adjustment overflowed.
+    Overflow {
+        ip: usize,
+        opcode: i64,
+        operands: [i64; 2],
+    },
+    InvalidOpcode {
+        ip: usize,
+        opcode: i64,
+    },
+    InvalidMode {
+        ip: usize,
+        inst: i64,
+    },
+    /// A read, write or jump to a negative address.
+    InvalidAddress {
+        ip: usize,
+        addr: i64,
+    },
+    /// An instruction tried to write to an immediate-mode parameter.
+    ImmediateWrite {
+        ip: usize,
+        inst: i64,
+    },
+    /// A read, write or jump past the end of the memory the computer was given.
+    OutOfMemory {
+        ip: usize,
+        addr: i64,
+    },
+    /// An output with the output ring full.
+    OutputFull {
+        ip: usize,
+    },
+}
+
+impl Fault {
+    pub const fn ip(&self) -> usize {
+        match *self {
+            Fault::Overflow { ip, .. }
+            | Fault::InvalidOpcode { ip, .. }
+            | Fault::InvalidMode { ip, .. }
+            | Fault::InvalidAddress { ip, .. }
+            | Fault::ImmediateWrite { ip, .. }
+            | Fault::OutOfMemory { ip, .. }
+            | Fault::OutputFull { ip } => ip,
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Overflow {
+                ip,
+                opcode,
+                operands: [fst, snd],
+            } => write!(
+                f,
+                "overflow at {}: opcode {} with operands {} and {}",
+                ip, opcode, fst, snd
+            ),
+            Fault::InvalidOpcode { ip, opcode } => write!(
+                f,
+                "invalid opcode at {}: opcode must be 1, 2, 3, 4, 5, 6, 7, 8, 9 or 99, got {}",
+                ip, opcode
+            ),
+            Fault::InvalidMode { ip, inst } => write!(
+                f,
+                "invalid mode at {}: modes must be 0, 1 or 2, got instruction {}",
+                ip, inst
+            ),
+            Fault::InvalidAddress { ip, addr } => {
+                write!(f, "invalid address at {}: {}", ip, addr)
+            }
+            Fault::ImmediateWrite { ip, inst } => write!(
+                f,
+                "write to an immediate parameter at {}: instruction {}",
+                ip, inst
+            ),
+            Fault::OutOfMemory { ip, addr } => {
+                write!(f, "out of memory at {}: address {}", ip, addr)
+            }
+            Fault::OutputFull { ip } => write!(f, "output full at {}", ip),
+        }
+    }
+}
+
+/// A ring with room for `N` values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ring<const N: usize> {
+    cells: [i64; N],
+    start: usize,
+    len: usize,
+}
+
+/// The value that did not fit in a full ring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full(pub i64);
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Self {
+            cells: [0; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub const fn push(&mut self, val: i64) -> Result<(), Full> {
+        if self.is_full() {
+            return Err(Full(val));
+        }
+        self.cells[(self.start + self.len) % N] = val;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub const fn pop(&mut self) -> Option<i64> {
+        if self.is_empty() {
+            return None;
+        }
+        let val = self.cells[self.start];
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        Some(val)
+    }
+
+    pub const fn peek(&self) -> Option<i64> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.cells[self.start])
+        }
+    }
+}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why `run` handed control back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    /// The program wants input and the input ring is empty.
+    NeedInput,
+    Fault(Fault),
+}
+
+/// The mode digit of parameter `param` of `inst`, taken like the shared computer does, with the
+/// sign of `inst`.
+const fn mode(inst: i64, param: usize) -> i64 {
+    inst % 100_000 / 10_i64.pow(param as u32 + 1) % 10
+}
+
+// `?` is not available in const fns.
+macro_rules! attempt {
+    ($result:expr) => {
+        match $result {
+            Ok(val) => val,
+            Err(fault) => return Err(fault),
+        }
+    };
+}
+
+/// An Intcode computer over a caller's memory, with room for `IN` queued inputs and `OUT`
+/// unread outputs.
+#[derive(Debug)]
+pub struct Computer<'m, const IN: usize, const OUT: usize> {
+    memory: &'m mut [i64],
+    inst_pointer: usize,
+    relative_base: i64,
+    input: Ring<IN>,
+    output: Ring<OUT>,
+    is_halted: bool,
+}
+
+impl<'m, const IN: usize, const OUT: usize> Computer<'m, IN, OUT> {
+    /// A computer whose memory starts out as `memory`, which should hold the program followed by
+    /// as many zeros as the program needs room.
+    pub const fn new(memory: &'m mut [i64]) -> Self {
+        Self {
+            memory,
+            inst_pointer: 0,
+            relative_base: 0,
+            input: Ring::new(),
+            output: Ring::new(),
+            is_halted: false,
+        }
+    }
+
+    /// Copies `program` to the start of `memory` and zeroes the rest.
+    pub const fn load(program: &[i64], memory: &'m mut [i64]) -> Result<Self, Fault> {
+        if program.len() > memory.len() {
+            return Err(Fault::OutOfMemory {
+                ip: 0,
+                addr: memory.len() as i64,
+            });
+        }
+        let mut addr = 0;
+        while addr < memory.len() {
+            memory[addr] = if addr < program.len() {
+                program[addr]
+            } else {
+                0
+            };
+            addr += 1;
+        }
+        Ok(Self::new(memory))
+    }
+
+    pub const fn add_input(&mut self, val: i64) -> Result<(), Full> {
+        self.input.push(val)
+    }
+
+    /// The oldest output not yet popped.
+    pub const fn pop_output(&mut self) -> Option<i64> {
+        self.output.pop()
+    }
+
+    pub const fn input(&self) -> &Ring<IN> {
+        &self.input
+    }
+
+    pub const fn output(&self) -> &Ring<OUT> {
+        &self.output
+    }
+
+    pub const fn memory(&self) -> &[i64] {
+        self.memory
+    }
+
+    pub const fn inst_pointer(&self) -> usize {
+        self.inst_pointer
+    }
+
+    pub const fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub const fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    /// Runs until the program halts, needs input or faults.
+    pub const fn run(&mut self) -> Stop {
+        loop {
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+        }
+    }
+
+    /// Executes a single instruction. Returns `None` when the program simply moved on to the next
+    /// one.
+    pub const fn step(&mut self) -> Option<Stop> {
+        if self.is_halted {
+            return Some(Stop::Halted);
+        }
+        match self.execute() {
+            Ok(stop) => stop,
+            Err(fault) => Some(Stop::Fault(fault)),
+        }
+    }
+
+    const fn address(&self, addr: i64) -> Result<usize, Fault> {
+        if addr < 0 {
+            Err(Fault::InvalidAddress {
+                ip: self.inst_pointer,
+                addr,
+            })
+        } else if addr as u64 >= self.memory.len() as u64 {
+            Err(Fault::OutOfMemory {
+                ip: self.inst_pointer,
+                addr,
+            })
+        } else {
+            Ok(addr as usize)
+        }
+    }
+
+    const fn read(&self, addr: usize) -> Result<i64, Fault> {
+        if addr < self.memory.len() {
+            Ok(self.memory[addr])
+        } else {
+            Err(Fault::OutOfMemory {
+                ip: self.inst_pointer,
+                addr: addr as i64,
+            })
+        }
+    }
+
+    const fn inst(&self) -> Result<i64, Fault> {
+        self.read(self.inst_pointer)
+    }
+
+    /// The address parameter `param` of the current instruction refers to.
+    const fn param_address(&self, param: usize) -> Result<usize, Fault> {
+        let inst = attempt!(self.inst());
+        let val = attempt!(self.read(self.inst_pointer + param));
+        match mode(inst, param) {
+            0 => self.address(val),
+            2 => match val.checked_add(self.relative_base) {
+                Some(addr) => self.address(addr),
+                None => Err(Fault::Overflow {
+                    ip: self.inst_pointer,
+                    opcode: inst % 100,
+                    operands: [val, self.relative_base],
+                }),
+            },
+            _ => Err(Fault::ImmediateWrite {
+                ip: self.inst_pointer,
+                inst,
+            }),
+        }
+    }
+
+    const fn get(&self, param: usize) -> Result<i64, Fault> {
+        let inst = attempt!(self.inst());
+        if mode(inst, param) == 1 {
+            return self.read(self.inst_pointer + param);
+        }
+        self.read(attempt!(self.param_address(param)))
+    }
+
+    const fn set(&mut self, param: usize, val: i64) -> Result<(), Fault> {
+        let addr = attempt!(self.param_address(param));
+        self.memory[addr] = val;
+        Ok(())
+    }
+
+    // Faults are raised before the instruction writes anything or moves the instruction pointer.
+    const fn execute(&mut self) -> Result<Option<Stop>, Fault> {
+        let inst = attempt!(self.inst());
+        let mut param = 1;
+        while param <= 3 {
+            if mode(inst, param) < 0 || mode(inst, param) > 2 {
+                return Err(Fault::InvalidMode {
+                    ip: self.inst_pointer,
+                    inst,
+                });
+            }
+            param += 1;
+        }
+        let opcode = inst % 100;
+        match opcode {
+            1 | 2 => {
+                let fst = attempt!(self.get(1));
+                let snd = attempt!(self.get(2));
+                let val = if opcode == 1 {
+                    fst.checked_add(snd)
+                } else {
+                    fst.checked_mul(snd)
+                };
+                match val {
+                    Some(val) => attempt!(self.set(3, val)),
+                    None => {
+                        return Err(Fault::Overflow {
+                            ip: self.inst_pointer,
+                            opcode,
+                            operands: [fst, snd],
+                        })
+                    }
+                }
+                self.inst_pointer += 4;
+            }
+            3 => match self.input.peek() {
+                Some(val) => {
+                    attempt!(self.set(1, val));
+                    self.input.pop();
+                    self.inst_pointer += 2;
+                }
+                None => return Ok(Some(Stop::NeedInput)),
+            },
+            4 => {
+                let val = attempt!(self.get(1));
+                if self.output.push(val).is_err() {
+                    return Err(Fault::OutputFull {
+                        ip: self.inst_pointer,
+                    });
+                }
+                self.inst_pointer += 2;
+            }
+            5 | 6 => {
+                let fst = attempt!(self.get(1));
+                let snd = attempt!(self.get(2));
+                if (fst == 0) != (opcode == 5) {
+                    self.inst_pointer = attempt!(self.address(snd));
+                } else {
+                    self.inst_pointer += 3;
+                }
+            }
+            7 | 8 => {
+                let fst = attempt!(self.get(1));
+                let snd = attempt!(self.get(2));
+                let holds = if opcode == 7 { fst < snd } else { fst == snd };
+                attempt!(self.set(3, holds as i64));
+                self.inst_pointer += 4;
+            }
+            9 => {
+                let adjustment = attempt!(self.get(1));
+                match self.relative_base.checked_add(adjustment) {
+                    Some(relative_base) => self.relative_base = relative_base,
+                    None => {
+                        return Err(Fault::Overflow {
+                            ip: self.inst_pointer,
+                            opcode,
+                            operands: [self.relative_base, adjustment],
+                        })
+                    }
+                }
+                self.inst_pointer += 2;
+            }
+            99 => {
+                self.is_halted = true;
+                return Ok(Some(Stop::Halted));
+            }
+            _ => {
+                return Err(Fault::InvalidOpcode {
+                    ip: self.inst_pointer,
+                    opcode,
+                })
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a program at compile time. Panics on anything but integers and commas.
+    const fn parse<const N: usize>(text: &[u8]) -> [i64; N] {
+        let mut program = [0; N];
+        let (mut i, mut addr) = (0, 0);
+        while i < text.len() {
+            let negative = text[i] == b'-';
+            if negative {
+                i += 1;
+            }
+            let mut val = 0;
+            while i < text.len() && text[i].is_ascii_digit() {
+                val = val * 10 + (text[i] - b'0') as i64;
+                i += 1;
+            }
+            program[addr] = if negative { -val } else { val };
+            addr += 1;
+            while i < text.len() && (text[i] == b',' || text[i].is_ascii_whitespace()) {
+                i += 1;
+            }
+        }
+        program
+    }
+
+    const fn count(text: &[u8]) -> usize {
+        let (mut i, mut commas) = (0, 0);
+        while i < text.len() {
+            commas += (text[i] == b',') as usize;
+            i += 1;
+        }
+        commas + 1
+    }
+
+    const DAY2_TEXT: &[u8] = include_bytes!("../../day2/input/input.txt");
+    const DAY2: [i64; count(DAY2_TEXT)] = parse(DAY2_TEXT);
+
+    const fn day2(noun: i64, verb: i64) -> i64 {
+        let mut memory = DAY2;
+        memory[1] = noun;
+        memory[2] = verb;
+        let mut computer = Computer::<0, 0>::new(&mut memory);
+        match computer.run() {
+            Stop::Halted => computer.memory()[0],
+            _ => panic!("day 2 did not halt"),
+        }
+    }
+
+    // Evaluated by the compiler, not by the test.
+    const DAY2_PART1: i64 = day2(12, 2);
+
+    #[test]
+    fn runs_at_compile_time() {
+        assert_eq!(DAY2_PART1, 3085697);
+        assert_eq!(day2(94, 25), 19690720);
+    }
+
+    #[test]
+    fn rings_are_fifo_and_bounded() {
+        let mut ring = Ring::<2>::new();
+        assert_eq!(ring.pop(), None);
+        assert_eq!(ring.push(1), Ok(()));
+        assert_eq!(ring.push(2), Ok(()));
+        assert_eq!(ring.push(3), Err(Full(3)));
+        assert_eq!(ring.pop(), Some(1));
+        assert_eq!(ring.push(3), Ok(()));
+        assert_eq!(
+            (ring.pop(), ring.pop(), ring.pop()),
+            (Some(2), Some(3), None)
+        );
+        assert_eq!(Ring::<0>::new().push(1), Err(Full(1)));
+    }
+
+    // Outputs the first input twice.
+    const ECHO_TWICE: [i64; 8] = [3, 7, 4, 7, 4, 7, 99, 0];
+
+    #[test]
+    fn full_output_faults_until_drained() {
+        let mut memory = [0; 8];
+        let mut computer = Computer::<1, 1>::load(&ECHO_TWICE, &mut memory).unwrap();
+        assert_eq!(computer.run(), Stop::NeedInput);
+        assert_eq!(computer.add_input(7), Ok(()));
+        assert_eq!(computer.add_input(8), Err(Full(8)));
+        assert_eq!(computer.run(), Stop::Fault(Fault::OutputFull { ip: 4 }));
+        assert_eq!(computer.run(), Stop::Fault(Fault::OutputFull { ip: 4 }));
+        assert_eq!(computer.pop_output(), Some(7));
+        assert_eq!(computer.run(), Stop::Halted);
+        assert_eq!(computer.pop_output(), Some(7));
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn memory_is_never_grown() {
+        let mut memory = [0; 6];
+        assert_eq!(
+            Computer::<0, 0>::load(&ECHO_TWICE, &mut memory).err(),
+            Some(Fault::OutOfMemory { ip: 0, addr: 6 })
+        );
+        // Stores to address 10 of a program given 8 cells.
+        let mut memory = [1101, 1, 2, 10, 99, 0, 0, 0];
+        let mut computer = Computer::<0, 0>::new(&mut memory);
+        assert_eq!(
+            computer.run(),
+            Stop::Fault(Fault::OutOfMemory { ip: 0, addr: 10 })
+        );
+        assert_eq!(computer.inst_pointer(), 0);
+        let mut memory = [109, -1, 204, 0, 99];
+        let mut computer = Computer::<0, 1>::new(&mut memory);
+        assert_eq!(
+            computer.run(),
+            Stop::Fault(Fault::InvalidAddress { ip: 2, addr: -1 })
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use intcode::conformance::{certify, Execution};
+use intcode::fuzz::{self, Config};
+use intcode_core::{Computer, Stop};
+
+// Room for every case in the suite and every program the fuzzer generates.
+const MEMORY: usize = 16 * 1024;
+
+fn run(program: &[i64], inputs: &[i64]) -> Execution {
+    let mut memory = vec![0; MEMORY];
+    let mut computer = Computer::<16, 64>::load(program, &mut memory).unwrap();
+    for &val in inputs {
+        computer.add_input(val).unwrap();
+    }
+    let stop = computer.run();
+    assert_eq!(stop, Stop::Halted);
+    let mut outputs = Vec::new();
+    while let Some(val) = computer.pop_output() {
+        outputs.push(val);
+    }
+    Execution {
+        outputs,
+        memory: computer.memory().to_vec(),
+    }
+}
+
+#[test]
+fn conformance() {
+    let report = certify(&run, 9).unwrap();
+    assert!(report.is_certified(), "{}", report);
+}
+
+#[test]
+fn differential_fuzzing() {
+    let report = fuzz::run(&Config::new(9), &[("intcode-core", &run)]).unwrap();
+    assert!(report.is_clean(), "{}", report);
+}
@@ -0,0 +1,121 @@
+//! Times scanning day 19's 50x50 grid with a fresh computer for every drone, with forks of the
+//! computer run up to its first input, and with forks of one run up to each column's second
+//! input. Run with `cargo bench`.
+//!
+//! The drone program reads its coordinates within its first 20 or so instructions, so there is
+//! little for forking to skip and the scans take about as long. The same scans are also timed
+//! on a compiled program that does its work up front, where forking skips nearly all of it.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use intcode::compiler::compile;
+use intcode::{load_program, IntCodeComputer, Prologue};
+
+const ROUNDS: u32 = 20;
+
+/// Counts the points of the 50x50 grid that the program puts in the beam.
+type Scan = fn(&[i64]) -> usize;
+
+// Decides whether (x, y) is in a beam, like the drone program, but only after building a table
+// of squares.
+const SLOW_BEAM: &str = "
+    var squares[64];
+    fn main() {
+        var i = 0;
+        while i < 64 {
+            squares[i] = i * i;
+            i = i + 1;
+        }
+        var x = input();
+        var y = input();
+        output(squares[x] + squares[y] < 2000 && x < y + y);
+    }
+";
+
+fn fresh(program: &[i64]) -> usize {
+    let mut count = 0;
+    for x in 0..50 {
+        for y in 0..50 {
+            let mut computer = IntCodeComputer::new(program);
+            computer.add_input(x);
+            computer.add_input(y);
+            if computer.run_program() == [1] {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn forked(program: &[i64]) -> usize {
+    let prologue = Prologue::new(program);
+    let mut count = 0;
+    for x in 0..50 {
+        for y in 0..50 {
+            let mut computer = prologue.fork();
+            computer.add_input(x);
+            computer.add_input(y);
+            if computer.run_program() == [1] {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn forked_per_column(program: &[i64]) -> usize {
+    let prologue = Prologue::new(program);
+    let mut computer = prologue.fork();
+    let mut count = 0;
+    for x in 0..50 {
+        let column = prologue.then(&[x]);
+        for y in 0..50 {
+            column.fork_into(&mut computer);
+            computer.add_input(y);
+            if computer.run_program() == [1] {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn time(scan: Scan, program: &[i64], expected: usize) -> Duration {
+    assert_eq!(scan(program), expected);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        scan(program);
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn compare(name: &str, program: &[i64], expected: usize) {
+    let prologue = Prologue::new(program);
+    println!(
+        "{}: {} instructions before the first input, {} before the second",
+        name,
+        prologue.steps(),
+        prologue.then(&[0]).steps()
+    );
+    let baseline = time(fresh, program, expected);
+    println!("  {:<18} {:>10.2?}", "fresh computers:", baseline);
+    let scans: [(&str, Scan); 2] = [("fork:", forked), ("fork per column:", forked_per_column)];
+    for (scan_name, scan) in scans {
+        let elapsed = time(scan, program, expected);
+        println!(
+            "  {:<18} {:>10.2?} ({:.2}x)",
+            scan_name,
+            elapsed,
+            baseline.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    let program = load_program(Path::new("../day19/input/input.txt")).unwrap();
+    compare("day 19", &program, 169);
+    let program = compile(SLOW_BEAM).unwrap();
+    let expected = fresh(&program);
+    compare("slow beam", &program, expected);
+}
@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::cell::Cell;
+
+/// Output of an ASCII program, split into text lines and the values that are not ASCII
+/// characters (usually the puzzle answer).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsciiOutput<C = i64> {
+    pub lines: Vec<String>,
+    pub values: Vec<C>,
+}
+
+impl<C> Default for AsciiOutput<C> {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<C> AsciiOutput<C> {
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+/// Returned when the program halts or blocks on input without printing the expected prompt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PromptError<C = i64> {
+    pub prompt: String,
+    pub output: AsciiOutput<C>,
+}
+
+impl<C> fmt::Display for PromptError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected prompt {:?}, got:\n{}",
+            self.prompt,
+            self.output.text()
+        )
+    }
+}
+
+impl<C: fmt::Debug> Error for PromptError<C> {}
+
+pub(crate) fn to_ascii<C: Cell>(val: &C) -> Option<char> {
+    match val.to_i64()? {
+        val @ 0..=127 => Some(char::from(val as u8)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::computer::IntCodeComputer;
+    use crate::machine::Machine;
+
+    // Prints "Name:", reads characters up to a newline and echoes them back, then prints 1000.
+    pub(crate) const GREETER: [i64; 41] = [
+        104, 78, 104, 97, 104, 109, 104, 101, 104, 58, 104, 10, 3, 100, 1008, 100, 10, 101, 1005,
+        101, 28, 4, 100, 1105, 1, 12, 0, 0, 104, 10, 104, 1000, 99, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn read_ascii_splits_text_and_values() {
+        let mut computer = IntCodeComputer::new(&GREETER);
+        let greeting = computer.wait_for_prompt("Name:").unwrap();
+        assert_eq!(greeting.lines, ["Name:"]);
+
+        computer.send_line("Bob");
+        let output = computer.read_ascii();
+        assert_eq!(output.lines, ["Bob"]);
+        assert_eq!(output.values, [1000]);
+    }
+
+    #[test]
+    fn missing_prompt_is_an_error() {
+        let mut computer = IntCodeComputer::new(&GREETER);
+        let err = computer.respond("Password:", &["hunter2"]).unwrap_err();
+        assert_eq!(err.output.lines, ["Name:"]);
+        assert!(computer.is_blocked());
+    }
+}
@@ -0,0 +1,292 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cell::Cell;
+
+/// Arbitrary-precision integer cell. ADD and MUL never overflow, so every `Arithmetic` policy
+/// gives the exact result.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    // Base 2^32 digits, least significant first, without trailing zeros. Zero has no digits and
+    // is never negative.
+    magnitude: Vec<u32>,
+}
+
+fn trim(mut magnitude: Vec<u32>) -> Vec<u32> {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = u64::from(*a.get(i).unwrap_or(&0)) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    res.push(carry as u32);
+    trim(res)
+}
+
+// Requires `a >= b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &digit) in a.iter().enumerate() {
+        let diff = i64::from(digit) - i64::from(*b.get(i).unwrap_or(&0)) - borrow;
+        borrow = if diff < 0 { 1 } else { 0 };
+        res.push((diff + (borrow << 32)) as u32);
+    }
+    trim(res)
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = vec![0; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = u64::from(res[i + j]) + u64::from(x) * u64::from(y) + carry;
+            res[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(res)
+}
+
+fn div_rem_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0; a.len()];
+    let mut rem = 0;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | u64::from(a[i]);
+        quotient[i] = (cur / u64::from(divisor)) as u32;
+        rem = cur % u64::from(divisor);
+    }
+    (trim(quotient), rem as u32)
+}
+
+impl BigInt {
+    fn new(negative: bool, magnitude: Vec<u32>) -> Self {
+        let magnitude = trim(magnitude);
+        Self {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    fn to_u64_magnitude(&self) -> Option<u64> {
+        match *self.magnitude.as_slice() {
+            [] => Some(0),
+            [low] => Some(u64::from(low)),
+            [low, high] => Some(u64::from(high) << 32 | u64::from(low)),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(val: i64) -> Self {
+        let abs = val.unsigned_abs();
+        Self::new(val < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.magnitude.is_empty() {
+            return write!(f, "0");
+        }
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let (quotient, rem) = div_rem_small(&magnitude, 1_000_000_000);
+            chunks.push(rem);
+            magnitude = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseBigIntError(String);
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer {:?}", self.0)
+    }
+}
+
+impl Error for ParseBigIntError {}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError(s.to_string()));
+        }
+        let mut magnitude = Vec::new();
+        for digit in digits.bytes() {
+            magnitude = add_magnitude(
+                &mul_magnitude(&magnitude, &[10]),
+                &[u32::from(digit - b'0')],
+            );
+        }
+        Ok(Self::new(negative, magnitude))
+    }
+}
+
+impl Cell for BigInt {
+    fn to_i64(&self) -> Option<i64> {
+        let abs = self.to_u64_magnitude()?;
+        if self.negative {
+            0_i64.checked_sub_unsigned(abs)
+        } else {
+            i64::try_from(abs).ok()
+        }
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        if self.negative {
+            return None;
+        }
+        usize::try_from(self.to_u64_magnitude()?).ok()
+    }
+
+    fn rem_i64(&self, modulus: i64) -> i64 {
+        let divisor = u32::try_from(modulus.unsigned_abs()).expect("modulus must fit in a u32");
+        let rem = i64::from(div_rem_small(&self.magnitude, divisor).1);
+        if self.negative {
+            -rem
+        } else {
+            rem
+        }
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        if self.negative == other.negative {
+            return Some(Self::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            ));
+        }
+        Some(match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Self::new(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => Self::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        })
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(Self::new(
+            self.negative != other.negative,
+            mul_magnitude(&self.magnitude, &other.magnitude),
+        ))
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        Cell::checked_add(self, other).unwrap()
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        Cell::checked_mul(self, other).unwrap()
+    }
+
+    fn saturating_add(&self, other: &Self) -> Self {
+        Cell::checked_add(self, other).unwrap()
+    }
+
+    fn saturating_mul(&self, other: &Self) -> Self {
+        Cell::checked_mul(self, other).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn big(val: i128) -> BigInt {
+        val.to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn arithmetic_matches_i128() {
+        let mut rng = Rng::new(32);
+        for _ in 0..1000 {
+            let a = rng.below(1 << 62) as i64 - (1 << 61);
+            let b = rng.below(1 << 62) as i64 - (1 << 61);
+            let (x, y) = (BigInt::from(a), BigInt::from(b));
+            let (a, b) = (i128::from(a), i128::from(b));
+            assert_eq!(Cell::checked_add(&x, &y), Some(big(a + b)));
+            assert_eq!(Cell::checked_mul(&x, &y), Some(big(a * b)));
+            assert_eq!(x.cmp(&y), a.cmp(&b));
+            assert_eq!(x.rem_i64(100), (a % 100) as i64);
+        }
+    }
+
+    #[test]
+    fn values_round_trip_through_text() {
+        let huge = "-123456789012345678901234567890123456789";
+        assert_eq!(huge.parse::<BigInt>().unwrap().to_string(), huge);
+        assert_eq!(big(0), BigInt::default());
+        assert_eq!(big(-0).to_string(), "0");
+        assert!("12a".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn conversions_check_range() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(big(i128::from(i64::MAX) + 1).to_i64(), None);
+        assert_eq!(big(-1).to_usize(), None);
+        assert_eq!(big(1 << 70).to_usize(), None);
+        assert_eq!(big(1 << 40).to_usize(), Some(1 << 40));
+    }
+}
@@ -0,0 +1,5 @@
+use std::io::{self, BufReader};
+
+fn main() -> io::Result<()> {
+    intcode::dap::serve(BufReader::new(io::stdin()), io::stdout().lock())
+}
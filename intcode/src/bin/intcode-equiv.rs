@@ -0,0 +1,93 @@
+use std::path::Path;
+use std::process;
+
+use intcode::equivalence::{check, Domain, Values};
+use intcode::{load_program, parse_program};
+
+const USAGE: &str = "\
+usage: intcode-equiv LEFT RIGHT [options]
+
+Runs both programs on the same inputs and reports the first inputs they disagree on.
+
+options:
+  --input VALUES   the values of the next input: 1,2,3, 0..10 or 0..=9; may be repeated
+  --case 1,2,3     a sequence of inputs to run, may be repeated instead of --input
+  --samples N      draw N cases at random from the --input values instead of trying them all
+  --seed N         the seed cases are drawn from, 0 by default
+  --max-steps N    stop each run after N instructions, 100000 by default
+
+exit codes:
+  0  the programs agree on every case
+  1  bad arguments, or a program could not be read
+  2  the programs differ";
+
+fn number<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {:?}", name, value))
+}
+
+fn parse_args() -> Result<(Vec<String>, Domain, u64), String> {
+    let mut args = std::env::args().skip(1);
+    let (mut programs, mut inputs, mut cases) = (Vec::new(), Vec::new(), Vec::new());
+    let (mut samples, mut seed, mut max_steps) = (None, 0, 100_000);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let values = args.next().ok_or("--input needs a value")?;
+                inputs.push(values.parse::<Values>()?);
+            }
+            "--case" => {
+                let case = args.next().ok_or("--case needs a value")?;
+                cases.push(parse_program(&case).map_err(|err| err.to_string())?);
+            }
+            "--samples" => samples = Some(number("--samples", args.next())?),
+            "--seed" => seed = number("--seed", args.next())?,
+            "--max-steps" => max_steps = number("--max-steps", args.next())?,
+            arg if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => programs.push(arg),
+        }
+    }
+    if programs.len() != 2 {
+        return Err("expected two programs".to_string());
+    }
+    let domain = match (cases.is_empty(), samples) {
+        (false, _) if !inputs.is_empty() || samples.is_some() => {
+            return Err("--case cannot be combined with --input or --samples".to_string())
+        }
+        (false, _) => Domain::Explicit(cases),
+        (true, Some(samples)) => Domain::Sampled {
+            inputs,
+            samples,
+            seed,
+        },
+        (true, None) => Domain::Exhaustive(inputs),
+    };
+    Ok((programs, domain, max_steps))
+}
+
+fn main() {
+    let (paths, domain, max_steps) = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("intcode-equiv: {}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+    let mut programs = Vec::new();
+    for path in &paths {
+        match load_program(Path::new(path)) {
+            Ok(program) => programs.push(program),
+            Err(err) => {
+                eprintln!("intcode-equiv: {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    let report = check(&programs[0], &programs[1], &domain, max_steps);
+    println!("{}", report);
+    if !report.is_equivalent() {
+        process::exit(2);
+    }
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use intcode::load_program;
+use intcode::optimize::optimize;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("usage: intcode-opt PROGRAM [OUTPUT]");
+            process::exit(1);
+        }
+    };
+    let program = match load_program(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("intcode-opt: {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    };
+    let optimized = optimize(&program);
+    print!("{}", optimized);
+    if let Some(output) = args.next() {
+        let cells: Vec<_> = optimized
+            .program
+            .iter()
+            .map(|val| val.to_string())
+            .collect();
+        if let Err(err) = fs::write(&output, cells.join(",") + "\n") {
+            eprintln!("intcode-opt: {}: {}", output, err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+use std::io;
+
+fn main() -> io::Result<()> {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    intcode::remote::serve(stdin.lock(), stdout.lock())
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+use std::process;
+
+use intcode::load_program;
+use intcode::strings::{extract, Config};
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("usage: intcode-strings PROGRAM");
+            process::exit(1);
+        }
+    };
+    match load_program(&path) {
+        Ok(program) => print!("{}", extract(&program, &Config::default())),
+        Err(err) => {
+            eprintln!("intcode-strings: {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
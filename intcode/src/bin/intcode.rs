@@ -0,0 +1,32 @@
+use std::io::{self, BufReader};
+use std::process;
+
+use intcode::cli::{self, Exit, USAGE};
+
+fn main() {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("intcode: {}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+    let stdin = BufReader::new(io::stdin());
+    let exit = match cli::run(&options, stdin, io::stdout()) {
+        Ok(exit) => exit,
+        Err(err) => {
+            eprintln!("intcode: {}", err);
+            process::exit(1);
+        }
+    };
+    match &exit {
+        Exit::Halted => {}
+        Exit::Fault(fault) => eprintln!("intcode: {}", fault),
+        Exit::OutOfSteps => eprintln!(
+            "intcode: stopped after {} steps",
+            options.max_steps.unwrap_or(0)
+        ),
+        Exit::OutOfInput => eprintln!("intcode: the program is waiting for input, but stdin ended"),
+    }
+    process::exit(exit.code());
+}
@@ -0,0 +1,166 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// A value held in one memory cell of the computer.
+///
+/// `i64` is what the puzzles need; `i128` and `BigInt` give programs more headroom before ADD
+/// and MUL overflow.
+pub trait Cell: Clone + Debug + Display + Default + Eq + Ord + Hash + From<i64> {
+    fn to_i64(&self) -> Option<i64>;
+    fn to_usize(&self) -> Option<usize>;
+
+    /// The remainder of dividing by `modulus`, with the sign of `self`, like `%` on integers.
+    /// Instructions are decoded from their lowest digits.
+    fn rem_i64(&self, modulus: i64) -> i64;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+    fn saturating_add(&self, other: &Self) -> Self;
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+macro_rules! primitive_cell {
+    ($ty:ty) => {
+        impl Cell for $ty {
+            fn to_i64(&self) -> Option<i64> {
+                std::convert::TryFrom::try_from(*self).ok()
+            }
+
+            fn to_usize(&self) -> Option<usize> {
+                std::convert::TryFrom::try_from(*self).ok()
+            }
+
+            fn rem_i64(&self, modulus: i64) -> i64 {
+                (*self % <$ty>::from(modulus)) as i64
+            }
+
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                <$ty>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other: &Self) -> Option<Self> {
+                <$ty>::checked_mul(*self, *other)
+            }
+
+            fn wrapping_add(&self, other: &Self) -> Self {
+                <$ty>::wrapping_add(*self, *other)
+            }
+
+            fn wrapping_mul(&self, other: &Self) -> Self {
+                <$ty>::wrapping_mul(*self, *other)
+            }
+
+            fn saturating_add(&self, other: &Self) -> Self {
+                <$ty>::saturating_add(*self, *other)
+            }
+
+            fn saturating_mul(&self, other: &Self) -> Self {
+                <$ty>::saturating_mul(*self, *other)
+            }
+        }
+    };
+}
+
+primitive_cell!(i64);
+primitive_cell!(i128);
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::bigint::BigInt;
+    use crate::computer::{IntCodeComputer, ResultCode};
+    use crate::fault::Fault;
+    use crate::load::load_program;
+
+    fn load(day: &str) -> Vec<i64> {
+        let filepath = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join(day)
+            .join("input/input.txt");
+        load_program(&filepath).unwrap()
+    }
+
+    // Feeds `inputs` one at a time and records what the program printed and how it stopped.
+    fn transcript<C: Cell>(program: &[i64], inputs: &[i64]) -> Vec<String> {
+        let mut computer = IntCodeComputer::<C>::from_i64s(program);
+        let mut inputs = inputs.iter();
+        let mut transcript = Vec::new();
+        loop {
+            match computer.run_one_turn() {
+                ResultCode::Output(val) => transcript.push(val.to_string()),
+                ResultCode::Input => match inputs.next() {
+                    Some(&val) => computer.add_input(C::from(val)),
+                    None => return transcript,
+                },
+                ResultCode::Terminated => return transcript,
+                ResultCode::Fault(fault) => {
+                    transcript.push(fault.to_string());
+                    return transcript;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn puzzle_programs_agree_across_cell_types() {
+        let springscript: Vec<_> = "NOT A J\nWALK\n".bytes().map(i64::from).collect();
+        let cases: Vec<(&str, Vec<i64>)> = vec![
+            ("day5", vec![1]),
+            ("day5", vec![5]),
+            ("day7", vec![4, 0]),
+            ("day9", vec![1]),
+            ("day9", vec![2]),
+            ("day11", vec![0; 50]),
+            ("day13", vec![]),
+            ("day15", [1, 4, 2, 3].repeat(20)),
+            ("day17", vec![]),
+            ("day19", vec![10, 20]),
+            ("day21", springscript),
+            ("day23", vec![0, -1, -1, -1]),
+        ];
+        for (day, inputs) in cases {
+            let program = load(day);
+            let expected = transcript::<i64>(&program, &inputs);
+            assert!(!expected.is_empty(), "{} printed nothing", day);
+            assert_eq!(transcript::<i128>(&program, &inputs), expected, "{}", day);
+            assert_eq!(transcript::<BigInt>(&program, &inputs), expected, "{}", day);
+        }
+    }
+
+    #[test]
+    fn large_values_fit_in_wider_cells() {
+        // Squares 2^40 and prints the result.
+        let program = [2, 7, 7, 7, 4, 7, 99, 1 << 40];
+        let mut computer = IntCodeComputer::new(&program);
+        assert!(matches!(
+            computer.run_one_turn(),
+            ResultCode::Fault(Fault::Overflow { ip: 0, .. })
+        ));
+        assert_eq!(
+            IntCodeComputer::<i128>::from_i64s(&program).run_program(),
+            [1 << 80]
+        );
+        assert_eq!(
+            IntCodeComputer::<BigInt>::from_i64s(&program).run_program()[0].to_string(),
+            "1208925819614629174706176"
+        );
+    }
+
+    #[test]
+    fn addresses_must_fit_in_usize() {
+        let huge = "100000000000000000000".parse::<BigInt>().unwrap();
+        let program = [BigInt::from(4), huge.clone(), BigInt::from(99)];
+        assert_eq!(
+            IntCodeComputer::from_cells(&program).run_one_turn(),
+            ResultCode::Fault(Fault::InvalidAddress { ip: 0, addr: huge })
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::ascii::to_ascii;
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::fault::Fault;
+use crate::json::Json;
+use crate::load::{load_program, parse_program};
+
+pub const USAGE: &str = "\
+usage: intcode run PROGRAM [options]
+
+Runs an Intcode program, reading its input from stdin whenever it runs out of queued input.
+
+options:
+  --ascii             read and write text instead of one number per line; values that are not
+                      ASCII characters are written as numbers on their own line
+  --input 1,2,3       queue inputs before reading stdin
+  --patch ADDR=VALUE  set a memory cell before running, may be repeated
+  --max-steps N       stop after N instructions
+  --trace FILE        write one JSON object per instruction run to FILE
+
+exit codes:
+  0  the program halted
+  1  bad arguments, or the program could not be read
+  2  the program faulted
+  3  the program ran for --max-steps instructions without halting
+  4  the program wanted input after stdin ended";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub program: PathBuf,
+    pub ascii: bool,
+    pub input: Vec<i64>,
+    pub patches: Vec<(usize, i64)>,
+    pub max_steps: Option<u64>,
+    pub trace: Option<PathBuf>,
+}
+
+/// Parses the arguments after the binary name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("run") => {}
+        Some(command) => return Err(format!("unknown command {:?}", command)),
+        None => return Err("missing command".to_string()),
+    }
+
+    let mut options = Options::default();
+    let mut program = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--input" => {
+                let values = value("--input")?;
+                let values: Vec<i64> = parse_program(&values).map_err(|err| err.to_string())?;
+                options.input.extend(values);
+            }
+            "--patch" => {
+                let patch = value("--patch")?;
+                let (addr, val) = patch
+                    .split_once('=')
+                    .and_then(|(addr, val)| Some((addr.parse().ok()?, val.parse().ok()?)))
+                    .ok_or_else(|| format!("--patch expects ADDR=VALUE, got {:?}", patch))?;
+                options.patches.push((addr, val));
+            }
+            "--max-steps" => {
+                let steps = value("--max-steps")?;
+                let steps = steps
+                    .parse()
+                    .map_err(|_| format!("--max-steps expects a number, got {:?}", steps))?;
+                options.max_steps = Some(steps);
+            }
+            "--trace" => options.trace = Some(value("--trace")?.into()),
+            arg if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            arg if program.is_none() => program = Some(PathBuf::from(arg)),
+            arg => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    options.program = program.ok_or("missing program")?;
+    Ok(options)
+}
+
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exit {
+    Halted,
+    Fault(Fault),
+    OutOfSteps,
+    OutOfInput,
+}
+
+impl Exit {
+    /// The process exit code, as listed in `USAGE`.
+    pub fn code(&self) -> i32 {
+        match self {
+            Exit::Halted => 0,
+            Exit::Fault(_) => 2,
+            Exit::OutOfSteps => 3,
+            Exit::OutOfInput => 4,
+        }
+    }
+}
+
+/// Reads one line of stdin as inputs. Returns false at the end of stdin.
+fn read_input<R: BufRead>(
+    options: &Options,
+    computer: &mut IntCodeComputer,
+    stdin: &mut R,
+) -> io::Result<bool> {
+    let mut line = String::new();
+    if stdin.read_line(&mut line)? == 0 {
+        return Ok(false);
+    }
+    if options.ascii {
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        for byte in line.bytes().filter(|&byte| byte != b'\r') {
+            computer.add_input(i64::from(byte));
+        }
+    } else {
+        let values: Vec<i64> = parse_program(&line)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        for val in values {
+            computer.add_input(val);
+        }
+    }
+    Ok(true)
+}
+
+/// Runs the program in `options`, reading stdin a line at a time whenever the program runs out of
+/// input, so it can be driven interactively.
+pub fn run<R: BufRead, W: Write>(
+    options: &Options,
+    mut stdin: R,
+    mut stdout: W,
+) -> io::Result<Exit> {
+    let mut program = load_program(&options.program)?;
+    let len = program.len();
+    for &(addr, val) in &options.patches {
+        let cell = program.get_mut(addr).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot patch address {}, the program has {} cells",
+                    addr, len
+                ),
+            )
+        })?;
+        *cell = val;
+    }
+    let mut trace = match &options.trace {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let mut computer = IntCodeComputer::new(&program);
+    for &val in &options.input {
+        computer.add_input(val);
+    }
+    let mut steps = 0;
+    loop {
+        if options.max_steps == Some(steps) {
+            return Ok(Exit::OutOfSteps);
+        }
+        let (ip, relative_base) = (computer.inst_pointer(), computer.relative_base());
+        let inst = computer.memory().get(ip).copied().unwrap_or(0);
+        let input = computer.queued_input().first().copied();
+        let result = computer.step();
+        if result != Some(ResultCode::Input) {
+            steps += 1;
+        }
+
+        if let Some(trace) = &mut trace {
+            if result != Some(ResultCode::Input) {
+                let mut entry = vec![
+                    ("step", Json::from(steps as i64)),
+                    ("ip", ip.into()),
+                    ("inst", inst.into()),
+                    ("relative_base", relative_base.into()),
+                ];
+                match &result {
+                    Some(ResultCode::Output(val)) => entry.push(("output", (*val).into())),
+                    Some(ResultCode::Fault(fault)) => {
+                        entry.push(("fault", fault.to_string().into()))
+                    }
+                    _ if inst % 100 == 3 => entry.push(("input", input.unwrap_or(0).into())),
+                    _ => {}
+                }
+                writeln!(trace, "{}", Json::object(entry))?;
+            }
+        }
+
+        match result {
+            None => {}
+            Some(ResultCode::Output(val)) => match to_ascii(&val) {
+                Some(ch) if options.ascii => write!(stdout, "{}", ch)?,
+                _ => writeln!(stdout, "{}", val)?,
+            },
+            Some(ResultCode::Input) => {
+                stdout.flush()?;
+                if !read_input(options, &mut computer, &mut stdin)? {
+                    return Ok(Exit::OutOfInput);
+                }
+            }
+            Some(ResultCode::Terminated) => {
+                stdout.flush()?;
+                return Ok(Exit::Halted);
+            }
+            Some(ResultCode::Fault(fault)) => {
+                stdout.flush()?;
+                return Ok(Exit::Fault(fault));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ascii::tests::GREETER;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    /// Numbers the program files, so that tests running at the same time never share one.
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+
+    fn run_with(program: &str, extra: &str, stdin: &str) -> (Exit, String) {
+        let dir = std::env::temp_dir().join(format!("intcode-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.txt", FILES.fetch_add(1, Ordering::Relaxed)));
+        fs::write(&path, program).unwrap();
+        let line = format!("run {} {}", path.display(), extra);
+        let options = parse_args(args(&line)).unwrap();
+        let mut stdout = Vec::new();
+        let exit = run(&options, Cursor::new(stdin), &mut stdout).unwrap();
+        fs::remove_file(&path).unwrap();
+        (exit, String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn runs_numeric_programs() {
+        let boost = fs::read_to_string(Path::new("../day9/input/input.txt")).unwrap();
+        assert_eq!(
+            run_with(&boost, "--input 1", ""),
+            (Exit::Halted, "3454977209\n".to_string())
+        );
+        assert_eq!(
+            run_with(&boost, "", "2\n"),
+            (Exit::Halted, "50120\n".to_string())
+        );
+        // Adds the cells at the patched noun and verb addresses like day 2, then prints address 0.
+        assert_eq!(
+            run_with("1,0,0,0,4,0,99", "--patch 1=6 --patch 2=6", ""),
+            (Exit::Halted, "198\n".to_string())
+        );
+    }
+
+    #[test]
+    fn runs_ascii_programs() {
+        let greeter: Vec<_> = GREETER.iter().map(|val| val.to_string()).collect();
+        assert_eq!(
+            run_with(&greeter.join(","), "--ascii", "Bob\n"),
+            (Exit::Halted, "Name:\nBob\n1000\n".to_string())
+        );
+    }
+
+    #[test]
+    fn exits_on_faults_budgets_and_missing_input() {
+        let (exit, _) = run_with("1,0,0,0,99", "--patch 0=42", "");
+        assert_eq!(exit.code(), 2);
+        let (exit, _) = run_with("1105,1,0", "--max-steps 10", "");
+        assert_eq!(exit, Exit::OutOfSteps);
+        assert_eq!(exit.code(), 3);
+        let (exit, output) = run_with("104,7,3,0,99", "", "");
+        assert_eq!((exit.code(), output.as_str()), (4, "7\n"));
+    }
+
+    #[test]
+    fn traces_every_instruction() {
+        let trace = std::env::temp_dir().join(format!("intcode-trace-{}", std::process::id()));
+        let (exit, _) = run_with(
+            "3,9,102,2,9,9,4,9,99,0",
+            &format!("--input 21 --trace {}", trace.display()),
+            "",
+        );
+        assert_eq!(exit, Exit::Halted);
+        let lines = fs::read_to_string(&trace).unwrap();
+        fs::remove_file(&trace).unwrap();
+        let entries: Vec<_> = lines
+            .lines()
+            .map(|line| Json::parse(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].get("input").as_i64(), Some(21));
+        assert_eq!(entries[2].get("output").as_i64(), Some(42));
+        assert_eq!(entries[3].get("ip").as_i64(), Some(8));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_args(args("walk prog.txt")).is_err());
+        assert!(parse_args(args("run")).is_err());
+        assert!(parse_args(args("run prog.txt --patch 3")).is_err());
+        assert!(parse_args(args("run prog.txt --max-steps")).is_err());
+        assert_eq!(
+            parse_args(args("run prog.txt --input 1,2 --input 3 --ascii")),
+            Ok(Options {
+                program: "prog.txt".into(),
+                ascii: true,
+                input: vec![1, 2, 3],
+                ..Options::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,1218 @@
+//! A compiler for a tiny structured language to Intcode.
+//!
+//! ```text
+//! var count = 0;            // globals are initialized with constants
+//! var primes[100];          // arrays are zeroed
+//!
+//! fn fib(n) {
+//!     if n < 2 { return n; }
+//!     return fib(n - 1) + fib(n - 2);
+//! }
+//!
+//! fn main() {
+//!     var n = input();
+//!     while n > 0 {
+//!         output(fib(n));
+//!         n = n - 1;
+//!     }
+//! }
+//! ```
+//!
+//! Values are integers. Functions take integer arguments and return one, or 0 when they end
+//! without `return`. Arrays can be global or local; an array's name evaluates to its address, so
+//! arrays are passed to functions as addresses that the callee indexes like arrays. There are
+//! `+`, `-`, `*`, the comparisons, `!`, and short-circuit `&&` and `||`, but no division, since
+//! Intcode has none. The builtins are `input()` and `output(value)`. Execution starts at `main`,
+//! and the program halts when `main` returns.
+//!
+//! Every function's frame lives on a stack addressed through the relative base: the return
+//! address, then the parameters, locals and temporaries. Indexing patches the address into the
+//! instruction that reads or writes the element, since Intcode has no indirect addressing.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for CompileError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, CompileError> {
+    Err(CompileError {
+        line,
+        message: message.into(),
+    })
+}
+
+/// Compiles `source` to an Intcode program.
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    let tokens = tokenize(source)?;
+    let items = Parser { tokens, pos: 0 }.program()?;
+    Codegen::new(&items)?.program(&items)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Punct(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Num(val) => write!(f, "{}", val),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Punct(punct) => write!(f, "{}", punct),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+// Two-character punctuation comes first so that `<=` is not read as `<`.
+const PUNCTUATION: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", "[", "]", ",", ";", "=", "<", ">", "+",
+    "-", "*", "!",
+];
+
+const KEYWORDS: [&str; 8] = [
+    "var", "fn", "if", "else", "while", "break", "continue", "return",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line.split("//").next().unwrap_or("").trim_start();
+        while !rest.is_empty() {
+            let len = if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let val = rest[..len].parse().map_err(|_| CompileError {
+                    line: line_no,
+                    message: format!("number {} is too large", &rest[..len]),
+                })?;
+                tokens.push((Token::Num(val), line_no));
+                len
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..len].to_string()), line_no));
+                len
+            } else {
+                match PUNCTUATION.iter().find(|punct| rest.starts_with(*punct)) {
+                    Some(punct) => {
+                        tokens.push((Token::Punct(punct), line_no));
+                        punct.len()
+                    }
+                    None => {
+                        let c = rest.chars().next().unwrap();
+                        return error(line_no, format!("unexpected character {:?}", c));
+                    }
+                }
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+    let last_line = source.lines().count().max(1);
+    tokens.push((Token::End, last_line));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Var(String, usize),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>, usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Stmt {
+    Var(String, Option<Expr>, usize),
+    Array(String, usize, usize),
+    Assign(Expr, Expr, usize),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Break(usize),
+    Continue(usize),
+    Return(Option<Expr>),
+    Block(Vec<Stmt>),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Item {
+    Global(String, i64, usize),
+    GlobalArray(String, usize, usize),
+    Function {
+        name: String,
+        params: Vec<String>,
+        body: Vec<Stmt>,
+        line: usize,
+    },
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matches = match self.peek() {
+            Token::Punct(found) => *found == punct,
+            Token::Ident(found) => found == punct,
+            _ => false,
+        };
+        if matches {
+            self.next();
+        }
+        matches
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), CompileError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            error(
+                self.line(),
+                format!("expected {}, found {}", punct, self.peek()),
+            )
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        match self.next() {
+            Token::Ident(name) if KEYWORDS.contains(&name.as_str()) => {
+                error(self.line(), format!("expected a name, found {}", name))
+            }
+            Token::Ident(name) => Ok(name),
+            token => error(self.line(), format!("expected a name, found {}", token)),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, CompileError> {
+        let negative = self.eat("-");
+        match self.next() {
+            Token::Num(val) if negative => Ok(-val),
+            Token::Num(val) => Ok(val),
+            token => error(self.line(), format!("expected a number, found {}", token)),
+        }
+    }
+
+    fn array_len(&mut self) -> Result<usize, CompileError> {
+        let line = self.line();
+        let len = self.number()?;
+        self.expect("]")?;
+        if len <= 0 {
+            return error(line, "arrays must have at least one element");
+        }
+        Ok(len as usize)
+    }
+
+    fn program(mut self) -> Result<Vec<Item>, CompileError> {
+        let mut items = Vec::new();
+        while *self.peek() != Token::End {
+            let line = self.line();
+            if self.eat("var") {
+                let name = self.ident()?;
+                if self.eat("[") {
+                    items.push(Item::GlobalArray(name, self.array_len()?, line));
+                } else {
+                    let val = if self.eat("=") { self.number()? } else { 0 };
+                    items.push(Item::Global(name, val, line));
+                }
+                self.expect(";")?;
+            } else if self.eat("fn") {
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut params = Vec::new();
+                while !self.eat(")") {
+                    if !params.is_empty() {
+                        self.expect(",")?;
+                    }
+                    params.push(self.ident()?);
+                }
+                let body = self.block()?;
+                items.push(Item::Function {
+                    name,
+                    params,
+                    body,
+                    line,
+                });
+            } else {
+                return error(line, format!("expected var or fn, found {}", self.peek()));
+            }
+        }
+        Ok(items)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if *self.peek() == Token::End {
+                return error(self.line(), "expected }, found end of input");
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, CompileError> {
+        let line = self.line();
+        let stmt = if self.eat("var") {
+            let name = self.ident()?;
+            if self.eat("[") {
+                Stmt::Array(name, self.array_len()?, line)
+            } else if self.eat("=") {
+                Stmt::Var(name, Some(self.expr()?), line)
+            } else {
+                Stmt::Var(name, None, line)
+            }
+        } else if self.eat("if") {
+            return self.if_stmt();
+        } else if self.eat("while") {
+            let cond = self.expr()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        } else if *self.peek() == Token::Punct("{") {
+            return Ok(Stmt::Block(self.block()?));
+        } else if self.eat("break") {
+            Stmt::Break(line)
+        } else if self.eat("continue") {
+            Stmt::Continue(line)
+        } else if self.eat("return") {
+            if *self.peek() == Token::Punct(";") {
+                Stmt::Return(None)
+            } else {
+                Stmt::Return(Some(self.expr()?))
+            }
+        } else {
+            let expr = self.expr()?;
+            if self.eat("=") {
+                Stmt::Assign(expr, self.expr()?, line)
+            } else {
+                Stmt::Expr(expr)
+            }
+        };
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, CompileError> {
+        let cond = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if !self.eat("else") {
+            Vec::new()
+        } else if self.eat("if") {
+            vec![self.if_stmt()?]
+        } else {
+            self.block()?
+        };
+        Ok(Stmt::If(cond, then, otherwise))
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    // Operators by precedence, loosest first.
+    const LEVELS: [&'static [(&'static str, BinOp)]; 5] = [
+        &[("||", BinOp::Or)],
+        &[("&&", BinOp::And)],
+        &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+        &[
+            ("<", BinOp::Lt),
+            (">", BinOp::Gt),
+            ("<=", BinOp::Le),
+            (">=", BinOp::Ge),
+        ],
+        &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    ];
+
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == Self::LEVELS.len() {
+            return self.product();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for &(punct, op) in Self::LEVELS[level] {
+                if self.eat(punct) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.unary()?;
+        while self.eat("*") {
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(BinOp::Mul, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, CompileError> {
+        let line = self.line();
+        let mut expr = match self.next() {
+            Token::Num(val) => Expr::Num(val),
+            Token::Ident(name) if self.eat("(") => {
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expr()?);
+                }
+                Expr::Call(name, args, line)
+            }
+            Token::Ident(name) => Expr::Var(name, line),
+            Token::Punct("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                expr
+            }
+            token => return error(line, format!("expected an expression, found {}", token)),
+        };
+        while self.eat("[") {
+            let index = self.expr()?;
+            self.expect("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+}
+
+/// A cell of the program being generated, which may depend on a symbol, such as a label or a
+/// frame size, that is only known once everything has been generated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Word {
+    Lit(i64),
+    /// `scale` times the value of the symbol, plus `offset`.
+    Sym {
+        sym: usize,
+        scale: i64,
+        offset: i64,
+    },
+}
+
+impl Word {
+    fn sym(sym: usize) -> Self {
+        Word::Sym {
+            sym,
+            scale: 1,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Abs(Word),
+    Imm(Word),
+    Rel(Word),
+}
+
+impl Operand {
+    fn mode(self) -> i64 {
+        match self {
+            Operand::Abs(_) => 0,
+            Operand::Imm(_) => 1,
+            Operand::Rel(_) => 2,
+        }
+    }
+
+    fn word(self) -> Word {
+        match self {
+            Operand::Abs(word) | Operand::Imm(word) | Operand::Rel(word) => word,
+        }
+    }
+
+    fn imm(val: i64) -> Self {
+        Operand::Imm(Word::Lit(val))
+    }
+
+    fn slot(slot: i64) -> Self {
+        Operand::Rel(Word::Lit(slot))
+    }
+}
+
+const ADD: i64 = 1;
+const MUL: i64 = 2;
+const IN: i64 = 3;
+const OUT: i64 = 4;
+const JUMP_IF_TRUE: i64 = 5;
+const JUMP_IF_FALSE: i64 = 6;
+const LESS_THAN: i64 = 7;
+const EQUALS: i64 = 8;
+const ADJUST_BASE: i64 = 9;
+const HALT: i64 = 99;
+
+#[derive(Clone, Copy, Debug)]
+enum Var {
+    /// A scalar at a symbol's address.
+    Global(usize),
+    /// An array starting at a symbol's address.
+    GlobalArray(usize),
+    /// A scalar in a slot of the current frame.
+    Local(i64),
+    /// An array starting at a slot of the current frame.
+    LocalArray(i64),
+}
+
+struct Function {
+    label: usize,
+    arity: usize,
+}
+
+struct Codegen {
+    code: Vec<Word>,
+    symbols: Vec<Option<i64>>,
+    globals: HashMap<String, Var>,
+    functions: HashMap<String, Function>,
+    data: Vec<(usize, Vec<Word>)>,
+    /// Where functions leave their return value.
+    ret: usize,
+    /// A global that mirrors the relative base, for finding local arrays. Only kept up to date
+    /// when some function has a local array.
+    frame_pointer: Option<usize>,
+    // The function being generated.
+    scopes: Vec<HashMap<String, Var>>,
+    next_slot: i64,
+    frame_size: usize,
+    frame: usize,
+    loops: Vec<(usize, usize)>,
+}
+
+fn has_local_array(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Array(..) => true,
+        Stmt::If(_, then, otherwise) => has_local_array(then) || has_local_array(otherwise),
+        Stmt::While(_, body) | Stmt::Block(body) => has_local_array(body),
+        _ => false,
+    })
+}
+
+impl Codegen {
+    fn new(items: &[Item]) -> Result<Self, CompileError> {
+        let mut codegen = Codegen {
+            code: Vec::new(),
+            symbols: Vec::new(),
+            globals: HashMap::new(),
+            functions: HashMap::new(),
+            data: Vec::new(),
+            ret: 0,
+            frame_pointer: None,
+            scopes: Vec::new(),
+            next_slot: 0,
+            frame_size: 0,
+            frame: 0,
+            loops: Vec::new(),
+        };
+        codegen.ret = codegen.data(vec![Word::Lit(0)]);
+        let mut stack_users = false;
+        for item in items {
+            match item {
+                Item::Global(name, val, line) => {
+                    let sym = codegen.data(vec![Word::Lit(*val)]);
+                    codegen.define_global(name, Var::Global(sym), *line)?;
+                }
+                Item::GlobalArray(name, len, line) => {
+                    let sym = codegen.data(vec![Word::Lit(0); *len]);
+                    codegen.define_global(name, Var::GlobalArray(sym), *line)?;
+                }
+                Item::Function {
+                    name,
+                    params,
+                    body,
+                    line,
+                } => {
+                    if codegen.functions.contains_key(name) || is_builtin(name) {
+                        return error(*line, format!("function {} is already defined", name));
+                    }
+                    if name == "main" && !params.is_empty() {
+                        return error(*line, "main cannot take parameters");
+                    }
+                    let label = codegen.symbol();
+                    let arity = params.len();
+                    codegen
+                        .functions
+                        .insert(name.clone(), Function { label, arity });
+                    stack_users |= has_local_array(body);
+                }
+            }
+        }
+        if !codegen.functions.contains_key("main") {
+            return error(1, "there is no main function");
+        }
+        if stack_users {
+            codegen.frame_pointer = Some(codegen.data(vec![Word::Lit(0)]));
+        }
+        Ok(codegen)
+    }
+
+    fn define_global(&mut self, name: &str, var: Var, line: usize) -> Result<(), CompileError> {
+        if self.globals.insert(name.to_string(), var).is_some() {
+            return error(line, format!("global {} is already defined", name));
+        }
+        Ok(())
+    }
+
+    fn symbol(&mut self) -> usize {
+        self.symbols.push(None);
+        self.symbols.len() - 1
+    }
+
+    fn data(&mut self, cells: Vec<Word>) -> usize {
+        let sym = self.symbol();
+        self.data.push((sym, cells));
+        sym
+    }
+
+    fn here(&self) -> i64 {
+        self.code.len() as i64
+    }
+
+    fn place(&mut self, label: usize) {
+        self.symbols[label] = Some(self.here());
+    }
+
+    fn emit(&mut self, opcode: i64, operands: &[Operand]) {
+        let modes = operands
+            .iter()
+            .rev()
+            .fold(0, |modes, operand| modes * 10 + operand.mode());
+        self.code.push(Word::Lit(modes * 100 + opcode));
+        self.code
+            .extend(operands.iter().map(|operand| operand.word()));
+    }
+
+    fn copy(&mut self, from: Operand, to: Operand) {
+        if from != to {
+            self.emit(ADD, &[from, Operand::imm(0), to]);
+        }
+    }
+
+    fn jump(&mut self, label: usize) {
+        self.emit(
+            JUMP_IF_TRUE,
+            &[Operand::imm(1), Operand::Imm(Word::sym(label))],
+        );
+    }
+
+    fn temp(&mut self) -> i64 {
+        self.next_slot += 1;
+        self.frame_size = self.frame_size.max(self.next_slot as usize);
+        self.next_slot - 1
+    }
+
+    fn program(mut self, items: &[Item]) -> Result<Vec<i64>, CompileError> {
+        let stack = self.symbol();
+        self.emit(ADJUST_BASE, &[Operand::Imm(Word::sym(stack))]);
+        if let Some(fp) = self.frame_pointer {
+            self.copy(Operand::Imm(Word::sym(stack)), Operand::Abs(Word::sym(fp)));
+        }
+        // Calls main with a frame of its own at the bottom of the stack.
+        let halt = self.symbol();
+        let main = self.functions["main"].label;
+        self.copy(Operand::Imm(Word::sym(halt)), Operand::slot(0));
+        self.jump(main);
+        self.place(halt);
+        self.emit(HALT, &[]);
+
+        for item in items {
+            if let Item::Function {
+                name, params, body, ..
+            } = item
+            {
+                self.function(name, params, body)?;
+            }
+        }
+
+        for (sym, cells) in std::mem::take(&mut self.data) {
+            self.place(sym);
+            self.code.extend(cells);
+        }
+        self.place(stack);
+        let symbols = self.symbols;
+        Ok(self
+            .code
+            .into_iter()
+            .map(|word| match word {
+                Word::Lit(val) => val,
+                Word::Sym { sym, scale, offset } => scale * symbols[sym].unwrap() + offset,
+            })
+            .collect())
+    }
+
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &[Stmt],
+    ) -> Result<(), CompileError> {
+        let label = self.functions[name].label;
+        self.place(label);
+        // Slot 0 holds the return address, and the parameters follow it.
+        let mut scope = HashMap::new();
+        for (i, param) in params.iter().enumerate() {
+            scope.insert(param.clone(), Var::Local(i as i64 + 1));
+        }
+        self.scopes = vec![scope];
+        self.next_slot = params.len() as i64 + 1;
+        self.frame_size = self.next_slot as usize;
+        self.frame = self.symbol();
+        self.block(body)?;
+        self.ret(Operand::imm(0));
+        self.symbols[self.frame] = Some(self.frame_size as i64);
+        Ok(())
+    }
+
+    fn ret(&mut self, val: Operand) {
+        self.copy(val, Operand::Abs(Word::sym(self.ret)));
+        self.emit(JUMP_IF_TRUE, &[Operand::imm(1), Operand::slot(0)]);
+    }
+
+    fn lookup(&self, name: &str, line: usize) -> Result<Var, CompileError> {
+        let scopes = self.scopes.iter().rev();
+        match scopes
+            .filter_map(|scope| scope.get(name))
+            .chain(self.globals.get(name))
+            .next()
+        {
+            Some(&var) => Ok(var),
+            None => error(line, format!("{} is not defined", name)),
+        }
+    }
+
+    fn declare(&mut self, name: &str, var: Var, line: usize) -> Result<(), CompileError> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.insert(name.to_string(), var).is_some() {
+            return error(line, format!("{} is already defined", name));
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+        match stmt {
+            Stmt::Var(name, init, line) => {
+                let val = match init {
+                    Some(init) => self.expr(init)?,
+                    None => Operand::imm(0),
+                };
+                self.next_slot = mark;
+                let slot = self.temp();
+                self.copy(val, Operand::slot(slot));
+                self.declare(name, Var::Local(slot), *line)?;
+                return Ok(());
+            }
+            Stmt::Array(name, len, line) => {
+                let slot = self.next_slot;
+                for _ in 0..*len {
+                    let elem = self.temp();
+                    self.copy(Operand::imm(0), Operand::slot(elem));
+                }
+                self.declare(name, Var::LocalArray(slot), *line)?;
+                return Ok(());
+            }
+            Stmt::Assign(target, val, line) => match target {
+                Expr::Var(name, _) => {
+                    let to = match self.lookup(name, *line)? {
+                        Var::Global(sym) => Operand::Abs(Word::sym(sym)),
+                        Var::Local(slot) => Operand::slot(slot),
+                        Var::GlobalArray(_) | Var::LocalArray(_) => {
+                            return error(*line, format!("cannot assign to array {}", name));
+                        }
+                    };
+                    let val = self.expr(val)?;
+                    self.copy(val, to);
+                }
+                Expr::Index(base, index) => {
+                    let val = self.expr(val)?;
+                    let base = self.expr(base)?;
+                    let index = self.expr(index)?;
+                    // Patches the address into the destination of the copy that follows.
+                    let patch = Word::Lit(self.here() + 4 + 3);
+                    self.emit(ADD, &[base, index, Operand::Abs(patch)]);
+                    self.emit(ADD, &[val, Operand::imm(0), Operand::Abs(Word::Lit(0))]);
+                }
+                _ => return error(*line, "can only assign to a variable or an array element"),
+            },
+            Stmt::If(cond, then, otherwise) => {
+                let (other, end) = (self.symbol(), self.symbol());
+                let cond = self.expr(cond)?;
+                self.next_slot = mark;
+                self.emit(JUMP_IF_FALSE, &[cond, Operand::Imm(Word::sym(other))]);
+                self.block(then)?;
+                if !otherwise.is_empty() {
+                    self.jump(end);
+                }
+                self.place(other);
+                self.block(otherwise)?;
+                self.place(end);
+            }
+            Stmt::While(cond, body) => {
+                let (top, end) = (self.symbol(), self.symbol());
+                self.place(top);
+                let cond = self.expr(cond)?;
+                self.next_slot = mark;
+                self.emit(JUMP_IF_FALSE, &[cond, Operand::Imm(Word::sym(end))]);
+                self.loops.push((top, end));
+                self.block(body)?;
+                self.loops.pop();
+                self.jump(top);
+                self.place(end);
+            }
+            Stmt::Break(line) | Stmt::Continue(line) => {
+                let &(top, end) = match self.loops.last() {
+                    Some(labels) => labels,
+                    None => return error(*line, "break and continue must be inside a loop"),
+                };
+                self.jump(if let Stmt::Break(_) = stmt { end } else { top });
+            }
+            Stmt::Return(val) => {
+                let val = match val {
+                    Some(val) => self.expr(val)?,
+                    None => Operand::imm(0),
+                };
+                self.ret(val);
+            }
+            Stmt::Block(stmts) => self.block(stmts)?,
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+            }
+        }
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    /// Generates code that evaluates `expr`, and returns where its value can be read. Values are
+    /// computed into temporary slots above `next_slot`, which is left just past the result.
+    fn expr(&mut self, expr: &Expr) -> Result<Operand, CompileError> {
+        let mark = self.next_slot;
+        let val = match expr {
+            Expr::Num(val) => Operand::imm(*val),
+            Expr::Var(name, line) => match self.lookup(name, *line)? {
+                Var::Global(sym) => Operand::Abs(Word::sym(sym)),
+                Var::GlobalArray(sym) => Operand::Imm(Word::sym(sym)),
+                Var::Local(slot) => Operand::slot(slot),
+                Var::LocalArray(slot) => {
+                    let fp = Word::sym(self.frame_pointer.unwrap());
+                    let temp = Operand::slot(self.temp());
+                    self.emit(ADD, &[Operand::Abs(fp), Operand::imm(slot), temp]);
+                    temp
+                }
+            },
+            Expr::Index(base, index) => {
+                let base = self.expr(base)?;
+                let index = self.expr(index)?;
+                self.next_slot = mark;
+                let temp = Operand::slot(self.temp());
+                // Patches the address into the source of the copy that follows.
+                let patch = Word::Lit(self.here() + 4 + 1);
+                self.emit(ADD, &[base, index, Operand::Abs(patch)]);
+                self.emit(ADD, &[Operand::Abs(Word::Lit(0)), Operand::imm(0), temp]);
+                temp
+            }
+            Expr::Call(name, args, line) => self.call(name, args, *line)?,
+            Expr::Neg(val) => {
+                let val = self.expr(val)?;
+                self.next_slot = mark;
+                let temp = Operand::slot(self.temp());
+                self.emit(MUL, &[val, Operand::imm(-1), temp]);
+                temp
+            }
+            Expr::Not(val) => {
+                let val = self.expr(val)?;
+                self.next_slot = mark;
+                let temp = Operand::slot(self.temp());
+                self.emit(EQUALS, &[val, Operand::imm(0), temp]);
+                temp
+            }
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), lhs, rhs) => {
+                // The result starts as the value that a short circuit gives.
+                let short = (*op == BinOp::Or) as i64;
+                let jump = if *op == BinOp::Or {
+                    JUMP_IF_TRUE
+                } else {
+                    JUMP_IF_FALSE
+                };
+                let end = self.symbol();
+                let temp = Operand::slot(self.temp());
+                self.copy(Operand::imm(short), temp);
+                for side in &[lhs, rhs] {
+                    let val = self.expr(side)?;
+                    self.next_slot = mark + 1;
+                    self.emit(jump, &[val, Operand::Imm(Word::sym(end))]);
+                }
+                self.copy(Operand::imm(1 - short), temp);
+                self.place(end);
+                temp
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(lhs)?;
+                let rhs = self.expr(rhs)?;
+                self.next_slot = mark;
+                let temp = Operand::slot(self.temp());
+                match op {
+                    BinOp::Add => self.emit(ADD, &[lhs, rhs, temp]),
+                    BinOp::Sub => {
+                        // lhs - rhs is lhs + -1 * rhs. The product goes into the result slot,
+                        // which may hold lhs, so it goes through the slot above it.
+                        let neg = Operand::slot(self.temp());
+                        self.emit(MUL, &[rhs, Operand::imm(-1), neg]);
+                        self.emit(ADD, &[lhs, neg, temp]);
+                    }
+                    BinOp::Mul => self.emit(MUL, &[lhs, rhs, temp]),
+                    BinOp::Lt => self.emit(LESS_THAN, &[lhs, rhs, temp]),
+                    BinOp::Gt => self.emit(LESS_THAN, &[rhs, lhs, temp]),
+                    BinOp::Eq => self.emit(EQUALS, &[lhs, rhs, temp]),
+                    BinOp::Le | BinOp::Ge | BinOp::Ne => {
+                        match op {
+                            BinOp::Le => self.emit(LESS_THAN, &[rhs, lhs, temp]),
+                            BinOp::Ge => self.emit(LESS_THAN, &[lhs, rhs, temp]),
+                            _ => self.emit(EQUALS, &[lhs, rhs, temp]),
+                        }
+                        self.emit(EQUALS, &[temp, Operand::imm(0), temp]);
+                    }
+                    BinOp::And | BinOp::Or => unreachable!(),
+                }
+                self.next_slot = mark + 1;
+                temp
+            }
+        };
+        Ok(val)
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], line: usize) -> Result<Operand, CompileError> {
+        let mark = self.next_slot;
+        let arity = match name {
+            "input" => 0,
+            "output" => 1,
+            _ => match self.functions.get(name) {
+                Some(function) => function.arity,
+                None => return error(line, format!("function {} is not defined", name)),
+            },
+        };
+        if args.len() != arity {
+            return error(
+                line,
+                format!("{} takes {} arguments, not {}", name, arity, args.len()),
+            );
+        }
+        // Every argument that is not a constant is held in a slot of its own until the call,
+        // since evaluating the next one may call a function that changes a variable or uses
+        // the callee's frame.
+        let mut vals = Vec::new();
+        for arg in args {
+            let start = self.next_slot;
+            let val = self.expr(arg)?;
+            let fresh = self.next_slot > start && val == Operand::slot(self.next_slot - 1);
+            if let Operand::Rel(_) | Operand::Abs(_) = val {
+                if !fresh {
+                    let slot = Operand::slot(self.temp());
+                    self.copy(val, slot);
+                    vals.push(slot);
+                    continue;
+                }
+            }
+            vals.push(val);
+        }
+        self.next_slot = mark;
+        if name == "output" {
+            self.emit(OUT, &[vals[0]]);
+            return Ok(Operand::imm(0));
+        }
+        let temp = Operand::slot(self.temp());
+        match name {
+            "input" => self.emit(IN, &[temp]),
+            _ => {
+                // The callee's frame starts just past the caller's, whose size is only known
+                // once the whole caller has been generated.
+                let frame = self.frame;
+                let callee = |offset| Word::Sym {
+                    sym: frame,
+                    scale: 1,
+                    offset,
+                };
+                for (i, val) in vals.into_iter().enumerate() {
+                    self.copy(val, Operand::Rel(callee(i as i64 + 1)));
+                }
+                let back = self.symbol();
+                self.copy(Operand::Imm(Word::sym(back)), Operand::Rel(callee(0)));
+                self.adjust_frame(frame, 1);
+                self.jump(self.functions[name].label);
+                self.place(back);
+                self.adjust_frame(frame, -1);
+                self.copy(Operand::Abs(Word::sym(self.ret)), temp);
+            }
+        }
+        Ok(temp)
+    }
+
+    fn adjust_frame(&mut self, frame: usize, scale: i64) {
+        let size = Operand::Imm(Word::Sym {
+            sym: frame,
+            scale,
+            offset: 0,
+        });
+        self.emit(ADJUST_BASE, &[size]);
+        if let Some(fp) = self.frame_pointer {
+            let fp = Operand::Abs(Word::sym(fp));
+            self.emit(ADD, &[fp, size, fp]);
+        }
+    }
+}
+
+fn is_builtin(name: &str) -> bool {
+    name == "input" || name == "output"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::IntCodeComputer;
+
+    fn run(source: &str, inputs: &[i64]) -> Vec<i64> {
+        let program = compile(source).unwrap_or_else(|err| panic!("{}", err));
+        let mut computer = IntCodeComputer::new(&program);
+        for &val in inputs {
+            computer.add_input(val);
+        }
+        let outputs = computer.run_program();
+        assert!(computer.is_halted());
+        outputs
+    }
+
+    #[test]
+    fn recursion() {
+        let source = "
+            fn fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn ackermann(m, n) {
+                if m == 0 { return n + 1; }
+                if n == 0 { return ackermann(m - 1, 1); }
+                return ackermann(m - 1, ackermann(m, n - 1));
+            }
+
+            fn main() {
+                output(fib(input()));
+                output(ackermann(2, 3));
+            }
+        ";
+        assert_eq!(run(source, &[20]), [6765, 9]);
+    }
+
+    #[test]
+    fn arrays() {
+        let source = "
+            var primes[50];
+            var count;
+
+            // Sorts the first `len` cells of `cells` in place.
+            fn sort(cells, len) {
+                var i = 1;
+                while i < len {
+                    var j = i;
+                    while j > 0 && cells[j - 1] > cells[j] {
+                        var swap = cells[j];
+                        cells[j] = cells[j - 1];
+                        cells[j - 1] = swap;
+                        j = j - 1;
+                    }
+                    i = i + 1;
+                }
+            }
+
+            fn main() {
+                var n = 2;
+                while n < 50 {
+                    if !primes[n] {
+                        count = count + 1;
+                        var multiple = n * n;
+                        while multiple < 50 {
+                            primes[multiple] = 1;
+                            multiple = multiple + n;
+                        }
+                    }
+                    n = n + 1;
+                }
+                output(count);
+
+                var values[6];
+                var len = 0;
+                var val = input();
+                while val != 0 {
+                    values[len] = val;
+                    len = len + 1;
+                    val = input();
+                }
+                sort(values, len);
+                var i = 0;
+                while i < len {
+                    output(values[i]);
+                    i = i + 1;
+                }
+            }
+        ";
+        assert_eq!(run(source, &[5, -3, 8, 1, 0]), [15, -3, 1, 5, 8]);
+    }
+
+    #[test]
+    fn operators_and_control_flow() {
+        let source = "
+            var calls;
+
+            fn truthy(val) {
+                calls = calls + 1;
+                return val;
+            }
+
+            fn main() {
+                var a = input();
+                var b = input();
+                output(a - b);
+                output(-a * 3 + b);
+                output((a < b) + 2 * (a > b) + 4 * (a <= b) + 8 * (a >= b));
+                output((a == b) + 2 * (a != b) + 4 * !a);
+                output(truthy(0) && truthy(1));
+                output(truthy(1) || truthy(1));
+                output(calls);
+
+                var i = 0;
+                var sum = 0;
+                while 1 {
+                    i = i + 1;
+                    if i > 10 {
+                        break;
+                    } else if i == 5 {
+                        continue;
+                    }
+                    sum = sum + i;
+                }
+                output(sum);
+            }
+        ";
+        assert_eq!(run(source, &[7, 4]), [3, -17, 10, 2, 0, 1, 2, 50]);
+        assert_eq!(run(source, &[0, 0]), [0, 0, 12, 5, 0, 1, 2, 50]);
+    }
+
+    #[test]
+    fn local_arrays_survive_recursion() {
+        // Each call has its own array, so the one in the caller is intact after the call.
+        let source = "
+            fn fill(depth) {
+                var cells[3];
+                cells[0] = depth;
+                cells[2] = depth * 10;
+                if depth > 0 { fill(depth - 1); }
+                output(cells[0] + cells[1] + cells[2]);
+            }
+
+            fn main() { fill(3); }
+        ";
+        assert_eq!(run(source, &[]), [0, 11, 22, 33]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |source: &str| compile(source).unwrap_err().to_string();
+        assert_eq!(error("fn f() {}"), "line 1: there is no main function");
+        assert_eq!(
+            error("fn main() {\n  output(x);\n}"),
+            "line 2: x is not defined"
+        );
+        assert_eq!(
+            error("fn f(a) {}\nfn main() { f(1, 2); }"),
+            "line 2: f takes 1 arguments, not 2"
+        );
+        assert_eq!(
+            error("fn main() {\n  break;\n}"),
+            "line 2: break and continue must be inside a loop"
+        );
+        assert_eq!(
+            error("var a[2];\nfn main() { a = 1; }"),
+            "line 2: cannot assign to array a"
+        );
+        assert_eq!(
+            error("fn main() { output(1) }"),
+            "line 1: expected ;, found }"
+        );
+        assert_eq!(error("fn main() { $ }"), "line 1: unexpected character '$'");
+    }
+}
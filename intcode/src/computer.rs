@@ -0,0 +1,381 @@
+use std::collections::VecDeque;
+
+use crate::ascii::to_ascii;
+use crate::cell::Cell;
+use crate::fault::{Arithmetic, Fault};
+
+trait AutoExpand {
+    type Item;
+    fn expandable_get(&mut self, pos: usize) -> &Self::Item;
+    fn expandable_set(&mut self, pos: usize, item: Self::Item);
+}
+
+impl<T: Default + Clone> AutoExpand for Vec<T> {
+    type Item = T;
+
+    fn expandable_get(&mut self, pos: usize) -> &Self::Item {
+        let len = self.len();
+        if pos + 1 > len {
+            self.resize(pos + 1, T::default());
+        }
+        &self[pos]
+    }
+
+    fn expandable_set(&mut self, pos: usize, item: Self::Item) {
+        let len = self.len();
+        if pos + 1 > len {
+            self.resize(pos + 1, T::default());
+        }
+        self[pos] = item;
+    }
+}
+
+/// Why `run_one_turn` handed control back to the caller.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum ResultCode<C = i64> {
+    Input,
+    Output(C),
+    Terminated,
+    Fault(Fault<C>),
+}
+
+enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn from_i64(val: i64) -> Option<Self> {
+        match val {
+            0 => Some(Self::Position),
+            1 => Some(Self::Immediate),
+            2 => Some(Self::Relative),
+            _ => None,
+        }
+    }
+}
+
+/// An Intcode computer whose memory cells hold values of type `C`.
+#[derive(Debug)]
+pub struct IntCodeComputer<C = i64> {
+    program: Vec<C>,
+    input: VecDeque<C>,
+    output: Vec<C>,
+    inst_pointer: usize,
+    relative_base: C,
+    is_halted: bool,
+    arithmetic: Arithmetic,
+    fault: Option<Fault<C>>,
+}
+
+// `clone_from` reuses the memory and queues of the computer it overwrites, so that a computer
+// can be reset to a saved state without allocating.
+impl<C: Clone> Clone for IntCodeComputer<C> {
+    fn clone(&self) -> Self {
+        Self {
+            program: self.program.clone(),
+            input: self.input.clone(),
+            output: self.output.clone(),
+            inst_pointer: self.inst_pointer,
+            relative_base: self.relative_base.clone(),
+            is_halted: self.is_halted,
+            arithmetic: self.arithmetic,
+            fault: self.fault.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.program.clone_from(&source.program);
+        self.input.clone_from(&source.input);
+        self.output.clone_from(&source.output);
+        self.inst_pointer = source.inst_pointer;
+        self.relative_base = source.relative_base.clone();
+        self.is_halted = source.is_halted;
+        self.arithmetic = source.arithmetic;
+        self.fault = source.fault.clone();
+    }
+}
+
+impl IntCodeComputer {
+    pub fn new(program: &[i64]) -> Self {
+        Self::from_cells(program)
+    }
+}
+
+impl<C: Cell> IntCodeComputer<C> {
+    pub fn from_cells(program: &[C]) -> Self {
+        Self {
+            program: program.to_vec(),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            inst_pointer: 0,
+            relative_base: C::default(),
+            is_halted: false,
+            arithmetic: Arithmetic::default(),
+            fault: None,
+        }
+    }
+
+    /// Loads a program read as `i64` values into a computer with wider cells.
+    pub fn from_i64s(program: &[i64]) -> Self {
+        let program: Vec<C> = program.iter().map(|&val| C::from(val)).collect();
+        Self::from_cells(&program)
+    }
+
+    /// Sets what ADD and MUL do on overflow. The default is `Arithmetic::Checked`.
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Queues a value for the program to read. Inputs are consumed in the order they are added.
+    pub fn add_input(&mut self, new_input: C) {
+        self.input.push_front(new_input)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    /// The fault that stopped the program, if any.
+    pub fn fault(&self) -> Option<Fault<C>> {
+        self.fault.clone()
+    }
+
+    /// True when the next instruction reads input and nothing is queued for it.
+    pub fn is_blocked(&self) -> bool {
+        if self.is_halted || self.fault.is_some() || !self.input.is_empty() {
+            return false;
+        }
+        self.current_inst().rem_i64(100) == 3
+    }
+
+    /// Inputs not read yet, in the order the program will read them.
+    pub fn queued_input(&self) -> Vec<C> {
+        self.input.iter().rev().cloned().collect()
+    }
+
+    /// Every value the program has output so far.
+    pub fn output(&self) -> &[C] {
+        &self.output
+    }
+
+    /// The address of the next instruction.
+    pub fn inst_pointer(&self) -> usize {
+        self.inst_pointer
+    }
+
+    pub fn relative_base(&self) -> C {
+        self.relative_base.clone()
+    }
+
+    /// The program's memory, including any cells it has grown into.
+    pub fn memory(&self) -> &[C] {
+        &self.program
+    }
+
+    fn current_inst(&self) -> C {
+        self.program
+            .get(self.inst_pointer)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Only the opcode and the three mode digits matter, so an instruction decodes the same way
+    // whatever the cell type.
+    fn parse_instruction(&self) -> Result<(i64, Mode, Mode, Mode), Fault<C>> {
+        let inst = self.current_inst();
+        let digits = inst.rem_i64(100_000);
+        let mode = |pos: u32| {
+            Mode::from_i64(digits / 10_i64.pow(pos) % 10).ok_or_else(|| Fault::InvalidMode {
+                ip: self.inst_pointer,
+                inst: inst.clone(),
+            })
+        };
+        Ok((digits % 100, mode(2)?, mode(3)?, mode(4)?))
+    }
+
+    fn address(&self, addr: C) -> Result<usize, Fault<C>> {
+        addr.to_usize().ok_or(Fault::InvalidAddress {
+            ip: self.inst_pointer,
+            addr,
+        })
+    }
+
+    fn relative_address(&self, offset: C) -> Result<usize, Fault<C>> {
+        match offset.checked_add(&self.relative_base) {
+            Some(addr) => self.address(addr),
+            None => Err(Fault::Overflow {
+                ip: self.inst_pointer,
+                opcode: self.current_inst().rem_i64(100),
+                operands: [offset, self.relative_base.clone()],
+            }),
+        }
+    }
+
+    fn get_val(&mut self, pos: usize, mode: Mode) -> Result<C, Fault<C>> {
+        let res = self.program.expandable_get(pos).clone();
+        let addr = match mode {
+            Mode::Immediate => return Ok(res),
+            Mode::Position => self.address(res)?,
+            Mode::Relative => self.relative_address(res)?,
+        };
+        Ok(self.program.expandable_get(addr).clone())
+    }
+
+    fn set_val(&mut self, pos: usize, val: C, mode: Mode) -> Result<(), Fault<C>> {
+        let offset = self.program.expandable_get(pos).clone();
+        let addr = match mode {
+            Mode::Position => self.address(offset)?,
+            Mode::Relative => self.relative_address(offset)?,
+            Mode::Immediate => {
+                return Err(Fault::ImmediateWrite {
+                    ip: self.inst_pointer,
+                    inst: self.current_inst(),
+                })
+            }
+        };
+        self.program.expandable_set(addr, val);
+        Ok(())
+    }
+
+    fn arithmetic(&self, opcode: i64, fst: C, snd: C) -> Result<C, Fault<C>> {
+        let add = opcode == 1;
+        match self.arithmetic {
+            Arithmetic::Wrapping if add => Ok(fst.wrapping_add(&snd)),
+            Arithmetic::Wrapping => Ok(fst.wrapping_mul(&snd)),
+            Arithmetic::Saturating if add => Ok(fst.saturating_add(&snd)),
+            Arithmetic::Saturating => Ok(fst.saturating_mul(&snd)),
+            Arithmetic::Checked => {
+                let res = if add {
+                    fst.checked_add(&snd)
+                } else {
+                    fst.checked_mul(&snd)
+                };
+                res.ok_or(Fault::Overflow {
+                    ip: self.inst_pointer,
+                    opcode,
+                    operands: [fst, snd],
+                })
+            }
+        }
+    }
+
+    /// Executes a single instruction. Returns `None` when the program simply moved on to the next
+    /// one, or the reason it would hand control back from `run_one_turn`.
+    pub fn step(&mut self) -> Option<ResultCode<C>> {
+        if self.is_halted {
+            return Some(ResultCode::Terminated);
+        }
+        if let Some(fault) = &self.fault {
+            return Some(ResultCode::Fault(fault.clone()));
+        }
+        match self.execute() {
+            Ok(result) => result,
+            Err(fault) => {
+                self.fault = Some(fault.clone());
+                Some(ResultCode::Fault(fault))
+            }
+        }
+    }
+
+    // Faults are raised before the instruction writes anything or moves the instruction pointer.
+    fn execute(&mut self) -> Result<Option<ResultCode<C>>, Fault<C>> {
+        let (opcode, mode_1, mode_2, mode_3) = self.parse_instruction()?;
+        match opcode {
+            1 | 2 => {
+                let fst = self.get_val(self.inst_pointer + 1, mode_1)?;
+                let snd = self.get_val(self.inst_pointer + 2, mode_2)?;
+                let val = self.arithmetic(opcode, fst, snd)?;
+                self.set_val(self.inst_pointer + 3, val, mode_3)?;
+                self.inst_pointer += 4;
+            }
+            3 => {
+                if let Some(val) = self.input.back().cloned() {
+                    self.set_val(self.inst_pointer + 1, val, mode_1)?;
+                    self.input.pop_back();
+                    self.inst_pointer += 2;
+                } else {
+                    return Ok(Some(ResultCode::Input));
+                }
+            }
+            4 => {
+                let output = self.get_val(self.inst_pointer + 1, mode_1)?;
+                self.inst_pointer += 2;
+                self.output.push(output.clone());
+                return Ok(Some(ResultCode::Output(output)));
+            }
+            5 | 6 => {
+                let fst = self.get_val(self.inst_pointer + 1, mode_1)?;
+                let snd = self.get_val(self.inst_pointer + 2, mode_2)?;
+                if fst.is_zero() != (opcode == 5) {
+                    self.inst_pointer = self.address(snd)?
+                } else {
+                    self.inst_pointer += 3
+                }
+            }
+            7 | 8 => {
+                let fst = self.get_val(self.inst_pointer + 1, mode_1)?;
+                let snd = self.get_val(self.inst_pointer + 2, mode_2)?;
+                let holds = if opcode == 7 { fst < snd } else { fst == snd };
+                let val = C::from(if holds { 1 } else { 0 });
+                self.set_val(self.inst_pointer + 3, val, mode_3)?;
+                self.inst_pointer += 4;
+            }
+            9 => {
+                let adjustment = self.get_val(self.inst_pointer + 1, mode_1)?;
+                match self.relative_base.checked_add(&adjustment) {
+                    Some(relative_base) => self.relative_base = relative_base,
+                    None => {
+                        return Err(Fault::Overflow {
+                            ip: self.inst_pointer,
+                            opcode,
+                            operands: [self.relative_base.clone(), adjustment],
+                        })
+                    }
+                }
+                self.inst_pointer += 2;
+            }
+            99 => {
+                self.is_halted = true;
+                return Ok(Some(ResultCode::Terminated));
+            }
+            opcode => {
+                return Err(Fault::InvalidOpcode {
+                    ip: self.inst_pointer,
+                    opcode,
+                })
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs until the program outputs a value, needs input that has not been queued, or halts.
+    pub fn run_one_turn(&mut self) -> ResultCode<C> {
+        loop {
+            if let Some(result) = self.step() {
+                return result;
+            }
+        }
+    }
+
+    /// Runs until the program halts or blocks on input, returning everything it has output.
+    ///
+    /// Panics if the program faults.
+    pub fn run_program(&mut self) -> Vec<C> {
+        loop {
+            match self.run_one_turn() {
+                ResultCode::Output(_) => {}
+                ResultCode::Fault(fault) => panic!("{}", fault),
+                ResultCode::Input | ResultCode::Terminated => break,
+            }
+        }
+        self.output.to_owned()
+    }
+
+    /// Every output so far as text. Values that are not ASCII characters are left out.
+    pub fn get_output_as_ascii(&self) -> String {
+        self.output.iter().filter_map(to_ascii).collect()
+    }
+}
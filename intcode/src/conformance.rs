@@ -0,0 +1,392 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{self, read_to_string};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Once;
+
+use crate::load::parse_program;
+
+/// The corpus that ships with this crate: one file per topic, one `[name]` section per case.
+///
+/// ```text
+/// # Opcode 3 stores the next input.
+/// [input]
+/// day: 5
+/// program: 3,3,99,0
+/// inputs: 42
+/// outputs:
+/// memory: 3,3,99,42
+/// ```
+///
+/// `day` is the puzzle that introduced everything the case needs: 2 for ADD, MUL and HALT in
+/// position mode, 5 for the other instructions except relative base adjustments and for
+/// immediate mode, 9 for the rest, including values that do not fit in an `i32` and memory past
+/// the end of the program. It defaults to 9. `inputs` may be left out when the program reads
+/// nothing, and `memory` when the final memory is not checked. Memory is compared ignoring
+/// trailing zeros.
+pub const CASES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/conformance");
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    /// `file/name`, e.g. `opcodes/add`.
+    pub name: String,
+    pub day: u32,
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+    pub memory: Option<Vec<i64>>,
+}
+
+impl fmt::Display for Case {
+    /// Writes the case in the corpus format, so it can be loaded back with `load_cases`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // An empty list leaves nothing after the colon, not even a space.
+        let join = |values: &[i64]| {
+            let values: Vec<_> = values.iter().map(|val| val.to_string()).collect();
+            match values.len() {
+                0 => String::new(),
+                _ => format!(" {}", values.join(",")),
+            }
+        };
+        let name = self.name.rsplit('/').next().unwrap();
+        writeln!(f, "[{}]", name)?;
+        writeln!(f, "day: {}", self.day)?;
+        writeln!(f, "program:{}", join(&self.program))?;
+        if !self.inputs.is_empty() {
+            writeln!(f, "inputs:{}", join(&self.inputs))?;
+        }
+        writeln!(f, "outputs:{}", join(&self.outputs))?;
+        if let Some(memory) = &self.memory {
+            writeln!(f, "memory:{}", join(memory))?;
+        }
+        Ok(())
+    }
+}
+
+/// What a VM did with a case's program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Execution {
+    pub outputs: Vec<i64>,
+    pub memory: Vec<i64>,
+}
+
+/// A VM under test. `run` is given every input up front and should run the program until it
+/// halts.
+pub trait Implementation {
+    fn run(&self, program: &[i64], inputs: &[i64]) -> Execution;
+}
+
+impl<F: Fn(&[i64], &[i64]) -> Execution> Implementation for F {
+    fn run(&self, program: &[i64], inputs: &[i64]) -> Execution {
+        self(program, inputs)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Deviation {
+    Panicked(String),
+    Outputs {
+        expected: Vec<i64>,
+        actual: Vec<i64>,
+    },
+    Memory {
+        addr: usize,
+        expected: i64,
+        actual: i64,
+    },
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Deviation::Panicked(message) => write!(f, "panicked: {}", message),
+            Deviation::Outputs { expected, actual } => {
+                write!(f, "expected outputs {:?}, got {:?}", expected, actual)
+            }
+            Deviation::Memory {
+                addr,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected {} at address {}, found {}",
+                expected, addr, actual
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub passed: Vec<String>,
+    pub failed: Vec<(String, Deviation)>,
+}
+
+impl Report {
+    pub fn is_certified(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn failed_names(&self) -> Vec<&str> {
+        self.failed.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} cases passed",
+            self.passed.len(),
+            self.passed.len() + self.failed.len()
+        )?;
+        for (name, deviation) in &self.failed {
+            writeln!(f, "{}: {}", name, deviation)?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_cases(topic: &str, text: &str) -> io::Result<Vec<Case>> {
+    let mut cases: Vec<Case> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let error = |message: &str| invalid_data(format!("{}:{}: {}", topic, line_no, message));
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            cases.push(Case {
+                name: format!("{}/{}", topic, line[1..line.len() - 1].trim()),
+                day: 9,
+                program: Vec::new(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                memory: None,
+            });
+            continue;
+        }
+
+        let case = cases
+            .last_mut()
+            .ok_or_else(|| error("field outside of a [case] section"))?;
+        let (key, values) = match line.find(':') {
+            Some(pos) => (line[..pos].trim(), &line[pos + 1..]),
+            None => return Err(error("expected `key: values`")),
+        };
+        let values = parse_program(values).map_err(|err| error(&err.to_string()))?;
+        match key {
+            "day" => match *values.as_slice() {
+                [day @ 2] | [day @ 5] | [day @ 9] => case.day = day as u32,
+                _ => return Err(error("day must be 2, 5 or 9")),
+            },
+            "program" => case.program = values,
+            "inputs" => case.inputs = values,
+            "outputs" => case.outputs = values,
+            "memory" => case.memory = Some(values),
+            _ => return Err(error(&format!("unknown field {:?}", key))),
+        }
+    }
+    Ok(cases)
+}
+
+/// Loads every `.txt` file in `dir`, in file name order.
+pub fn load_cases(dir: &Path) -> io::Result<Vec<Case>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "txt"));
+    paths.sort();
+
+    let mut cases = Vec::new();
+    for path in paths {
+        let topic = path.file_stem().unwrap().to_string_lossy().into_owned();
+        cases.extend(parse_cases(&topic, &read_to_string(&path)?)?);
+    }
+    Ok(cases)
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// Runs one case. A panicking implementation deviates rather than failing the caller.
+pub fn check<I: Implementation + ?Sized>(implementation: &I, case: &Case) -> Result<(), Deviation> {
+    let execution = panic::catch_unwind(AssertUnwindSafe(|| {
+        implementation.run(&case.program, &case.inputs)
+    }))
+    .map_err(|payload| Deviation::Panicked(panic_message(payload)))?;
+
+    if execution.outputs != case.outputs {
+        return Err(Deviation::Outputs {
+            expected: case.outputs.clone(),
+            actual: execution.outputs,
+        });
+    }
+    if let Some(memory) = &case.memory {
+        let len = memory.len().max(execution.memory.len());
+        for addr in 0..len {
+            let expected = memory.get(addr).copied().unwrap_or(0);
+            let actual = execution.memory.get(addr).copied().unwrap_or(0);
+            if expected != actual {
+                return Err(Deviation::Memory {
+                    addr,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs every case in `CASES_DIR` that needs no more than the Intcode of puzzle `day`. Panic
+/// messages are silenced while the cases run, since panics are reported as deviations.
+pub fn certify<I: Implementation + ?Sized>(implementation: &I, day: u32) -> io::Result<Report> {
+    let mut cases = load_cases(Path::new(CASES_DIR))?;
+    cases.retain(|case| case.day <= day);
+    let mut report = Report::default();
+    quietly(|| {
+        for case in cases {
+            match check(implementation, &case) {
+                Ok(()) => report.passed.push(case.name),
+                Err(deviation) => report.failed.push((case.name, deviation)),
+            }
+        }
+    });
+    Ok(report)
+}
+
+thread_local! {
+    /// How many calls to `quietly` are running on this thread.
+    static QUIET: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Leaves `quietly` on this thread, even if `f` panicked.
+struct Loud;
+
+impl Drop for Loud {
+    fn drop(&mut self) {
+        QUIET.with(|quiet| quiet.set(quiet.get() - 1));
+    }
+}
+
+/// Runs `f` with panics on this thread kept from printing. The panic hook is global, so it is
+/// wrapped once, in a hook that stays silent on threads inside `quietly` and passes everything
+/// else on; other threads keep printing their panics.
+pub(crate) fn quietly<T>(f: impl FnOnce() -> T) -> T {
+    static WRAP: Once = Once::new();
+    WRAP.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if QUIET.with(Cell::get) == 0 {
+                hook(info);
+            }
+        }));
+    });
+    QUIET.with(|quiet| quiet.set(quiet.get() + 1));
+    let _loud = Loud;
+    f()
+}
+
+/// The shared VM with `C` cells, run until it halts.
+#[cfg(test)]
+pub(crate) fn run_shared<C: crate::cell::Cell>(program: &[i64], inputs: &[i64]) -> Execution {
+    let mut computer = crate::computer::IntCodeComputer::<C>::from_i64s(program);
+    for &val in inputs {
+        computer.add_input(C::from(val));
+    }
+    let outputs = computer.run_program();
+    Execution {
+        outputs: outputs.iter().map(|val| val.to_i64().unwrap()).collect(),
+        memory: computer
+            .memory()
+            .iter()
+            .map(|val| val.to_i64().unwrap())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigInt;
+
+    #[test]
+    fn corpus_covers_every_topic() {
+        let cases = load_cases(Path::new(CASES_DIR)).unwrap();
+        for topic in &["aoc", "modes", "opcodes", "relative_base", "self_modifying"] {
+            assert!(cases.iter().any(|case| case.name.starts_with(topic)));
+        }
+        // Cases are written back in the same format, without trailing spaces after empty lists.
+        let case = cases.iter().find(|case| case.outputs.is_empty()).unwrap();
+        let text = case.to_string();
+        assert!(text.lines().all(|line| !line.ends_with(' ')), "{}", text);
+        let topic = case.name.split('/').next().unwrap();
+        assert_eq!(
+            parse_cases(topic, &text).unwrap(),
+            std::slice::from_ref(case)
+        );
+    }
+
+    #[test]
+    fn shared_vm_is_certified() {
+        for report in &[
+            certify(&run_shared::<i64>, 9).unwrap(),
+            certify(&run_shared::<i128>, 9).unwrap(),
+            certify(&run_shared::<BigInt>, 9).unwrap(),
+        ] {
+            assert!(report.is_certified(), "{}", report);
+        }
+    }
+
+    #[test]
+    fn deviations_are_reported() {
+        // Reads every input, then outputs them all and halts, whatever the program.
+        let echo = |_: &[i64], inputs: &[i64]| Execution {
+            outputs: inputs.to_vec(),
+            memory: Vec::new(),
+        };
+        let case = |name: &str| {
+            load_cases(Path::new(CASES_DIR))
+                .unwrap()
+                .into_iter()
+                .find(|case| case.name == name)
+                .unwrap()
+        };
+        assert_eq!(
+            check(&echo, &case("opcodes/input")),
+            Err(Deviation::Outputs {
+                expected: vec![],
+                actual: vec![42]
+            })
+        );
+        assert_eq!(
+            check(&echo, &case("aoc/day2-add")),
+            Err(Deviation::Memory {
+                addr: 0,
+                expected: 2,
+                actual: 0
+            })
+        );
+        let broken = |_: &[i64], _: &[i64]| -> Execution { panic!("no") };
+        assert_eq!(
+            check(&broken, &case("aoc/day2-add")),
+            Err(Deviation::Panicked("no".to_string()))
+        );
+    }
+}
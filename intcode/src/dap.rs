@@ -0,0 +1,955 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::read_to_string;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::json::Json;
+use crate::load::{load_program, parse_program};
+
+/// A debuggee is a single machine, shown to the editor as a single thread.
+const THREAD_ID: i64 = 1;
+
+/// `variablesReference`s of the scopes and of the queues inside them.
+const MACHINE: i64 = 1;
+const INPUT: i64 = 2;
+const OUTPUT: i64 = 3;
+const MEMORY: i64 = 4;
+
+/// Memory references are byte addresses into memory laid out as in the binary program format:
+/// cell `n` is an `i64` in little-endian order at bytes `8n` to `8n + 7`.
+const CELL_BYTES: usize = 8;
+
+/// How many instructions a running program executes between checks for a `pause`.
+const SLICE: u64 = 10_000;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads one `Content-Length` framed message, or `None` at the end of the stream.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Json>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if len.is_some() {
+                break;
+            }
+        } else if let Some(val) = line.strip_prefix("Content-Length:") {
+            len =
+                Some(val.trim().parse().map_err(|_| {
+                    invalid_data(format!("invalid Content-Length {:?}", val.trim()))
+                })?);
+        }
+    }
+    let mut body = vec![0; len.unwrap()];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|err| invalid_data(err.to_string()))?;
+    Json::parse(&body).map(Some).map_err(invalid_data)
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(DIGITS[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Label names from an assembler's source map: one `label: address` per line, with `#`
+/// comments.
+pub fn parse_source_map(text: &str) -> io::Result<BTreeMap<String, usize>> {
+    let mut labels = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = || invalid_data(format!("line {}: expected `label: address`", i + 1));
+        let pos = line.find(':').ok_or_else(error)?;
+        let addr = line[pos + 1..].trim().parse().map_err(|_| error())?;
+        labels.insert(line[..pos].trim().to_string(), addr);
+    }
+    Ok(labels)
+}
+
+fn format_values(values: &[i64]) -> String {
+    if values.len() > 8 {
+        return format!("{} values", values.len());
+    }
+    let values: Vec<_> = values.iter().map(|val| val.to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn variable(name: &str, value: String) -> Json {
+    Json::object(vec![
+        ("name", name.into()),
+        ("value", value.into()),
+        ("variablesReference", 0.into()),
+    ])
+}
+
+struct Session {
+    computer: IntCodeComputer,
+    labels: BTreeMap<String, usize>,
+    instruction_breakpoints: BTreeSet<usize>,
+    function_breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+    exited: bool,
+}
+
+impl Session {
+    /// A label or an address.
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.labels
+            .get(name.trim())
+            .copied()
+            .or_else(|| name.trim().parse().ok())
+    }
+
+    /// `addr` relative to the nearest label at or before it, e.g. `loop+2`.
+    fn describe(&self, addr: usize) -> String {
+        match self
+            .labels
+            .iter()
+            .filter(|&(_, &start)| start <= addr)
+            .max_by_key(|&(_, &start)| start)
+        {
+            Some((label, &start)) if start == addr => label.clone(),
+            Some((label, &start)) => format!("{}+{}", label, addr - start),
+            None => addr.to_string(),
+        }
+    }
+
+    fn is_breakpoint(&self, addr: usize) -> bool {
+        self.instruction_breakpoints.contains(&addr) || self.function_breakpoints.contains(&addr)
+    }
+}
+
+/// A Debug Adapter Protocol server for one Intcode program. It supports `launch` with the
+/// arguments `program` (a program file, text or binary), `input` (a file of comma or whitespace
+/// separated inputs), `sourceMap` (labels, see `parse_source_map`) and `stopOnEntry`; instruction
+/// breakpoints by address or label, and function breakpoints by label; stepping one instruction
+/// at a time; `readMemory` and a memory scope; and variables for the instruction pointer, the
+/// relative base and both queues. `evaluate` reads `[addr]`, `ip`, `relative_base` and labels,
+/// and `input 1,2` queues inputs for a program waiting on them.
+///
+/// While the program runs, only `continue`, `pause`, `disconnect` and `terminate` are
+/// handled; other requests wait until it stops.
+struct Server<W> {
+    output: W,
+    seq: i64,
+    session: Option<Session>,
+    events: Vec<(&'static str, Json)>,
+    /// Set while the program is running, to whether a breakpoint on the current instruction
+    /// stops it.
+    running: Option<bool>,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            seq: 0,
+            session: None,
+            events: Vec::new(),
+            running: None,
+        }
+    }
+
+    fn send(&mut self, mut message: Vec<(&str, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        message.push(("seq", self.seq.into()));
+        write_message(&mut self.output, &Json::object(message))
+    }
+
+    fn event(&mut self, event: &'static str, body: Json) {
+        self.events.push((event, body));
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) {
+        let mut body = vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ];
+        if let Some(text) = text {
+            body.push(("text", text.into()));
+        }
+        self.event("stopped", Json::object(body));
+    }
+
+    /// Handles one request. Returns false once the client has disconnected.
+    fn handle(&mut self, request: &Json) -> io::Result<bool> {
+        let command = request.get("command").as_str().unwrap_or("").to_string();
+        let result = self.dispatch(&command, request.get("arguments"));
+        let mut response = vec![
+            ("type", "response".into()),
+            ("request_seq", request.get("seq").clone()),
+            ("command", command.as_str().into()),
+            ("success", result.is_ok().into()),
+        ];
+        match result {
+            Ok(body) => response.push(("body", body)),
+            Err(message) => response.push(("message", message.into())),
+        }
+        self.send(response)?;
+        self.flush()?;
+        Ok(command != "disconnect" && command != "terminate")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for (event, body) in std::mem::take(&mut self.events) {
+            self.send(vec![
+                ("type", "event".into()),
+                ("event", event.into()),
+                ("body", body),
+            ])?;
+        }
+        self.output.flush()
+    }
+
+    /// Runs the program for a slice, if it is running.
+    fn run_slice(&mut self) -> io::Result<()> {
+        if let Some(starting) = self.running {
+            self.running = match self.resume(false, starting, SLICE) {
+                Ok(false) => Some(true),
+                Ok(true) => None,
+                Err(message) => {
+                    self.stopped("exception", Some(message));
+                    None
+                }
+            };
+        }
+        self.flush()
+    }
+
+    fn session(&mut self) -> Result<&mut Session, String> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| "no program has been launched".to_string())
+    }
+
+    fn dispatch(&mut self, command: &str, args: &Json) -> Result<Json, String> {
+        match command {
+            "initialize" => Ok(Json::object(vec![
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsFunctionBreakpoints", true.into()),
+                ("supportsInstructionBreakpoints", true.into()),
+                ("supportsReadMemoryRequest", true.into()),
+                ("supportsEvaluateForHovers", true.into()),
+            ])),
+            "launch" => self.launch(args),
+            "setInstructionBreakpoints" => {
+                let session = self.session()?;
+                let mut breakpoints = Vec::new();
+                session.instruction_breakpoints.clear();
+                for breakpoint in args.get("breakpoints").as_array() {
+                    let reference = breakpoint.get("instructionReference").as_str();
+                    let offset = breakpoint.get("offset").as_i64().unwrap_or(0);
+                    let addr = reference
+                        .and_then(|reference| session.resolve(reference))
+                        .and_then(|addr| (addr as i64).checked_add(offset))
+                        .filter(|&addr| addr >= 0);
+                    if let Some(addr) = addr {
+                        session.instruction_breakpoints.insert(addr as usize);
+                    }
+                    breakpoints.push(Json::object(vec![
+                        ("verified", addr.is_some().into()),
+                        ("instructionReference", addr.unwrap_or(0).into()),
+                    ]));
+                }
+                Ok(Json::object(vec![("breakpoints", breakpoints.into())]))
+            }
+            "setFunctionBreakpoints" => {
+                let session = self.session()?;
+                let mut breakpoints = Vec::new();
+                session.function_breakpoints.clear();
+                for breakpoint in args.get("breakpoints").as_array() {
+                    let addr = breakpoint
+                        .get("name")
+                        .as_str()
+                        .and_then(|name| session.resolve(name));
+                    if let Some(addr) = addr {
+                        session.function_breakpoints.insert(addr);
+                    }
+                    breakpoints.push(Json::object(vec![("verified", addr.is_some().into())]));
+                }
+                Ok(Json::object(vec![("breakpoints", breakpoints.into())]))
+            }
+            "setBreakpoints" => {
+                let breakpoints: Vec<_> = args
+                    .get("breakpoints")
+                    .as_array()
+                    .iter()
+                    .map(|_| {
+                        Json::object(vec![
+                            ("verified", false.into()),
+                            (
+                                "message",
+                                "Intcode has no source lines, use instruction or function \
+                                 breakpoints"
+                                    .into(),
+                            ),
+                        ])
+                    })
+                    .collect();
+                Ok(Json::object(vec![("breakpoints", breakpoints.into())]))
+            }
+            "configurationDone" => {
+                if self.session()?.stop_on_entry {
+                    self.stopped("entry", None);
+                } else {
+                    self.running = Some(true);
+                }
+                Ok(Json::Null)
+            }
+            "threads" => Ok(Json::object(vec![(
+                "threads",
+                vec![Json::object(vec![
+                    ("id", THREAD_ID.into()),
+                    ("name", "intcode".into()),
+                ])]
+                .into(),
+            )])),
+            "stackTrace" => {
+                let session = self.session()?;
+                let ip = session.computer.inst_pointer();
+                let frame = Json::object(vec![
+                    ("id", 1.into()),
+                    ("name", session.describe(ip).into()),
+                    ("line", 0.into()),
+                    ("column", 0.into()),
+                    ("instructionPointerReference", ip.to_string().into()),
+                ]);
+                Ok(Json::object(vec![
+                    ("stackFrames", vec![frame].into()),
+                    ("totalFrames", 1.into()),
+                ]))
+            }
+            "scopes" => {
+                let len = self.session()?.computer.memory().len();
+                Ok(Json::object(vec![(
+                    "scopes",
+                    vec![
+                        Json::object(vec![
+                            ("name", "Machine".into()),
+                            ("variablesReference", MACHINE.into()),
+                            ("expensive", false.into()),
+                        ]),
+                        Json::object(vec![
+                            ("name", "Memory".into()),
+                            ("variablesReference", MEMORY.into()),
+                            ("indexedVariables", len.into()),
+                            ("expensive", false.into()),
+                        ]),
+                    ]
+                    .into(),
+                )]))
+            }
+            "variables" => self.variables(args),
+            "readMemory" => {
+                let session = self.session()?;
+                let reference = args.get("memoryReference").as_str().unwrap_or("");
+                let start = reference
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|start| start.checked_add(args.get("offset").as_i64().unwrap_or(0)))
+                    .filter(|&start| start >= 0)
+                    .ok_or_else(|| format!("invalid memory reference {:?}", reference))?
+                    as usize;
+                let count = args.get("count").as_i64().unwrap_or(0).max(0) as usize;
+                let bytes: Vec<u8> = session
+                    .computer
+                    .memory()
+                    .iter()
+                    .flat_map(|val| val.to_le_bytes().to_vec())
+                    .skip(start)
+                    .take(count)
+                    .collect();
+                Ok(Json::object(vec![
+                    ("address", start.to_string().into()),
+                    ("unreadableBytes", (count - bytes.len()).into()),
+                    ("data", base64(&bytes).into()),
+                ]))
+            }
+            "evaluate" => self.evaluate(args.get("expression").as_str().unwrap_or("")),
+            "continue" => {
+                if self.session()?.exited {
+                    return Err("the program has exited".to_string());
+                }
+                self.running = Some(false);
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            }
+            "next" | "stepIn" => {
+                self.resume(true, false, u64::MAX)?;
+                Ok(Json::Null)
+            }
+            "pause" => {
+                if self.running.take().is_none() {
+                    return Err("the program is not running".to_string());
+                }
+                self.stopped("pause", None);
+                Ok(Json::Null)
+            }
+            "disconnect" => {
+                self.running = None;
+                Ok(Json::Null)
+            }
+            "terminate" => {
+                self.running = None;
+                self.event("terminated", Json::object(vec![]));
+                Ok(Json::Null)
+            }
+            command => Err(format!("unsupported request {}", command)),
+        }
+    }
+
+    fn launch(&mut self, args: &Json) -> Result<Json, String> {
+        let path = args
+            .get("program")
+            .as_str()
+            .ok_or("launch needs a `program` path")?;
+        let program = load_program(Path::new(path)).map_err(|err| format!("{}: {}", path, err))?;
+        let mut computer = IntCodeComputer::new(&program);
+        if let Some(path) = args.get("input").as_str() {
+            let text = read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let inputs: Vec<i64> =
+                parse_program(&text).map_err(|err| format!("{}: {}", path, err))?;
+            for val in inputs {
+                computer.add_input(val);
+            }
+        }
+        let labels = match args.get("sourceMap").as_str() {
+            Some(path) => read_to_string(path)
+                .and_then(|text| parse_source_map(&text))
+                .map_err(|err| format!("{}: {}", path, err))?,
+            None => BTreeMap::new(),
+        };
+        self.session = Some(Session {
+            computer,
+            labels,
+            instruction_breakpoints: BTreeSet::new(),
+            function_breakpoints: BTreeSet::new(),
+            stop_on_entry: args.get("stopOnEntry").as_bool().unwrap_or(false),
+            exited: false,
+        });
+        self.event("initialized", Json::object(vec![]));
+        Ok(Json::Null)
+    }
+
+    /// Runs until a breakpoint, or for one instruction when `stepping`, but for at most
+    /// `budget` instructions. A breakpoint on the current instruction only stops a run that has
+    /// not started yet. Returns whether the program stopped.
+    fn resume(&mut self, stepping: bool, starting: bool, budget: u64) -> Result<bool, String> {
+        let mut executed = 0;
+        loop {
+            let session = self.session()?;
+            if session.exited {
+                return Err("the program has exited".to_string());
+            }
+            let computer = &session.computer;
+            let ip = computer.inst_pointer();
+            if computer.is_halted() {
+                session.exited = true;
+                self.event("exited", Json::object(vec![("exitCode", 0.into())]));
+                self.event("terminated", Json::object(vec![]));
+                return Ok(true);
+            } else if let Some(fault) = computer.fault() {
+                self.stopped("exception", Some(fault.to_string()));
+                return Ok(true);
+            } else if computer.is_blocked() {
+                self.stopped("pause", Some("waiting for input".to_string()));
+                return Ok(true);
+            } else if stepping && executed == 1 {
+                self.stopped("step", None);
+                return Ok(true);
+            } else if (executed > 0 || starting) && session.is_breakpoint(ip) {
+                self.stopped("breakpoint", None);
+                return Ok(true);
+            } else if executed == budget {
+                return Ok(false);
+            }
+
+            if let Some(ResultCode::Output(val)) = session.computer.step() {
+                self.event(
+                    "output",
+                    Json::object(vec![
+                        ("category", "stdout".into()),
+                        ("output", format!("{}\n", val).into()),
+                    ]),
+                );
+            }
+            executed += 1;
+        }
+    }
+
+    fn variables(&mut self, args: &Json) -> Result<Json, String> {
+        let session = self.session()?;
+        let computer = &session.computer;
+        let variables = match args.get("variablesReference").as_i64() {
+            Some(MACHINE) => {
+                let ip = computer.inst_pointer();
+                let (input, output) = (computer.queued_input(), computer.output());
+                let queue = |name: &str, values: &[i64], reference: i64| {
+                    Json::object(vec![
+                        ("name", name.into()),
+                        ("value", format_values(values).into()),
+                        ("variablesReference", reference.into()),
+                        ("indexedVariables", values.len().into()),
+                    ])
+                };
+                vec![
+                    Json::object(vec![
+                        ("name", "ip".into()),
+                        ("value", session.describe(ip).into()),
+                        ("variablesReference", 0.into()),
+                        ("memoryReference", (ip * CELL_BYTES).to_string().into()),
+                    ]),
+                    variable("relative_base", computer.relative_base().to_string()),
+                    queue("input", &input, INPUT),
+                    queue("output", output, OUTPUT),
+                ]
+            }
+            Some(reference @ INPUT) | Some(reference @ OUTPUT) | Some(reference @ MEMORY) => {
+                let input;
+                let values = match reference {
+                    INPUT => {
+                        input = computer.queued_input();
+                        &input[..]
+                    }
+                    OUTPUT => computer.output(),
+                    _ => computer.memory(),
+                };
+                let start = args.get("start").as_i64().unwrap_or(0).max(0) as usize;
+                let count = args.get("count").as_i64().unwrap_or(0).max(0) as usize;
+                let count = if count == 0 { values.len() } else { count };
+                values
+                    .iter()
+                    .enumerate()
+                    .skip(start)
+                    .take(count)
+                    .map(|(i, val)| variable(&format!("[{}]", i), val.to_string()))
+                    .collect()
+            }
+            _ => return Err("unknown variablesReference".to_string()),
+        };
+        Ok(Json::object(vec![("variables", variables.into())]))
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Result<Json, String> {
+        let session = self.session()?;
+        let expression = expression.trim();
+        let result = if let Some(values) = expression.strip_prefix("input ") {
+            let values: Vec<i64> = parse_program(values).map_err(|err| err.to_string())?;
+            for &val in &values {
+                session.computer.add_input(val);
+            }
+            format!("queued {}", format_values(&values))
+        } else if expression == "ip" {
+            session.computer.inst_pointer().to_string()
+        } else if expression == "relative_base" {
+            session.computer.relative_base().to_string()
+        } else if expression.starts_with('[') && expression.ends_with(']') {
+            let addr = session
+                .resolve(&expression[1..expression.len() - 1])
+                .ok_or_else(|| format!("invalid address in {}", expression))?;
+            let val = session.computer.memory().get(addr).copied().unwrap_or(0);
+            val.to_string()
+        } else {
+            let addr = session
+                .resolve(expression)
+                .ok_or_else(|| format!("cannot evaluate {:?}", expression))?;
+            addr.to_string()
+        };
+        Ok(Json::object(vec![
+            ("result", result.into()),
+            ("variablesReference", 0.into()),
+        ]))
+    }
+}
+
+/// Whether a request is handled while the program is running.
+fn interrupts(request: &Json) -> bool {
+    matches!(
+        request.get("command").as_str(),
+        Some("continue") | Some("pause") | Some("disconnect") | Some("terminate")
+    )
+}
+
+/// Serves requests read from `input` until the client disconnects or the stream ends. Requests
+/// are read on a thread of their own, so that a running program can be paused.
+pub fn serve<R: BufRead + Send + 'static, W: Write>(mut input: R, output: W) -> io::Result<()> {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || loop {
+        let message = read_message(&mut input);
+        let done = !matches!(message, Ok(Some(_)));
+        if sender.send(message).is_err() || done {
+            break;
+        }
+    });
+
+    let mut server = Server::new(output);
+    let mut waiting = VecDeque::new();
+    loop {
+        if server.running.is_some() {
+            server.run_slice()?;
+            while server.running.is_some() {
+                // The client going away stops the program with it.
+                let request = match requests.try_recv() {
+                    Ok(message) => match message? {
+                        Some(request) => request,
+                        None => return Ok(()),
+                    },
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+                if !interrupts(&request) {
+                    waiting.push_back(request);
+                } else if !server.handle(&request)? {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+        let request = match waiting.pop_front() {
+            Some(request) => request,
+            None => match requests.recv() {
+                Ok(message) => match message? {
+                    Some(request) => request,
+                    None => return Ok(()),
+                },
+                Err(_) => return Ok(()),
+            },
+        };
+        if !server.handle(&request)? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    /// Writes requests up front, then replays them through `serve` like an editor would.
+    /// Requests other than `continue`, `pause`, `disconnect` and `terminate` wait for the
+    /// program to stop, so the script does not need to wait on events.
+    struct Client {
+        script: Vec<u8>,
+        seq: i64,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            Self {
+                script: Vec::new(),
+                seq: 0,
+            }
+        }
+
+        fn request(&mut self, command: &str, arguments: Json) -> &mut Self {
+            self.seq += 1;
+            let request = Json::object(vec![
+                ("seq", self.seq.into()),
+                ("type", "request".into()),
+                ("command", command.into()),
+                ("arguments", arguments),
+            ]);
+            write_message(&mut self.script, &request).unwrap();
+            self
+        }
+
+        fn run(&self) -> Vec<Json> {
+            let mut output = Vec::new();
+            serve(Cursor::new(self.script.clone()), &mut output).unwrap();
+            let mut output = Cursor::new(output);
+            let mut messages = Vec::new();
+            while let Some(message) = read_message(&mut output).unwrap() {
+                messages.push(message);
+            }
+            messages
+        }
+    }
+
+    fn responses<'a>(messages: &'a [Json], command: &str) -> Vec<&'a Json> {
+        messages
+            .iter()
+            .filter(|message| message.get("command").as_str() == Some(command))
+            .collect()
+    }
+
+    fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+        messages
+            .iter()
+            .filter(|message| message.get("event").as_str() == Some(event))
+            .map(|message| message.get("body"))
+            .collect()
+    }
+
+    fn stops(messages: &[Json]) -> Vec<&str> {
+        events(messages, "stopped")
+            .iter()
+            .map(|body| body.get("reason").as_str().unwrap())
+            .collect()
+    }
+
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("intcode-dap-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, text) in files {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    fn launch(dir: &Path, extra: Vec<(&str, Json)>) -> Json {
+        let mut args = vec![(
+            "program",
+            dir.join("program.txt")
+                .to_string_lossy()
+                .into_owned()
+                .into(),
+        )];
+        args.extend(extra);
+        Json::object(args)
+    }
+
+    #[test]
+    fn breakpoints_stepping_and_variables() {
+        let dir = write_files(
+            "breakpoints",
+            &[
+                ("program.txt", "3,9, 102,2,9,9, 4,9, 99, 0"),
+                ("input.txt", "21"),
+                ("program.map", "start: 0\ndouble: 2\nprint: 6\nx: 9\n"),
+            ],
+        );
+        let path = |file: &str| Json::from(dir.join(file).to_string_lossy().into_owned());
+        let messages = Client::new()
+            .request("initialize", Json::object(vec![]))
+            .request(
+                "launch",
+                launch(
+                    &dir,
+                    vec![
+                        ("input", path("input.txt")),
+                        ("sourceMap", path("program.map")),
+                    ],
+                ),
+            )
+            .request(
+                "setFunctionBreakpoints",
+                Json::object(vec![(
+                    "breakpoints",
+                    vec![
+                        Json::object(vec![("name", "print".into())]),
+                        Json::object(vec![("name", "nowhere".into())]),
+                    ]
+                    .into(),
+                )]),
+            )
+            .request(
+                "setInstructionBreakpoints",
+                Json::object(vec![(
+                    "breakpoints",
+                    vec![Json::object(vec![("instructionReference", "2".into())])].into(),
+                )]),
+            )
+            .request("configurationDone", Json::Null)
+            .request("evaluate", Json::object(vec![("expression", "ip".into())]))
+            .request("continue", Json::Null)
+            .request("next", Json::Null)
+            .request("stackTrace", Json::Null)
+            .request(
+                "variables",
+                Json::object(vec![("variablesReference", MACHINE.into())]),
+            )
+            .request(
+                "readMemory",
+                Json::object(vec![("memoryReference", "72".into()), ("count", 8.into())]),
+            )
+            .request("evaluate", Json::object(vec![("expression", "[x]".into())]))
+            .request("continue", Json::Null)
+            .request("disconnect", Json::Null)
+            .run();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(messages
+            .iter()
+            .filter(|message| message.get("type").as_str() == Some("response"))
+            .all(|response| response.get("success") == &Json::Bool(true)));
+        let verified: Vec<_> = responses(&messages, "setFunctionBreakpoints")[0]
+            .get("body")
+            .get("breakpoints")
+            .as_array()
+            .iter()
+            .map(|breakpoint| breakpoint.get("verified").as_bool().unwrap())
+            .collect();
+        assert_eq!(verified, [true, false]);
+        assert_eq!(stops(&messages), ["breakpoint", "breakpoint", "step"]);
+
+        let result = |i: usize| {
+            responses(&messages, "evaluate")[i]
+                .get("body")
+                .get("result")
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(result(0), "2");
+        assert_eq!(result(1), "42");
+        let frame = &responses(&messages, "stackTrace")[0]
+            .get("body")
+            .get("stackFrames")
+            .as_array()[0];
+        assert_eq!(frame.get("name").as_str(), Some("print+2"));
+
+        let variables: Vec<_> = responses(&messages, "variables")[0]
+            .get("body")
+            .get("variables")
+            .as_array()
+            .iter()
+            .map(|var| {
+                format!(
+                    "{} = {}",
+                    var.get("name").as_str().unwrap(),
+                    var.get("value").as_str().unwrap()
+                )
+            })
+            .collect();
+        assert_eq!(
+            variables,
+            [
+                "ip = print+2",
+                "relative_base = 0",
+                "input = []",
+                "output = [42]"
+            ]
+        );
+        let memory = responses(&messages, "readMemory")[0].get("body");
+        assert_eq!(memory.get("data").as_str(), Some("KgAAAAAAAAA="));
+
+        let outputs: Vec<_> = events(&messages, "output")
+            .iter()
+            .map(|body| body.get("output").as_str().unwrap())
+            .collect();
+        assert_eq!(outputs, ["42\n"]);
+        assert_eq!(events(&messages, "exited").len(), 1);
+        assert_eq!(events(&messages, "terminated").len(), 1);
+    }
+
+    #[test]
+    fn waits_for_input_and_stops_on_faults() {
+        let dir = write_files("input", &[("program.txt", "3,0,4,0,99")]);
+        let messages = Client::new()
+            .request("initialize", Json::object(vec![]))
+            .request("launch", launch(&dir, vec![]))
+            .request("configurationDone", Json::Null)
+            .request(
+                "evaluate",
+                Json::object(vec![("expression", "input 7".into())]),
+            )
+            .request("continue", Json::Null)
+            .request("disconnect", Json::Null)
+            .run();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(stops(&messages), ["pause"]);
+        assert_eq!(
+            events(&messages, "output")[0].get("output").as_str(),
+            Some("7\n")
+        );
+        assert_eq!(events(&messages, "exited").len(), 1);
+
+        let dir = write_files("fault", &[("program.txt", "1,0,0,0,42")]);
+        let messages = Client::new()
+            .request("initialize", Json::object(vec![]))
+            .request("launch", launch(&dir, vec![("stopOnEntry", true.into())]))
+            .request("configurationDone", Json::Null)
+            .request("next", Json::Null)
+            .request("continue", Json::Null)
+            .request("disconnect", Json::Null)
+            .run();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(stops(&messages), ["entry", "step", "exception"]);
+        assert_eq!(
+            events(&messages, "stopped")[2].get("text").as_str(),
+            Some("invalid opcode at 4: opcode must be 1, 2, 3, 4, 5, 6, 7, 8, 9 or 99, got 42")
+        );
+    }
+
+    #[test]
+    fn pauses_a_program_that_never_stops() {
+        let dir = write_files("pause", &[("program.txt", "1105,1,0")]);
+        let messages = Client::new()
+            .request("initialize", Json::object(vec![]))
+            .request("launch", launch(&dir, vec![]))
+            .request("configurationDone", Json::Null)
+            .request("pause", Json::Null)
+            .request("evaluate", Json::object(vec![("expression", "ip".into())]))
+            .request("pause", Json::Null)
+            .request("disconnect", Json::Null)
+            .run();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(stops(&messages), ["pause"]);
+        let succeeded: Vec<_> = responses(&messages, "pause")
+            .iter()
+            .map(|response| response.get("success").as_bool().unwrap())
+            .collect();
+        assert_eq!(succeeded, [true, false]);
+        assert_eq!(
+            responses(&messages, "evaluate")[0]
+                .get("body")
+                .get("result")
+                .as_str(),
+            Some("0")
+        );
+    }
+
+    #[test]
+    fn stops_when_the_client_goes_away() {
+        let dir = write_files("gone", &[("program.txt", "1105,1,0")]);
+        let mut client = Client::new();
+        client
+            .request("initialize", Json::object(vec![]))
+            .request("launch", launch(&dir, vec![]))
+            .request("configurationDone", Json::Null)
+            .request("continue", Json::Null);
+        let (sender, done) = mpsc::channel();
+        thread::spawn(move || sender.send(client.run()));
+        let messages = done
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("the server kept running after the client went away");
+        fs::remove_dir_all(&dir).unwrap();
+        // The program is already running when `continue` arrives, which is still answered.
+        assert_eq!(responses(&messages, "continue").len(), 1);
+        assert!(stops(&messages).is_empty());
+    }
+}
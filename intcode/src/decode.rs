@@ -0,0 +1,255 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+/// How an instruction's parameter is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Param {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+/// An instruction decoded from a program without running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub opcode: i64,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    /// The instruction at `addr`, or `None` if the cells there do not hold one the computer
+    /// would run. As on the computer, only the opcode and three mode digits are looked at, all
+    /// three modes must be valid, and writes to immediate-mode parameters are not.
+    pub fn decode(program: &[i64], addr: usize) -> Option<Self> {
+        let inst = *program.get(addr)?;
+        if inst < 0 {
+            return None;
+        }
+        let digits = inst % 100_000;
+        let opcode = digits % 100;
+        let (len, writes) = match opcode {
+            1 | 2 | 7 | 8 => (3, true),
+            3 => (1, true),
+            4 | 9 => (1, false),
+            5 | 6 => (2, false),
+            99 => (0, false),
+            _ => return None,
+        };
+        if (2..5).any(|pos| digits / 10i64.pow(pos) % 10 > 2) {
+            return None;
+        }
+        let mut params = Vec::with_capacity(len);
+        let mut modes = digits / 100;
+        for i in 0..len {
+            let mode = match modes % 10 {
+                0 => Mode::Position,
+                1 if writes && i == len - 1 => return None,
+                1 => Mode::Immediate,
+                _ => Mode::Relative,
+            };
+            modes /= 10;
+            let value = *program.get(addr + 1 + i)?;
+            params.push(Param { mode, value });
+        }
+        Some(Self {
+            addr,
+            opcode,
+            params,
+        })
+    }
+
+    /// The address of the instruction after this one.
+    pub fn next(&self) -> usize {
+        self.addr + 1 + self.params.len()
+    }
+
+    /// The parameter the instruction writes to, if any.
+    pub fn write(&self) -> Option<Param> {
+        match self.opcode {
+            1 | 2 | 7 | 8 => Some(self.params[2]),
+            3 => Some(self.params[0]),
+            _ => None,
+        }
+    }
+
+    /// The parameters the instruction reads.
+    pub fn reads(&self) -> &[Param] {
+        match self.opcode {
+            1 | 2 | 7 | 8 => &self.params[..2],
+            3 => &[],
+            _ => &self.params,
+        }
+    }
+
+    /// The value an ADD or MUL of two immediates stores, which is how programs store constants
+    /// such as return addresses.
+    pub fn constant(&self) -> Option<i64> {
+        match (self.opcode, self.reads()) {
+            (
+                1,
+                [Param {
+                    mode: Mode::Immediate,
+                    value: fst,
+                }, Param {
+                    mode: Mode::Immediate,
+                    value: snd,
+                }],
+            ) => fst.checked_add(*snd),
+            (
+                2,
+                [Param {
+                    mode: Mode::Immediate,
+                    value: fst,
+                }, Param {
+                    mode: Mode::Immediate,
+                    value: snd,
+                }],
+            ) => fst.checked_mul(*snd),
+            _ => None,
+        }
+    }
+
+    /// Whether execution can carry on to the next instruction. Jumps whose condition is
+    /// immediate are only followed one way.
+    pub fn falls_through(&self) -> bool {
+        match (self.opcode, self.params.first()) {
+            (99, _) => false,
+            (
+                5,
+                Some(Param {
+                    mode: Mode::Immediate,
+                    value,
+                }),
+            ) => *value == 0,
+            (
+                6,
+                Some(Param {
+                    mode: Mode::Immediate,
+                    value,
+                }),
+            ) => *value != 0,
+            _ => true,
+        }
+    }
+
+    /// Where the instruction jumps to, when that is known without running the program.
+    pub fn jump_target(&self) -> Option<usize> {
+        match (self.opcode, self.params.first()?, self.params.get(1)?) {
+            (
+                5,
+                Param {
+                    mode: Mode::Immediate,
+                    value: 0,
+                },
+                _,
+            ) => None,
+            (
+                6,
+                Param {
+                    mode: Mode::Immediate,
+                    value,
+                },
+                _,
+            ) if *value != 0 => None,
+            (5, _, target) | (6, _, target) if target.mode == Mode::Immediate => {
+                usize::try_from(target.value).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The instructions reachable from address 0, in address order.
+///
+/// Jumps through memory or the relative base, such as returns, cannot be followed without
+/// running the program. Subroutine calls store their return address as a constant before
+/// jumping, so an instruction that ends a block is also taken to fall through when some
+/// reachable instruction stores the address after it.
+pub fn reachable(program: &[i64]) -> Vec<Instruction> {
+    reachable_from(program, &[0])
+}
+
+/// The instructions reachable from any of `entries`, in address order, found as `reachable`
+/// finds them.
+pub fn reachable_from(program: &[i64], entries: &[usize]) -> Vec<Instruction> {
+    let mut found = BTreeSet::new();
+    let mut instructions = Vec::new();
+    let mut dead_ends = BTreeSet::new();
+    let mut queue = entries.to_vec();
+    while !queue.is_empty() {
+        while let Some(addr) = queue.pop() {
+            if found.contains(&addr) {
+                continue;
+            }
+            let inst = match Instruction::decode(program, addr) {
+                Some(inst) => inst,
+                None => continue,
+            };
+            found.insert(addr);
+            if inst.falls_through() {
+                queue.push(inst.next());
+            } else {
+                dead_ends.insert(inst.next());
+            }
+            if let Some(target) = inst.jump_target() {
+                queue.push(target);
+            }
+            instructions.push(inst);
+        }
+        for inst in &instructions {
+            let addr = match inst.constant().map(usize::try_from) {
+                Some(Ok(addr)) => addr,
+                _ => continue,
+            };
+            if dead_ends.remove(&addr) && !found.contains(&addr) {
+                queue.push(addr);
+            }
+        }
+    }
+    instructions.sort_by_key(|inst| inst.addr);
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_instructions() {
+        let inst = Instruction::decode(&[21101, 7, -2, 3], 0).unwrap();
+        assert_eq!(inst.opcode, 1);
+        assert_eq!(inst.next(), 4);
+        assert_eq!(
+            inst.write(),
+            Some(Param {
+                mode: Mode::Relative,
+                value: 3
+            })
+        );
+        assert_eq!(inst.reads().len(), 2);
+        assert_eq!(Instruction::decode(&[11101, 7, -2, 3], 0), None);
+        assert_eq!(Instruction::decode(&[104, 1, 99], 2).unwrap().next(), 3);
+        assert_eq!(Instruction::decode(&[1, 0, 0], 0), None);
+        assert_eq!(Instruction::decode(&[1304, 0], 0), None);
+        assert_eq!(Instruction::decode(&[301104, 0], 0).unwrap().opcode, 4);
+        assert_eq!(Instruction::decode(&[73, 110], 0), None);
+    }
+
+    #[test]
+    fn follows_calls_and_returns() {
+        // Calls the subroutine at 12 with its return address, 9, at rb + 0, then outputs
+        // cell 20 and halts. The subroutine stores 5 in cell 20 and returns.
+        let program = [
+            109, 30, 21101, 0, 9, 0, 1105, 1, 12, 4, 20, 99, 1101, 0, 5, 20, 2105, 1, 0, 0, 0,
+        ];
+        let addrs: Vec<_> = reachable(&program).iter().map(|inst| inst.addr).collect();
+        assert_eq!(addrs, [0, 2, 6, 9, 11, 12, 16]);
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem;
+
+use crate::ascii::{to_ascii, AsciiOutput};
+
+/// `(x, y)`, with `y` growing downwards as on a screen.
+pub type Position = (i64, i64);
+
+/// What a program talks to: the device sees every value the program outputs and supplies every
+/// value it reads, keeping whatever state it needs in between.
+pub trait Device<C = i64> {
+    fn on_output(&mut self, val: C);
+
+    /// The next input for the program. `None` leaves the program blocked and ends the run.
+    fn next_input(&mut self) -> Option<C>;
+
+    /// Checked before every instruction run; a device that is done ends the run.
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+/// Why `attach` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detach {
+    Halted,
+    /// The program wanted input the device did not have.
+    Blocked,
+    /// The device was done.
+    Done,
+}
+
+/// Day 11's hull painting robot. It reads the color of the panel under it, then takes a color to
+/// paint it and a turn to make, 0 for left and 1 for right, before moving forward one panel.
+#[derive(Clone, Debug, Default)]
+pub struct PaintingRobot {
+    pos: Position,
+    dir: Position,
+    panels: HashMap<Position, i64>,
+    painted: HashSet<Position>,
+    turning: bool,
+}
+
+impl PaintingRobot {
+    /// A robot facing up on a black hull, except for the panel under it which is `start_color`.
+    pub fn new(start_color: i64) -> Self {
+        Self {
+            dir: (0, -1),
+            panels: vec![((0, 0), start_color)].into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The color of every panel that is not black by default.
+    pub fn panels(&self) -> &HashMap<Position, i64> {
+        &self.panels
+    }
+
+    /// How many panels were painted at least once.
+    pub fn painted(&self) -> usize {
+        self.painted.len()
+    }
+}
+
+impl Device for PaintingRobot {
+    fn on_output(&mut self, val: i64) {
+        if self.turning {
+            let (x, y) = self.dir;
+            self.dir = match val {
+                0 => (y, -x),
+                1 => (-y, x),
+                _ => panic!("Direction must be either 0 or 1, got {}", val),
+            };
+            self.pos = (self.pos.0 + self.dir.0, self.pos.1 + self.dir.1);
+        } else {
+            assert!(val == 0 || val == 1, "Color must be either 0 or 1");
+            self.panels.insert(self.pos, val);
+            self.painted.insert(self.pos);
+        }
+        self.turning = !self.turning;
+    }
+
+    fn next_input(&mut self) -> Option<i64> {
+        Some(self.panels.get(&self.pos).copied().unwrap_or(0))
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl From<i64> for Tile {
+    fn from(val: i64) -> Self {
+        match val {
+            0 => Self::Empty,
+            1 => Self::Wall,
+            2 => Self::Block,
+            3 => Self::Paddle,
+            4 => Self::Ball,
+            i => panic!("Tile id could only be 0, 1, 2, 3, or 4, have {}", i),
+        }
+    }
+}
+
+/// Day 13's arcade cabinet. The program draws with `(x, y, tile)` triples and sets the score
+/// with `(-1, 0, score)`; the joystick always moves the paddle towards the ball.
+#[derive(Clone, Debug, Default)]
+pub struct Arcade {
+    screen: HashMap<Position, Tile>,
+    score: i64,
+    ball_x: i64,
+    paddle_x: i64,
+    packet: Vec<i64>,
+}
+
+impl Arcade {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn screen(&self) -> &HashMap<Position, Tile> {
+        &self.screen
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.screen.values().filter(|&&drawn| drawn == tile).count()
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+}
+
+impl Device for Arcade {
+    fn on_output(&mut self, val: i64) {
+        self.packet.push(val);
+        if let [x, y, val] = self.packet[..] {
+            self.packet.clear();
+            if (x, y) == (-1, 0) {
+                self.score = val;
+                return;
+            }
+            let tile = Tile::from(val);
+            match tile {
+                Tile::Ball => self.ball_x = x,
+                Tile::Paddle => self.paddle_x = x,
+                _ => {}
+            }
+            self.screen.insert((x, y), tile);
+        }
+    }
+
+    fn next_input(&mut self) -> Option<i64> {
+        Some((self.ball_x - self.paddle_x).signum())
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Terrain {
+    Wall,
+    Open,
+    Oxygen,
+}
+
+const MOVES: [(i64, Position); 4] = [(1, (0, -1)), (2, (0, 1)), (3, (-1, 0)), (4, (1, 0))];
+
+/// Day 15's repair droid. It explores the whole maze depth first, moving with 1 to 4 for north,
+/// south, west and east, and reading back 0 for a wall, 1 for a step taken and 2 for a step onto
+/// the oxygen system. It is done once every reachable position has been visited and it is back
+/// where it started.
+#[derive(Clone, Debug)]
+pub struct Droid {
+    pos: Position,
+    map: HashMap<Position, Terrain>,
+    /// The moves from the start to `pos`, to retrace when a dead end is reached.
+    trail: Vec<usize>,
+    /// The move sent to the program and whether it retraces the trail.
+    pending: Option<(usize, bool)>,
+    explored: bool,
+}
+
+impl Droid {
+    pub fn new() -> Self {
+        Self {
+            pos: (0, 0),
+            map: vec![((0, 0), Terrain::Open)].into_iter().collect(),
+            trail: Vec::new(),
+            pending: None,
+            explored: false,
+        }
+    }
+
+    pub fn map(&self) -> &HashMap<Position, Terrain> {
+        &self.map
+    }
+
+    pub fn oxygen(&self) -> Option<Position> {
+        self.map
+            .iter()
+            .find(|&(_, &terrain)| terrain == Terrain::Oxygen)
+            .map(|(&pos, _)| pos)
+    }
+
+    /// The fewest steps from `from` to every position reachable from it on the known map.
+    pub fn distances(&self, from: Position) -> HashMap<Position, usize> {
+        let mut distances = HashMap::new();
+        distances.insert(from, 0);
+        let mut queue = VecDeque::from(vec![from]);
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[&pos];
+            for &(_, (dx, dy)) in &MOVES {
+                let next = (pos.0 + dx, pos.1 + dy);
+                let open = self.map.get(&next).is_some_and(|&t| t != Terrain::Wall);
+                if open && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+}
+
+impl Default for Droid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Droid {
+    fn on_output(&mut self, val: i64) {
+        let (i, retracing) = self.pending.take().expect("status without a move");
+        let (dx, dy) = MOVES[i].1;
+        let next = (self.pos.0 + dx, self.pos.1 + dy);
+        let terrain = match val {
+            0 => Terrain::Wall,
+            1 => Terrain::Open,
+            2 => Terrain::Oxygen,
+            _ => panic!("Status could only be 0, 1, or 2, have {}", val),
+        };
+        self.map.insert(next, terrain);
+        if terrain != Terrain::Wall {
+            self.pos = next;
+            if !retracing {
+                self.trail.push(i);
+            }
+        }
+    }
+
+    fn next_input(&mut self) -> Option<i64> {
+        let unknown = (0..MOVES.len()).find(|&i| {
+            let (dx, dy) = MOVES[i].1;
+            !self.map.contains_key(&(self.pos.0 + dx, self.pos.1 + dy))
+        });
+        // North and south, and west and east, are next to each other in `MOVES`.
+        let (i, retracing) = match unknown {
+            Some(i) => (i, false),
+            None => match self.trail.pop() {
+                Some(i) => (i ^ 1, true),
+                None => {
+                    self.explored = true;
+                    return None;
+                }
+            },
+        };
+        self.pending = Some((i, retracing));
+        Some(MOVES[i].0)
+    }
+
+    fn is_done(&self) -> bool {
+        self.explored
+    }
+}
+
+/// An ASCII terminal, as used from day 17 on. It keeps what the program prints, and answers
+/// prompts from a script: when the program wants input, the next answer is typed if the last
+/// line printed ends with its prompt.
+#[derive(Clone, Debug, Default)]
+pub struct Terminal {
+    output: AsciiOutput,
+    line: String,
+    script: VecDeque<(String, String)>,
+    typed: VecDeque<i64>,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_script(script: &[(&str, &str)]) -> Self {
+        Self {
+            script: script
+                .iter()
+                .map(|&(prompt, answer)| (prompt.to_string(), answer.to_string()))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Types `line` and a newline, whatever the program prints.
+    pub fn type_line(&mut self, line: &str) {
+        self.typed.extend(line.bytes().map(i64::from));
+        self.typed.push_back(i64::from(b'\n'));
+    }
+
+    /// Everything printed so far, including a line still being written.
+    pub fn screen(&self) -> AsciiOutput {
+        let mut output = self.output.clone();
+        if !self.line.is_empty() {
+            output.lines.push(self.line.clone());
+        }
+        output
+    }
+
+    fn last_line(&self) -> &str {
+        if self.line.is_empty() {
+            self.output.lines.last().map_or("", String::as_str)
+        } else {
+            &self.line
+        }
+    }
+}
+
+impl Device for Terminal {
+    fn on_output(&mut self, val: i64) {
+        match to_ascii(&val) {
+            Some('\n') => self.output.lines.push(mem::take(&mut self.line)),
+            Some(ch) => self.line.push(ch),
+            None => self.output.values.push(val),
+        }
+    }
+
+    fn next_input(&mut self) -> Option<i64> {
+        if self.typed.is_empty() {
+            let prompt = &self.script.front()?.0;
+            if !self.last_line().trim_end().ends_with(prompt.as_str()) {
+                return None;
+            }
+            let (_, answer) = self.script.pop_front().unwrap();
+            self.type_line(&answer);
+        }
+        self.typed.pop_front()
+    }
+}
+
+/// One value passed between a program and its device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exchange<C = i64> {
+    /// The program output the value.
+    Output(C),
+    /// The program read the value.
+    Input(C),
+}
+
+/// Wraps a device and records everything passed between it and the program.
+#[derive(Clone, Debug)]
+pub struct Recorder<D, C = i64> {
+    pub device: D,
+    pub transcript: Vec<Exchange<C>>,
+}
+
+impl<D, C> Recorder<D, C> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            transcript: Vec::new(),
+        }
+    }
+}
+
+impl<C: Clone, D: Device<C>> Device<C> for Recorder<D, C> {
+    fn on_output(&mut self, val: C) {
+        self.transcript.push(Exchange::Output(val.clone()));
+        self.device.on_output(val);
+    }
+
+    fn next_input(&mut self) -> Option<C> {
+        let val = self.device.next_input()?;
+        self.transcript.push(Exchange::Input(val.clone()));
+        Some(val)
+    }
+
+    fn is_done(&self) -> bool {
+        self.device.is_done()
+    }
+}
+
+/// Stands in for a device by playing back a recorded transcript, without modelling anything.
+/// It stops the run as soon as the program strays from the transcript.
+#[derive(Clone, Debug)]
+pub struct Replay<C = i64> {
+    transcript: Vec<Exchange<C>>,
+    pos: usize,
+    diverged: bool,
+}
+
+impl<C> Replay<C> {
+    pub fn new(transcript: Vec<Exchange<C>>) -> Self {
+        Self {
+            transcript,
+            pos: 0,
+            diverged: false,
+        }
+    }
+
+    /// Where in the transcript the program strayed, if it did.
+    pub fn divergence(&self) -> Option<usize> {
+        if self.diverged {
+            Some(self.pos)
+        } else {
+            None
+        }
+    }
+
+    /// True once the whole transcript has been played back as recorded.
+    pub fn is_finished(&self) -> bool {
+        !self.diverged && self.pos == self.transcript.len()
+    }
+}
+
+impl<C: Clone + PartialEq> Device<C> for Replay<C> {
+    fn on_output(&mut self, val: C) {
+        if self.diverged || self.transcript.get(self.pos) != Some(&Exchange::Output(val)) {
+            self.diverged = true;
+        } else {
+            self.pos += 1;
+        }
+    }
+
+    fn next_input(&mut self) -> Option<C> {
+        match self.transcript.get(self.pos) {
+            Some(Exchange::Input(val)) if !self.diverged => {
+                self.pos += 1;
+                Some(val.clone())
+            }
+            _ => {
+                self.diverged = true;
+                None
+            }
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.diverged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ascii::tests::GREETER;
+    use crate::computer::IntCodeComputer;
+    use crate::load::load_program;
+    use crate::machine::Machine;
+    use std::path::Path;
+
+    #[test]
+    fn painting_robot_follows_the_example() {
+        let mut robot = PaintingRobot::new(0);
+        for &(color, turn) in &[(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)] {
+            robot.next_input();
+            robot.on_output(color);
+            robot.on_output(turn);
+        }
+        assert_eq!(robot.painted(), 6);
+        assert_eq!(robot.pos, (0, -1));
+    }
+
+    #[test]
+    fn arcade_tracks_the_ball() {
+        let mut arcade = Arcade::new();
+        for &val in &[1, 2, 3, 6, 5, 4, -1, 0, 12345, 2, 2, 2] {
+            arcade.on_output(val);
+        }
+        assert_eq!(arcade.score(), 12345);
+        assert_eq!(arcade.count(Tile::Block), 1);
+        assert_eq!(arcade.next_input(), Some(1));
+    }
+
+    #[test]
+    fn droid_maps_a_simulated_maze() {
+        let maze = [" ##   ", "#..## ", "#.#..#", "#.O.# ", " ###  "];
+        let at = |(x, y): Position| maze[(y + 1) as usize].as_bytes()[(x + 1) as usize];
+        // The droid starts on the top left open cell, at (1, 1) in `maze`.
+        let mut droid = Droid::new();
+        let mut pos = (0, 0);
+        while let Some(mv) = droid.next_input() {
+            let (dx, dy) = MOVES[(mv - 1) as usize].1;
+            let next = (pos.0 + dx, pos.1 + dy);
+            let status = match at(next) {
+                b'.' => 1,
+                b'O' => 2,
+                _ => 0,
+            };
+            if status != 0 {
+                pos = next;
+            }
+            droid.on_output(status);
+        }
+        assert!(droid.is_done());
+        assert_eq!(pos, (0, 0));
+        let oxygen = droid.oxygen().unwrap();
+        assert_eq!(oxygen, (1, 2));
+        assert_eq!(droid.distances((0, 0))[&oxygen], 3);
+        assert_eq!(droid.distances(oxygen).values().max(), Some(&4));
+    }
+
+    #[test]
+    fn terminal_answers_prompts() {
+        let mut terminal = Terminal::with_script(&[("Name:", "Bob")]);
+        let mut computer = IntCodeComputer::new(&GREETER);
+        assert_eq!(computer.attach(&mut terminal), Detach::Halted);
+        assert_eq!(terminal.screen().lines, ["Name:", "Bob"]);
+        assert_eq!(terminal.screen().values, [1000]);
+
+        let mut terminal = Terminal::with_script(&[("Age:", "42")]);
+        let mut computer = IntCodeComputer::new(&GREETER);
+        assert_eq!(computer.attach(&mut terminal), Detach::Blocked);
+        assert_eq!(terminal.screen().lines, ["Name:"]);
+    }
+
+    #[test]
+    fn recordings_replay_against_the_program() {
+        let program: Vec<i64> = load_program(Path::new("../day11/input/input.txt")).unwrap();
+        let mut recorder = Recorder::new(PaintingRobot::new(0));
+        IntCodeComputer::new(&program).attach(&mut recorder);
+        assert!(recorder.device.painted() > 0);
+
+        let mut replay = Replay::new(recorder.transcript.clone());
+        assert_eq!(
+            IntCodeComputer::new(&program).attach(&mut replay),
+            Detach::Halted
+        );
+        assert!(replay.is_finished());
+
+        // Flip the first color the robot reads.
+        let mut transcript = recorder.transcript;
+        transcript[0] = Exchange::Input(1);
+        let mut replay = Replay::new(transcript);
+        assert_eq!(
+            IntCodeComputer::new(&program).attach(&mut replay),
+            Detach::Done
+        );
+        assert!(replay.divergence().is_some());
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::fault::Fault;
+
+/// A value output by a diagnostic program, with the address of the instruction that output it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub addr: usize,
+    pub value: i64,
+}
+
+/// Runs a diagnostic program on the shared computer with a single input and collects every
+/// output.
+pub fn diagnose(program: &[i64], input: i64) -> Result<Vec<Diagnostic>, Fault> {
+    let mut computer = IntCodeComputer::new(program);
+    computer.add_input(input);
+    let mut diagnostics = Vec::new();
+    loop {
+        let addr = computer.inst_pointer();
+        match computer.step() {
+            None => {}
+            Some(ResultCode::Output(value)) => diagnostics.push(Diagnostic { addr, value }),
+            Some(ResultCode::Terminated) | Some(ResultCode::Input) => return Ok(diagnostics),
+            Some(ResultCode::Fault(fault)) => return Err(fault),
+        }
+    }
+}
+
+/// The outcome of day 5's TEST program. Each test outputs 0 when it passes, or how far off the
+/// result was when it fails, and the last output is the diagnostic code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestReport {
+    pub failures: Vec<Diagnostic>,
+    pub code: Option<Diagnostic>,
+}
+
+impl TestReport {
+    pub fn decode(outputs: &[Diagnostic]) -> Self {
+        match outputs.split_last() {
+            Some((code, tests)) => Self {
+                failures: tests
+                    .iter()
+                    .filter(|test| test.value != 0)
+                    .copied()
+                    .collect(),
+                code: Some(*code),
+            },
+            None => Self {
+                failures: Vec::new(),
+                code: None,
+            },
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        self.failures.is_empty() && self.code.is_some()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(
+                f,
+                "test reported at {} failed, off by {}",
+                failure.addr, failure.value
+            )?;
+        }
+        match self.code {
+            Some(code) => write!(
+                f,
+                "diagnostic code {} (output at {})",
+                code.value, code.addr
+            ),
+            None => write!(f, "no diagnostic code was output"),
+        }
+    }
+}
+
+/// The outcome of day 9's BOOST program in test mode. A working computer outputs only the
+/// keycode; otherwise each malfunctioning opcode is output, with its parameter modes, followed
+/// by a 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoostReport {
+    pub malfunctions: Vec<Diagnostic>,
+    pub keycode: Option<Diagnostic>,
+}
+
+impl BoostReport {
+    pub fn decode(outputs: &[Diagnostic]) -> Self {
+        match outputs {
+            [keycode] => Self {
+                malfunctions: Vec::new(),
+                keycode: Some(*keycode),
+            },
+            _ => Self {
+                malfunctions: outputs
+                    .iter()
+                    .filter(|out| out.value != 0)
+                    .copied()
+                    .collect(),
+                keycode: None,
+            },
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        self.malfunctions.is_empty() && self.keycode.is_some()
+    }
+}
+
+impl fmt::Display for BoostReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for malfunction in &self.malfunctions {
+            writeln!(
+                f,
+                "opcode {} malfunctions (reported at {})",
+                malfunction.value, malfunction.addr
+            )?;
+        }
+        match self.keycode {
+            Some(keycode) => write!(f, "keycode {} (output at {})", keycode.value, keycode.addr),
+            None => write!(f, "no keycode was output"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::load_program;
+    use std::path::Path;
+
+    fn outputs(values: &[(usize, i64)]) -> Vec<Diagnostic> {
+        values
+            .iter()
+            .map(|&(addr, value)| Diagnostic { addr, value })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_test_reports() {
+        let program = load_program(Path::new("../day5/input/input.txt")).unwrap();
+        let report = TestReport::decode(&diagnose(&program, 1).unwrap());
+        assert!(report.is_pass(), "{}", report);
+        assert_eq!(
+            report.to_string(),
+            "diagnostic code 5577461 (output at 220)"
+        );
+
+        let report = TestReport::decode(&outputs(&[(10, 0), (28, 1), (54, 0), (220, 8)]));
+        assert!(!report.is_pass());
+        assert_eq!(
+            report.to_string(),
+            "test reported at 28 failed, off by 1\ndiagnostic code 8 (output at 220)"
+        );
+    }
+
+    #[test]
+    fn decodes_boost_reports() {
+        let program = load_program(Path::new("../day9/input/input.txt")).unwrap();
+        let report = BoostReport::decode(&diagnose(&program, 1).unwrap());
+        assert!(report.is_pass(), "{}", report);
+        assert_eq!(report.keycode.map(|out| out.value), Some(3454977209));
+
+        // What BOOST outputs on a computer that ignores the relative base when storing input.
+        let report = BoostReport::decode(&outputs(&[(48, 203), (50, 0)]));
+        assert!(!report.is_pass());
+        assert_eq!(
+            report.to_string(),
+            "opcode 203 malfunctions (reported at 48)\nno keycode was output"
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Bounded equivalence checking: runs two programs, such as a program and its patched or
+//! optimized version, on the same inputs and reports the first inputs they disagree on.
+//!
+//! Each run is limited to a number of instructions, so programs are only known to agree on the
+//! inputs tried, and only when both finish within the limit.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::fault::Fault;
+use crate::rng::Rng;
+
+/// The values one input is taken from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Values {
+    Set(Vec<i64>),
+    Range(RangeInclusive<i64>),
+}
+
+impl Values {
+    fn first(&self) -> Option<i64> {
+        match self {
+            Values::Set(values) => values.first().copied(),
+            Values::Range(range) if !range.is_empty() => Some(*range.start()),
+            Values::Range(_) => None,
+        }
+    }
+
+    /// The value after the `i`th, which is `val`, or `None` after the last.
+    fn after(&self, i: usize, val: i64) -> Option<i64> {
+        match self {
+            Values::Set(values) => values.get(i + 1).copied(),
+            Values::Range(range) if val < *range.end() => Some(val + 1),
+            Values::Range(_) => None,
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Option<i64> {
+        match self {
+            Values::Set(values) if values.is_empty() => None,
+            Values::Set(values) => Some(values[rng.below(values.len() as u64) as usize]),
+            Values::Range(range) if range.is_empty() => None,
+            Values::Range(range) => {
+                let span = range.end().wrapping_sub(*range.start()) as u64;
+                let offset = match span.checked_add(1) {
+                    Some(len) => rng.below(len),
+                    None => rng.next_u64(),
+                };
+                Some(range.start().wrapping_add(offset as i64))
+            }
+        }
+    }
+}
+
+/// Parses `1,2,3` as a set, or `0..10` and `0..=9` as ranges, with the same meaning as in Rust.
+impl FromStr for Values {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let number = |text: &str| {
+            text.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("expected a number, got {:?}", text.trim()))
+        };
+        if let Some((start, end)) = text.split_once("..=") {
+            return Ok(Values::Range(number(start)?..=number(end)?));
+        }
+        if let Some((start, end)) = text.split_once("..") {
+            let end = number(end)?.checked_sub(1).ok_or("the range is empty")?;
+            return Ok(Values::Range(number(start)?..=end));
+        }
+        let values = text.split(',').map(number).collect::<Result<_, _>>()?;
+        Ok(Values::Set(values))
+    }
+}
+
+/// The inputs to run both programs on. Each case is a sequence of inputs, given to the programs
+/// up front.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Domain {
+    Explicit(Vec<Vec<i64>>),
+    /// Every combination of one value per input, in order, with the last input varying fastest.
+    Exhaustive(Vec<Values>),
+    /// `samples` combinations of one value per input, drawn at random from `seed`.
+    Sampled {
+        inputs: Vec<Values>,
+        samples: usize,
+        seed: u64,
+    },
+}
+
+impl Domain {
+    fn cases(&self) -> Box<dyn Iterator<Item = Vec<i64>> + '_> {
+        match self {
+            Domain::Explicit(cases) => Box::new(cases.iter().cloned()),
+            Domain::Exhaustive(inputs) => Box::new(Combinations::new(inputs)),
+            Domain::Sampled {
+                inputs,
+                samples,
+                seed,
+            } => {
+                let mut rng = Rng::new(*seed);
+                Box::new((0..*samples).map_while(move |_| {
+                    inputs
+                        .iter()
+                        .map(|values| values.sample(&mut rng))
+                        .collect()
+                }))
+            }
+        }
+    }
+}
+
+struct Combinations<'a> {
+    inputs: &'a [Values],
+    /// The next combination, and the index of each value in it.
+    next: Option<(Vec<usize>, Vec<i64>)>,
+}
+
+impl<'a> Combinations<'a> {
+    fn new(inputs: &'a [Values]) -> Self {
+        let first: Option<Vec<_>> = inputs.iter().map(Values::first).collect();
+        Self {
+            inputs,
+            next: first.map(|first| (vec![0; inputs.len()], first)),
+        }
+    }
+}
+
+impl<'a> Iterator for Combinations<'a> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        let (indices, values) = self.next.as_mut()?;
+        let combination = values.clone();
+        let mut pos = self.inputs.len();
+        loop {
+            if pos == 0 {
+                self.next = None;
+                break;
+            }
+            pos -= 1;
+            match self.inputs[pos].after(indices[pos], values[pos]) {
+                Some(val) => {
+                    indices[pos] += 1;
+                    values[pos] = val;
+                    break;
+                }
+                None => {
+                    indices[pos] = 0;
+                    values[pos] = self.inputs[pos].first().unwrap();
+                }
+            }
+        }
+        Some(combination)
+    }
+}
+
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum End {
+    Halted,
+    /// The program wanted more inputs than the case has.
+    NeedInput,
+    Fault(Fault),
+    OutOfSteps,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub outputs: Vec<i64>,
+    pub end: End,
+}
+
+impl Run {
+    pub fn new(program: &[i64], inputs: &[i64], max_steps: u64) -> Self {
+        let mut computer = IntCodeComputer::new(program);
+        for &val in inputs {
+            computer.add_input(val);
+        }
+        let mut end = End::OutOfSteps;
+        for _ in 0..max_steps {
+            match computer.step() {
+                None | Some(ResultCode::Output(_)) => continue,
+                Some(ResultCode::Terminated) => end = End::Halted,
+                Some(ResultCode::Input) => end = End::NeedInput,
+                Some(ResultCode::Fault(fault)) => end = End::Fault(fault),
+            }
+            break;
+        }
+        Self {
+            outputs: computer.output().to_vec(),
+            end,
+        }
+    }
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.outputs.as_slice() {
+            [] => write!(f, "no output")?,
+            outputs => {
+                let outputs: Vec<_> = outputs.iter().map(|val| val.to_string()).collect();
+                write!(f, "output {}", outputs.join(","))?;
+            }
+        }
+        match &self.end {
+            End::Halted => write!(f, ", then halted"),
+            End::NeedInput => write!(f, ", then wanted more input"),
+            End::Fault(fault) => write!(f, ", then faulted: {}", fault),
+            End::OutOfSteps => write!(f, ", then ran out of steps"),
+        }
+    }
+}
+
+/// Whether two runs agree: `Some(true)` or `Some(false)`, or `None` when one of them ran out of
+/// steps before it could tell. Faults agree whatever they are, since an optimized program may
+/// fault at a different address.
+fn agree(left: &Run, right: &Run) -> Option<bool> {
+    if left.end == End::OutOfSteps || right.end == End::OutOfSteps {
+        let len = left.outputs.len().min(right.outputs.len());
+        let unfinished = |run: &Run| run.end == End::OutOfSteps && run.outputs.len() == len;
+        let outputs_agree = left.outputs[..len] == right.outputs[..len];
+        return match (outputs_agree, left.end == right.end) {
+            (false, _) => Some(false),
+            (true, true) => None,
+            // The shorter run may still catch up.
+            (true, false) if unfinished(left) || unfinished(right) => None,
+            (true, false) => Some(false),
+        };
+    }
+    let same_end = match (&left.end, &right.end) {
+        (End::Fault(_), End::Fault(_)) => true,
+        (left, right) => left == right,
+    };
+    Some(left.outputs == right.outputs && same_end)
+}
+
+/// The first inputs the programs disagreed on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub inputs: Vec<i64>,
+    pub left: Run,
+    pub right: Run,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// How many cases were run, including the one that differed.
+    pub cases: usize,
+    /// Cases that could not be decided because a program ran out of steps.
+    pub undecided: Vec<Vec<i64>>,
+    pub difference: Option<Difference>,
+}
+
+impl Report {
+    pub fn is_equivalent(&self) -> bool {
+        self.difference.is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.difference {
+            Some(difference) => {
+                let inputs: Vec<_> = difference
+                    .inputs
+                    .iter()
+                    .map(|val| val.to_string())
+                    .collect();
+                writeln!(
+                    f,
+                    "the programs differ on case {}, inputs [{}]",
+                    self.cases,
+                    inputs.join(",")
+                )?;
+                writeln!(f, "  left:  {}", difference.left)?;
+                write!(f, "  right: {}", difference.right)
+            }
+            None => {
+                write!(f, "the programs agree on {} cases", self.cases)?;
+                if !self.undecided.is_empty() {
+                    write!(
+                        f,
+                        ", {} of them undecided within the step limit",
+                        self.undecided.len()
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Runs `left` and `right` on every case in `domain`, each for at most `max_steps`
+/// instructions, stopping at the first case they disagree on.
+pub fn check(left: &[i64], right: &[i64], domain: &Domain, max_steps: u64) -> Report {
+    let mut report = Report {
+        cases: 0,
+        undecided: Vec::new(),
+        difference: None,
+    };
+    for inputs in domain.cases() {
+        report.cases += 1;
+        let runs = (
+            Run::new(left, &inputs, max_steps),
+            Run::new(right, &inputs, max_steps),
+        );
+        match agree(&runs.0, &runs.1) {
+            Some(true) => {}
+            None => report.undecided.push(inputs),
+            Some(false) => {
+                report.difference = Some(Difference {
+                    inputs,
+                    left: runs.0,
+                    right: runs.1,
+                });
+                break;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    fn program(body: &str) -> Vec<i64> {
+        compile(&format!("fn main() {{ {} }}", body)).unwrap()
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!("1, 2,-3".parse(), Ok(Values::Set(vec![1, 2, -3])));
+        assert_eq!("-2..3".parse(), Ok(Values::Range(-2..=2)));
+        assert_eq!("0..=9".parse(), Ok(Values::Range(0..=9)));
+        assert!("1..x".parse::<Values>().is_err());
+    }
+
+    #[test]
+    fn domains_list_their_cases() {
+        let domain = Domain::Exhaustive(vec![Values::Range(0..=1), Values::Set(vec![5, 7])]);
+        let cases: Vec<_> = domain.cases().collect();
+        assert_eq!(cases, [[0, 5], [0, 7], [1, 5], [1, 7]]);
+        assert_eq!(
+            Domain::Exhaustive(vec![Values::Set(vec![])])
+                .cases()
+                .count(),
+            0
+        );
+
+        let sampled = |seed| Domain::Sampled {
+            inputs: vec![Values::Range(i64::MIN..=i64::MAX), Values::Range(-3..=3)],
+            samples: 50,
+            seed,
+        };
+        let cases: Vec<_> = sampled(1).cases().collect();
+        assert_eq!(cases.len(), 50);
+        assert!(cases.iter().all(|case| (-3..=3).contains(&case[1])));
+        assert_eq!(sampled(1).cases().collect::<Vec<_>>(), cases);
+        assert_ne!(sampled(2).cases().collect::<Vec<_>>(), cases);
+    }
+
+    #[test]
+    fn finds_the_first_difference() {
+        let double = program("var x = input(); output(x + x);");
+        let times_two = program("var x = input(); output(x * 2);");
+        let square = program("var x = input(); output(x * x);");
+        let domain = Domain::Exhaustive(vec![Values::Range(-50..=50)]);
+        let report = check(&double, &times_two, &domain, 1000);
+        assert!(report.is_equivalent(), "{}", report);
+        assert_eq!(report.to_string(), "the programs agree on 101 cases");
+
+        let domain = Domain::Exhaustive(vec![Values::Range(0..=5)]);
+        let report = check(&double, &square, &domain, 1000);
+        assert_eq!(
+            report.to_string(),
+            "the programs differ on case 2, inputs [1]\n  \
+             left:  output 2, then halted\n  \
+             right: output 1, then halted"
+        );
+        let report = check(&double, &[99], &Domain::Explicit(vec![vec![]]), 1000);
+        assert_eq!(
+            report.difference.unwrap().left.to_string(),
+            "no output, then wanted more input"
+        );
+    }
+
+    #[test]
+    fn budgets_leave_cases_undecided() {
+        // Counts down from the input before outputting it, so large inputs run out of steps.
+        let slow = program("var x = input(); var n = x; while n > 0 { n = n - 1; } output(x);");
+        let fast = program("output(input());");
+        let wrong = program("var x = input(); output(x + 1);");
+        let domain = Domain::Explicit(vec![vec![1], vec![2], vec![100_000]]);
+        let report = check(&slow, &fast, &domain, 1000);
+        assert!(report.is_equivalent());
+        assert_eq!(report.undecided, [[100_000]]);
+
+        // The slow program has not output anything yet, so it may still agree.
+        let report = check(&slow, &wrong, &Domain::Explicit(vec![vec![100_000]]), 1000);
+        assert!(report.is_equivalent());
+        assert_eq!(report.undecided.len(), 1);
+        // Outputs that already disagree are a difference, however long the run.
+        let looping = program("output(input()); while 1 { }");
+        let report = check(&looping, &wrong, &Domain::Explicit(vec![vec![3]]), 1000);
+        assert_eq!(report.difference.unwrap().left.outputs, [3]);
+    }
+}
@@ -0,0 +1,177 @@
+use std::error::Error;
+use std::fmt;
+
+/// What ADD and MUL do when the result does not fit in a cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Arithmetic {
+    /// Wrap around in two's complement.
+    Wrapping,
+    /// Stop the program with `Fault::Overflow`.
+    #[default]
+    Checked,
+    /// Clamp to the largest or smallest value a cell can hold.
+    Saturating,
+}
+
+/// Why a program stopped without halting. The computer is left as it was before the faulting
+/// instruction, and keeps reporting the same fault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fault<C = i64> {
+    /// ADD or MUL under `Arithmetic::Checked`, or a relative address or relative base
+    /// adjustment, overflowed.
+    Overflow {
+        ip: usize,
+        opcode: i64,
+        operands: [C; 2],
+    },
+    InvalidOpcode {
+        ip: usize,
+        opcode: i64,
+    },
+    InvalidMode {
+        ip: usize,
+        inst: C,
+    },
+    /// A read, write or jump to an address that is negative or does not fit in `usize`.
+    InvalidAddress {
+        ip: usize,
+        addr: C,
+    },
+    /// An instruction tried to write to an immediate-mode parameter.
+    ImmediateWrite {
+        ip: usize,
+        inst: C,
+    },
+}
+
+impl<C> Fault<C> {
+    pub fn ip(&self) -> usize {
+        match *self {
+            Fault::Overflow { ip, .. }
+            | Fault::InvalidOpcode { ip, .. }
+            | Fault::InvalidMode { ip, .. }
+            | Fault::InvalidAddress { ip, .. }
+            | Fault::ImmediateWrite { ip, .. } => ip,
+        }
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for Fault<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Overflow {
+                ip,
+                opcode,
+                operands: [fst, snd],
+            } => write!(
+                f,
+                "overflow at {}: opcode {} with operands {} and {}",
+                ip, opcode, fst, snd
+            ),
+            Fault::InvalidOpcode { ip, opcode } => write!(
+                f,
+                "invalid opcode at {}: opcode must be 1, 2, 3, 4, 5, 6, 7, 8, 9 or 99, got {}",
+                ip, opcode
+            ),
+            Fault::InvalidMode { ip, inst } => write!(
+                f,
+                "invalid mode at {}: modes must be 0, 1 or 2, got instruction {}",
+                ip, inst
+            ),
+            Fault::InvalidAddress { ip, addr } => {
+                write!(f, "invalid address at {}: {}", ip, addr)
+            }
+            Fault::ImmediateWrite { ip, inst } => write!(
+                f,
+                "write to an immediate parameter at {}: instruction {}",
+                ip, inst
+            ),
+        }
+    }
+}
+
+impl<C: fmt::Debug + fmt::Display> Error for Fault<C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::{IntCodeComputer, ResultCode};
+
+    // Outputs fst + snd, then fst * snd.
+    fn add_then_mul(fst: i64, snd: i64) -> Vec<i64> {
+        vec![
+            1, 13, 14, 15, 2, 13, 14, 16, 4, 15, 4, 16, 99, fst, snd, 0, 0,
+        ]
+    }
+
+    fn run(program: &[i64], arithmetic: Arithmetic) -> (Vec<i64>, Option<Fault>) {
+        let mut computer = IntCodeComputer::new(program).with_arithmetic(arithmetic);
+        let mut outputs = Vec::new();
+        loop {
+            match computer.run_one_turn() {
+                ResultCode::Output(val) => outputs.push(val),
+                ResultCode::Fault(fault) => return (outputs, Some(fault)),
+                _ => return (outputs, None),
+            }
+        }
+    }
+
+    #[test]
+    fn overflow_follows_the_arithmetic_policy() {
+        let program = add_then_mul(i64::MAX, 2);
+        assert_eq!(
+            run(&program, Arithmetic::Wrapping),
+            (vec![i64::MIN + 1, -2], None)
+        );
+        assert_eq!(
+            run(&program, Arithmetic::Saturating),
+            (vec![i64::MAX, i64::MAX], None)
+        );
+        assert_eq!(
+            run(&program, Arithmetic::Checked),
+            (
+                vec![],
+                Some(Fault::Overflow {
+                    ip: 0,
+                    opcode: 1,
+                    operands: [i64::MAX, 2]
+                })
+            )
+        );
+        assert_eq!(
+            run(&add_then_mul(i64::MIN / 2, 3), Arithmetic::default()).1,
+            Some(Fault::Overflow {
+                ip: 4,
+                opcode: 2,
+                operands: [i64::MIN / 2, 3]
+            })
+        );
+    }
+
+    #[test]
+    fn faults_stop_the_program() {
+        let mut computer = IntCodeComputer::new(&[104, 1, 42, 99]);
+        let fault = Fault::InvalidOpcode { ip: 2, opcode: 42 };
+        assert_eq!(computer.run_one_turn(), ResultCode::Output(1));
+        assert_eq!(computer.run_one_turn(), ResultCode::Fault(fault));
+        assert_eq!(computer.step(), Some(ResultCode::Fault(fault)));
+        assert_eq!(computer.fault(), Some(fault));
+        assert!(!computer.is_halted());
+
+        let mut computer = IntCodeComputer::new(&[3, -1, 99]);
+        computer.add_input(5);
+        assert_eq!(
+            computer.run_one_turn(),
+            ResultCode::Fault(Fault::InvalidAddress { ip: 0, addr: -1 })
+        );
+        assert!(!computer.is_blocked());
+        assert_eq!(
+            IntCodeComputer::new(&[1101, 1, 2, 3, 11101, 1, 2, 3]).run_one_turn(),
+            ResultCode::Fault(Fault::ImmediateWrite { ip: 4, inst: 11101 })
+        );
+        assert_eq!(
+            IntCodeComputer::new(&[301, 1, 2, 3]).run_one_turn(),
+            ResultCode::Fault(Fault::InvalidMode { ip: 0, inst: 301 })
+        );
+    }
+}
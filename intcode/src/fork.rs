@@ -0,0 +1,128 @@
+use crate::cell::Cell;
+use crate::computer::{IntCodeComputer, ResultCode};
+
+/// A computer run until it first blocks on input, kept so that copies of it can carry on with
+/// different inputs without running the instructions before that point again.
+///
+/// Programs that read several inputs before doing most of their work can be cached further
+/// with `then`, once per shared prefix of inputs.
+#[derive(Clone, Debug)]
+pub struct Prologue<C = i64> {
+    computer: IntCodeComputer<C>,
+    steps: u64,
+}
+
+impl Prologue {
+    pub fn new(program: &[i64]) -> Self {
+        Self::of(IntCodeComputer::new(program))
+    }
+}
+
+impl<C: Cell> Prologue<C> {
+    /// Runs `computer` until it blocks on input, halts or faults.
+    pub fn of(computer: IntCodeComputer<C>) -> Self {
+        let mut prologue = Self { computer, steps: 0 };
+        prologue.run();
+        prologue
+    }
+
+    fn run(&mut self) {
+        while !self.computer.is_blocked() {
+            match self.computer.step() {
+                Some(ResultCode::Terminated) | Some(ResultCode::Fault(_)) => break,
+                Some(ResultCode::Input) => unreachable!(),
+                Some(ResultCode::Output(_)) | None => self.steps += 1,
+            }
+        }
+    }
+
+    /// The prologue extended by giving the program `inputs` and running it until it blocks
+    /// again.
+    pub fn then(&self, inputs: &[C]) -> Self {
+        let mut prologue = self.clone();
+        for val in inputs {
+            prologue.computer.add_input(val.clone());
+        }
+        prologue.run();
+        prologue
+    }
+
+    /// A copy of the computer, ready to be given input.
+    pub fn fork(&self) -> IntCodeComputer<C> {
+        self.computer.clone()
+    }
+
+    /// Overwrites `computer` with a copy of the cached one, reusing its memory rather than
+    /// allocating.
+    pub fn fork_into(&self, computer: &mut IntCodeComputer<C>) {
+        computer.clone_from(&self.computer);
+    }
+
+    pub fn computer(&self) -> &IntCodeComputer<C> {
+        &self.computer
+    }
+
+    /// How many instructions every fork skips.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    // Does a lot of work before reading anything, then outputs the sum of what it has computed
+    // and two inputs.
+    const SOURCE: &str = "
+        fn main() {
+            var total = 0;
+            var i = 0;
+            while i < 200 {
+                total = total + i * i;
+                i = i + 1;
+            }
+            output(total);
+            var x = input();
+            total = total + x;
+            var y = input();
+            output(total + y);
+        }
+    ";
+
+    #[test]
+    fn forks_carry_on_like_fresh_computers() {
+        let program = compile(SOURCE).unwrap();
+        let prologue = Prologue::new(&program);
+        assert!(prologue.steps() > 1000);
+        assert_eq!(prologue.computer().output(), [2646700]);
+
+        let mut computer = IntCodeComputer::new(&[99]);
+        for (x, y) in [(1, 2), (30, 40), (-5, 0)] {
+            let mut fresh = IntCodeComputer::new(&program);
+            fresh.add_input(x);
+            fresh.add_input(y);
+            let expected = fresh.run_program();
+
+            let mut fork = prologue.fork();
+            fork.add_input(x);
+            fork.add_input(y);
+            assert_eq!(fork.run_program(), expected);
+
+            prologue.then(&[x]).fork_into(&mut computer);
+            computer.add_input(y);
+            assert_eq!(computer.run_program(), expected);
+        }
+        // Forking does not disturb the cached computer.
+        assert!(prologue.computer().is_blocked());
+    }
+
+    #[test]
+    fn stops_at_halts_and_faults() {
+        let prologue = Prologue::new(&[104, 1, 99]);
+        assert!(prologue.computer().is_halted());
+        assert_eq!(prologue.fork().run_program(), [1]);
+        assert!(Prologue::new(&[42]).fork().fault().is_some());
+    }
+}
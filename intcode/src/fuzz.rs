@@ -0,0 +1,600 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::conformance::{check, quietly, Case, Deviation, Execution, Implementation};
+use crate::rng::Rng;
+
+/// Scratch cells placed after the code of a generated program.
+const DATA_CELLS: usize = 8;
+
+/// Generated programs that do not fit the `Config` are thrown away and generated again, at most
+/// this many times per case.
+const ATTEMPTS: usize = 100;
+
+/// How far past the end of a program its memory may grow before the run is thrown away.
+const GROWTH: usize = 64;
+
+/// What programs to generate and how many.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The puzzle whose Intcode the programs stick to, as in the conformance corpus: 2, 5 or 9.
+    pub day: u32,
+    pub cases: usize,
+    /// Case `i` is generated from seed `seed + i`, so a failure can be replayed on its own.
+    pub seed: u64,
+    /// The most instructions a program is generated with, not counting the final HALT.
+    pub instructions: usize,
+    /// Programs that run longer than this on the shared VM are thrown away.
+    pub max_steps: usize,
+    /// How many inputs each program is given. Programs read at most that many.
+    pub inputs: usize,
+    /// Whether programs may read input after they output, which is where VMs that tie inputs
+    /// to outputs, as day 7 and day 9 do, disagree. VMs that loop forever on such programs can
+    /// turn it off.
+    pub reads_after_output: bool,
+    /// Where minimized failures are saved, one file per failure. `None` does not save them.
+    pub failures: Option<PathBuf>,
+}
+
+impl Config {
+    /// 200 cases of day `day` Intcode with one input each, which may be read after outputs,
+    /// without saving failures.
+    pub fn new(day: u32) -> Self {
+        Self {
+            day,
+            cases: 200,
+            seed: 0,
+            instructions: 12,
+            max_steps: 500,
+            inputs: 1,
+            reads_after_output: true,
+            failures: None,
+        }
+    }
+}
+
+/// A program on which some implementations disagree with the shared VM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub seed: u64,
+    /// The minimized program, expecting what the shared VM did with it.
+    pub case: Case,
+    pub deviations: Vec<(String, Deviation)>,
+    pub saved: Option<PathBuf>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "seed {}", self.seed)?;
+        if let Some(path) = &self.saved {
+            write!(f, ", saved to {}", path.display())?;
+        }
+        writeln!(f, ", program {:?}", self.case.program)?;
+        for (name, deviation) in &self.deviations {
+            writeln!(f, "  {}: {}", name, deviation)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Programs run on every implementation.
+    pub programs: usize,
+    /// Programs thrown away before running, for not fitting the `Config`.
+    pub rejected: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} programs run, {} rejected, {} failures",
+            self.programs,
+            self.rejected,
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            write!(f, "{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+fn between(rng: &mut Rng, lo: i64, hi: i64) -> i64 {
+    lo + rng.below((hi - lo + 1) as u64) as i64
+}
+
+fn width(opcode: i64) -> usize {
+    match opcode {
+        1 | 2 | 7 | 8 => 4,
+        5 | 6 => 3,
+        3 | 4 | 9 => 2,
+        _ => 1,
+    }
+}
+
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    day: u32,
+    /// Where every instruction starts, including the final HALT.
+    starts: Vec<usize>,
+    data: usize,
+    len: usize,
+}
+
+impl Generator<'_> {
+    fn value(&mut self) -> i64 {
+        if self.day >= 9 && self.rng.below(10) == 0 {
+            between(self.rng, -(1 << 40), 1 << 40)
+        } else {
+            between(self.rng, -3, 9)
+        }
+    }
+
+    /// A `(mode, operand)` pair naming a cell. The relative base is assumed to point at the
+    /// data, which the prologue makes true until an ARB moves it.
+    fn cell(&mut self) -> (i64, i64) {
+        let addr = match self.rng.below(10) {
+            0 => self.rng.below(self.len as u64) as usize,
+            1 if self.day >= 9 => self.len + self.rng.below(4) as usize,
+            _ => self.data + self.rng.below(DATA_CELLS as u64) as usize,
+        };
+        if self.day >= 9 && self.rng.below(3) == 0 {
+            (2, addr as i64 - self.data as i64)
+        } else {
+            (0, addr as i64)
+        }
+    }
+
+    fn read(&mut self) -> (i64, i64) {
+        if self.day >= 5 && self.rng.below(10) < 4 {
+            (1, self.value())
+        } else {
+            self.cell()
+        }
+    }
+
+    fn target(&mut self) -> (i64, i64) {
+        if self.rng.below(10) == 0 {
+            self.cell()
+        } else {
+            let start = self.starts[self.rng.below(self.starts.len() as u64) as usize];
+            (1, start as i64)
+        }
+    }
+
+    fn instruction(&mut self, opcode: i64) -> Vec<i64> {
+        let operands = match opcode {
+            1 | 2 | 7 | 8 => vec![self.read(), self.read(), self.cell()],
+            3 => vec![self.cell()],
+            4 => vec![self.read()],
+            5 | 6 => vec![self.read(), self.target()],
+            _ => vec![(1, between(self.rng, -2, 2))],
+        };
+        let mut inst = opcode;
+        let mut scale = 100;
+        for (mode, _) in &operands {
+            inst += mode * scale;
+            scale *= 10;
+        }
+        let mut cells = vec![inst];
+        cells.extend(operands.into_iter().map(|(_, operand)| operand));
+        cells
+    }
+}
+
+/// A random program and its inputs. The program may still fault or run forever; `reference`
+/// tells which ones to keep.
+fn generate(config: &Config, rng: &mut Rng) -> (Vec<i64>, Vec<i64>) {
+    let opcodes: &[i64] = match config.day {
+        2 => &[1, 2],
+        5 => &[1, 2, 3, 4, 5, 6, 7, 8],
+        _ => &[1, 2, 3, 4, 5, 6, 7, 8, 9],
+    };
+    let count = 1 + rng.below(config.instructions.max(1) as u64) as usize;
+    let mut ops: Vec<_> = (0..count)
+        .map(|_| opcodes[rng.below(opcodes.len() as u64) as usize])
+        .collect();
+    if config.day >= 9 {
+        // Points the relative base at the data.
+        ops.insert(0, 9);
+    }
+
+    let mut starts = Vec::new();
+    let mut addr = 0;
+    for &opcode in &ops {
+        starts.push(addr);
+        addr += width(opcode);
+    }
+    starts.push(addr);
+    let data = addr + 1;
+    let len = data + DATA_CELLS;
+
+    let mut gen = Generator {
+        rng,
+        day: config.day,
+        starts,
+        data,
+        len,
+    };
+    let mut program = Vec::with_capacity(len);
+    for (i, &opcode) in ops.iter().enumerate() {
+        if i == 0 && config.day >= 9 {
+            program.extend(&[109, data as i64]);
+        } else {
+            program.extend(gen.instruction(opcode));
+        }
+    }
+    program.push(99);
+    for _ in 0..DATA_CELLS {
+        program.push(gen.value());
+    }
+    let inputs = (0..config.inputs).map(|_| gen.value()).collect();
+    (program, inputs)
+}
+
+/// Whether `inst` only uses what day `day` introduced.
+fn within_day(day: u32, inst: i64) -> bool {
+    match day {
+        2 => inst == 1 || inst == 2 || inst == 99,
+        5 => {
+            let (opcode, mut modes) = (inst % 100, inst / 100);
+            while modes > 0 {
+                if modes % 10 > 1 {
+                    return false;
+                }
+                modes /= 10;
+            }
+            inst >= 0 && ((1..=8).contains(&opcode) || opcode == 99)
+        }
+        _ => true,
+    }
+}
+
+/// Whether the next instruction and every cell it names lie below `limit`.
+fn in_bounds(computer: &IntCodeComputer, limit: usize) -> bool {
+    let (memory, ip) = (computer.memory(), computer.inst_pointer());
+    if ip >= limit {
+        return false;
+    }
+    let cell = |addr: usize| memory.get(addr).copied().unwrap_or(0);
+    let inst = cell(ip);
+    let mut modes = inst / 100;
+    for offset in 1..width(inst % 100) {
+        let addr = match modes % 10 {
+            0 => cell(ip + offset),
+            2 => cell(ip + offset).saturating_add(computer.relative_base()),
+            _ => 0,
+        };
+        if addr >= limit as i64 {
+            return false;
+        }
+        modes /= 10;
+    }
+    true
+}
+
+/// What the shared VM does with `program`, or `None` if the run does not fit `config`: it
+/// faults, runs too long, grows its memory too far, reads more inputs than it is given or,
+/// unless the config allows it, after an output, or strays out of the day's Intcode. Before
+/// day 9 that includes growing memory and values outside `i32`. Inputs are given one at a time
+/// as the program asks for them, so reads after outputs see the same values as if they had all
+/// been queued up front.
+fn reference(config: &Config, program: &[i64], inputs: &[i64]) -> Option<Execution> {
+    let mut computer = IntCodeComputer::new(program);
+    let mut inputs = inputs.iter();
+    for _ in 0..config.max_steps {
+        if !in_bounds(&computer, program.len() + GROWTH) {
+            return None;
+        }
+        let inst = computer
+            .memory()
+            .get(computer.inst_pointer())
+            .copied()
+            .unwrap_or(0);
+        if !within_day(config.day, inst) {
+            return None;
+        }
+        if inst % 100 == 3 && !config.reads_after_output && !computer.output().is_empty() {
+            return None;
+        }
+        match computer.step() {
+            Some(ResultCode::Terminated) => {
+                return Some(Execution {
+                    outputs: computer.output().to_vec(),
+                    memory: computer.memory().to_vec(),
+                })
+            }
+            Some(ResultCode::Input) => computer.add_input(*inputs.next()?),
+            Some(ResultCode::Fault(_)) => return None,
+            Some(ResultCode::Output(_)) | None => {}
+        }
+        if config.day < 9
+            && (computer.memory().len() != program.len()
+                || computer
+                    .memory()
+                    .iter()
+                    .any(|&val| i32::try_from(val).is_err()))
+        {
+            return None;
+        }
+    }
+    None
+}
+
+/// The deviations of `implementations` on a program that fits `config`, or `None` if the
+/// program does not fit or nothing deviates.
+fn deviations(
+    config: &Config,
+    implementations: &[(&str, &dyn Implementation)],
+    program: &[i64],
+    inputs: &[i64],
+) -> Option<(Case, Vec<(String, Deviation)>)> {
+    let expected = reference(config, program, inputs)?;
+    let case = Case {
+        name: String::new(),
+        day: config.day,
+        program: program.to_vec(),
+        inputs: inputs.to_vec(),
+        outputs: expected.outputs,
+        memory: Some(expected.memory),
+    };
+    let deviations: Vec<_> = implementations
+        .iter()
+        .filter_map(|&(name, implementation)| {
+            let deviation = check(implementation, &case).err()?;
+            Some((name.to_string(), deviation))
+        })
+        .collect();
+    if deviations.is_empty() {
+        None
+    } else {
+        Some((case, deviations))
+    }
+}
+
+/// How far `val` is from the simplest values, 0 and then HALT. Shrinking only ever lowers it.
+fn rank(val: i64) -> u64 {
+    match val {
+        0 => 0,
+        99 => 1,
+        val => 2 + val.unsigned_abs(),
+    }
+}
+
+fn shrink(val: i64) -> Vec<i64> {
+    let mut candidates = vec![0, 99, val / 2];
+    candidates.retain(|&candidate| rank(candidate) < rank(val));
+    candidates
+}
+
+/// Shrinks a failing program and its inputs until no cut of up to four cells, dropped input or
+/// simpler value still fails.
+fn minimize(
+    config: &Config,
+    implementations: &[(&str, &dyn Implementation)],
+    mut failure: (Case, Vec<(String, Deviation)>),
+) -> (Case, Vec<(String, Deviation)>) {
+    loop {
+        let (program, inputs) = (&failure.0.program, &failure.0.inputs);
+        let mut candidates = Vec::new();
+        for (start, end) in (0..program.len()).flat_map(|start| {
+            (start + 1..=(start + 4).min(program.len())).map(move |end| (start, end))
+        }) {
+            let mut shorter = program[..start].to_vec();
+            shorter.extend(&program[end..]);
+            // Cells that look like addresses past the cut may need to follow the code they name.
+            let moved: Vec<_> = shorter
+                .iter()
+                .map(|&val| match usize::try_from(val) {
+                    Ok(addr) if addr >= end && addr < program.len() => val - (end - start) as i64,
+                    _ => val,
+                })
+                .collect();
+            candidates.push((moved, inputs.clone()));
+            candidates.push((shorter, inputs.clone()));
+        }
+        if !inputs.is_empty() {
+            candidates.push((program.clone(), inputs[..inputs.len() - 1].to_vec()));
+        }
+        for (addr, &val) in program.iter().enumerate() {
+            for candidate in shrink(val) {
+                let mut program = program.clone();
+                program[addr] = candidate;
+                candidates.push((program, inputs.clone()));
+            }
+        }
+        for (i, &val) in inputs.iter().enumerate() {
+            for candidate in shrink(val) {
+                let mut inputs = inputs.clone();
+                inputs[i] = candidate;
+                candidates.push((program.clone(), inputs));
+            }
+        }
+
+        let smaller = candidates
+            .into_iter()
+            .find_map(|(program, inputs)| deviations(config, implementations, &program, &inputs));
+        match smaller {
+            Some(smaller) => failure = smaller,
+            None => return failure,
+        }
+    }
+}
+
+fn save(config: &Config, failure: &Failure) -> io::Result<Option<PathBuf>> {
+    let dir = match &config.failures {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.txt", failure.case.name));
+    let mut text = format!("# Found by intcode::fuzz with seed {}.\n", failure.seed);
+    for (name, deviation) in &failure.deviations {
+        text += &format!("# {}: {}\n", name, deviation);
+    }
+    text += &failure.case.to_string();
+    fs::write(&path, text)?;
+    Ok(Some(path))
+}
+
+/// Runs `config.cases` random programs on every implementation and compares what they do with
+/// what the shared VM does: outputs, final memory, and whether they halt rather than panic.
+/// Each failure is minimized and saved to `config.failures` in the conformance corpus format.
+///
+/// An implementation that loops forever on a program the shared VM halts on hangs the run.
+pub fn run(config: &Config, implementations: &[(&str, &dyn Implementation)]) -> io::Result<Report> {
+    let mut report = Report::default();
+    quietly(|| {
+        for i in 0..config.cases {
+            let seed = config.seed.wrapping_add(i as u64);
+            let mut rng = Rng::new(seed);
+            let (program, inputs) =
+                match (0..ATTEMPTS)
+                    .map(|_| generate(config, &mut rng))
+                    .find(|(program, inputs)| {
+                        let fits = reference(config, program, inputs).is_some();
+                        if !fits {
+                            report.rejected += 1;
+                        }
+                        fits
+                    }) {
+                    Some(generated) => generated,
+                    None => continue,
+                };
+
+            report.programs += 1;
+            if let Some(failure) = deviations(config, implementations, &program, &inputs) {
+                let (mut case, deviations) = minimize(config, implementations, failure);
+                case.name = format!("seed-{}", seed);
+                let mut failure = Failure {
+                    seed,
+                    case,
+                    deviations,
+                    saved: None,
+                };
+                failure.saved = save(config, &failure)?;
+                report.failures.push(failure);
+            }
+        }
+        Ok(report)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigInt;
+    use crate::conformance::{load_cases, run_shared};
+    use crate::fault::Arithmetic;
+
+    fn run_wrapping(program: &[i64], inputs: &[i64]) -> Execution {
+        let mut computer = IntCodeComputer::new(program).with_arithmetic(Arithmetic::Wrapping);
+        for &val in inputs {
+            computer.add_input(val);
+        }
+        let outputs = computer.run_program();
+        Execution {
+            outputs,
+            memory: computer.memory().to_vec(),
+        }
+    }
+
+    #[test]
+    fn shared_vms_agree() {
+        for &day in &[2, 5, 9] {
+            let config = Config {
+                inputs: 3,
+                ..Config::new(day)
+            };
+            let report = run(
+                &config,
+                &[
+                    ("i128", &run_shared::<i128>),
+                    ("BigInt", &run_shared::<BigInt>),
+                    ("wrapping", &run_wrapping),
+                ],
+            )
+            .unwrap();
+            assert!(report.is_clean(), "{}", report);
+            assert_eq!(report.programs, config.cases, "{}", report);
+        }
+    }
+
+    #[test]
+    fn failures_are_minimized_and_saved() {
+        // Adds one to every output.
+        let off_by_one = |program: &[i64], inputs: &[i64]| {
+            let mut execution = run_shared::<i64>(program, inputs);
+            for val in &mut execution.outputs {
+                *val += 1;
+            }
+            execution
+        };
+        let dir = std::env::temp_dir().join(format!("intcode-fuzz-{}", std::process::id()));
+        let config = Config {
+            cases: 20,
+            failures: Some(dir.clone()),
+            ..Config::new(5)
+        };
+        let report = run(&config, &[("off by one", &off_by_one)]).unwrap();
+        assert!(!report.is_clean());
+
+        let saved = load_cases(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved.len(), report.failures.len());
+        for failure in &report.failures {
+            // Outputting a cell and halting is as small as a program that outputs gets.
+            assert_eq!(failure.case.program.len(), 3, "{}", failure);
+            let name = format!("{0}/{0}", failure.case.name);
+            let case = saved.iter().find(|case| case.name == name).unwrap();
+            assert_eq!(case.program, failure.case.program);
+            assert!(check(&run_shared::<i64>, case).is_ok());
+            assert!(check(&off_by_one, case).is_err());
+        }
+    }
+
+    #[test]
+    fn reads_after_outputs_are_checked() {
+        // Gives each read the input at the position of the read among the outputs so far, the
+        // way a VM that hands out one input per output does.
+        let one_per_output = |program: &[i64], inputs: &[i64]| {
+            let mut computer = IntCodeComputer::new(program);
+            loop {
+                match computer.run_one_turn() {
+                    ResultCode::Input => {
+                        let outputs = computer.output().len();
+                        computer.add_input(inputs.get(outputs).copied().unwrap_or(0));
+                    }
+                    ResultCode::Output(_) => {}
+                    _ => break,
+                }
+            }
+            Execution {
+                outputs: computer.output().to_vec(),
+                memory: computer.memory().to_vec(),
+            }
+        };
+        let report = run(&Config::new(5), &[("one per output", &one_per_output)]).unwrap();
+        assert!(!report.is_clean());
+
+        let config = Config {
+            reads_after_output: false,
+            ..Config::new(5)
+        };
+        let report = run(&config, &[("one per output", &one_per_output)]).unwrap();
+        assert!(report.is_clean(), "{}", report);
+    }
+}
@@ -0,0 +1,159 @@
+use std::convert::TryInto;
+
+use crate::cell::Cell;
+use crate::computer::{IntCodeComputer, ResultCode};
+
+fn no_input<C>() -> Option<C> {
+    None
+}
+
+/// Iterator over the values a computer outputs.
+///
+/// When the program asks for input that has not been queued, the feeder is asked for a value.
+/// If it has none, iteration pauses by returning `None`; queue more input with `add_input` and
+/// call `next` again to resume. Use `is_halted` to tell a pause from the end of the program.
+/// Panics if the program faults; drive the computer with `step` to handle faults.
+pub struct Outputs<'a, C = i64, F = fn() -> Option<C>> {
+    computer: &'a mut IntCodeComputer<C>,
+    feed: F,
+}
+
+impl<C: Cell> IntCodeComputer<C> {
+    pub fn outputs(&mut self) -> Outputs<'_, C> {
+        Outputs {
+            computer: self,
+            feed: no_input,
+        }
+    }
+
+    /// Like `outputs`, but calls `feed` for a value whenever the program blocks on input.
+    pub fn outputs_with<F: FnMut() -> Option<C>>(&mut self, feed: F) -> Outputs<'_, C, F> {
+        Outputs {
+            computer: self,
+            feed,
+        }
+    }
+}
+
+impl<'a, C: Cell, F: FnMut() -> Option<C>> Outputs<'a, C, F> {
+    pub fn add_input(&mut self, val: C) {
+        self.computer.add_input(val)
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.computer.is_blocked()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.computer.is_halted()
+    }
+
+    /// Groups consecutive outputs into fixed-size packets, e.g. `(x, y, tile)` triples.
+    pub fn chunks<const N: usize>(self) -> Chunks<'a, C, F, N> {
+        Chunks {
+            outputs: self,
+            buffer: Vec::with_capacity(N),
+        }
+    }
+}
+
+impl<C: Cell, F: FnMut() -> Option<C>> Iterator for Outputs<'_, C, F> {
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        loop {
+            match self.computer.run_one_turn() {
+                ResultCode::Output(val) => return Some(val),
+                ResultCode::Terminated => return None,
+                ResultCode::Fault(fault) => panic!("{}", fault),
+                ResultCode::Input => match (self.feed)() {
+                    Some(val) => self.computer.add_input(val),
+                    None => return None,
+                },
+            }
+        }
+    }
+}
+
+/// Iterator over packets of `N` consecutive outputs.
+///
+/// A packet that is only partly written when the program pauses for input is kept and completed
+/// on the next call. A partial packet left over when the program halts is dropped.
+pub struct Chunks<'a, C, F, const N: usize> {
+    outputs: Outputs<'a, C, F>,
+    buffer: Vec<C>,
+}
+
+impl<C: Cell, F: FnMut() -> Option<C>, const N: usize> Chunks<'_, C, F, N> {
+    pub fn add_input(&mut self, val: C) {
+        self.outputs.add_input(val)
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.outputs.is_blocked()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.outputs.is_halted()
+    }
+}
+
+impl<C: Cell, F: FnMut() -> Option<C>, const N: usize> Iterator for Chunks<'_, C, F, N> {
+    type Item = [C; N];
+
+    fn next(&mut self) -> Option<[C; N]> {
+        while self.buffer.len() < N {
+            self.buffer.push(self.outputs.next()?);
+        }
+        let packet = std::mem::replace(&mut self.buffer, Vec::with_capacity(N));
+        Some(packet.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a count, then outputs (i, i * i, i * i * i) for i in 1..=count.
+    const CUBES: [i64; 28] = [
+        3, 100, 1001, 101, 1, 101, 4, 101, 2, 101, 101, 102, 4, 102, 2, 102, 101, 102, 4, 102, 8,
+        101, 100, 103, 1006, 103, 2, 99,
+    ];
+
+    #[test]
+    fn outputs_pause_for_input() {
+        let mut computer = IntCodeComputer::new(&CUBES);
+        let mut outputs = computer.outputs();
+        assert_eq!(outputs.next(), None);
+        assert!(outputs.is_blocked());
+
+        outputs.add_input(2);
+        assert_eq!(outputs.collect::<Vec<_>>(), [1, 1, 1, 2, 4, 8]);
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn chunks_group_outputs() {
+        let mut count = Some(3);
+        let mut computer = IntCodeComputer::new(&CUBES);
+        let cubes: Vec<_> = computer
+            .outputs_with(|| count.take())
+            .chunks::<3>()
+            .collect();
+        assert_eq!(cubes, [[1, 1, 1], [2, 4, 8], [3, 9, 27]]);
+    }
+
+    #[test]
+    fn chunks_keep_partial_packets_across_pauses() {
+        // Outputs 7, then reads a value and outputs it.
+        let mut computer = IntCodeComputer::new(&[104, 7, 3, 9, 4, 9, 99, 0, 0, 0]);
+        let mut pairs = computer.outputs().chunks::<2>();
+        assert_eq!(pairs.next(), None);
+        assert!(pairs.is_blocked());
+
+        pairs.add_input(42);
+        assert_eq!(pairs.next(), Some([7, 42]));
+        assert_eq!(pairs.next(), None);
+        assert!(pairs.is_halted());
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Just enough JSON for the debug adapter's messages. Numbers are integers, since nothing in the
+/// protocol that the adapter reads needs fractions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, val)| (key.to_string(), val))
+                .collect(),
+        )
+    }
+
+    /// The field `key` of an object, or `Null` if there is none.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.get(key).unwrap_or(&Json::Null),
+            _ => &Json::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Int(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let val = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(val),
+            Some(c) => Err(format!("unexpected {:?} after value", c)),
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(val: bool) -> Self {
+        Json::Bool(val)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(val: i32) -> Self {
+        Json::Int(val.into())
+    }
+}
+
+impl From<i64> for Json {
+    fn from(val: i64) -> Self {
+        Json::Int(val)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(val: usize) -> Self {
+        Json::Int(val as i64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(val: &str) -> Self {
+        Json::String(val.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(val: String) -> Self {
+        Json::String(val)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Json::Array(items)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, val: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in val.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(val) => write!(f, "{}", val),
+            Json::Int(val) => write!(f, "{}", val),
+            Json::String(val) => write_string(f, val),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, val)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", val)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, word: &str) -> Result<(), String> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("expected {}", word));
+        }
+    }
+    Ok(())
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, "\"")?;
+    let mut val = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(val),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => val.push('\n'),
+                'r' => val.push('\r'),
+                't' => val.push('\t'),
+                'b' => val.push('\u{8}'),
+                'f' => val.push('\u{c}'),
+                'u' => {
+                    let hex: String = chars.take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16)
+                        .map_err(|_| format!("invalid escape \\u{}", hex))?;
+                    // Surrogate pairs are not needed by any message the adapter reads.
+                    val.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                c => val.push(c),
+            },
+            c => val.push(c),
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek().copied().ok_or("unexpected end of input")? {
+        'n' => expect(chars, "null").map(|_| Json::Null),
+        't' => expect(chars, "true").map(|_| Json::Bool(true)),
+        'f' => expect(chars, "false").map(|_| Json::Bool(false)),
+        '"' => parse_string(chars).map(Json::String),
+        '[' => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => return Err("expected , or ]".to_string()),
+                }
+            }
+        }
+        '{' => {
+            chars.next();
+            let mut fields = BTreeMap::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ":")?;
+                fields.insert(key, parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return Err("expected , or }".to_string()),
+                }
+            }
+        }
+        c if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' || c.is_ascii_digit())
+                {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            number
+                .parse()
+                .map(Json::Int)
+                .map_err(|_| format!("unsupported number {}", number))
+        }
+        c => Err(format!("unexpected {:?}", c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let text = r#"{"arguments":{"lines":[1,-2],"name":"a \"b\"\n"},"seq":3,"x":null,"y":true}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.get("seq").as_i64(), Some(3));
+        assert_eq!(
+            json.get("arguments").get("name").as_str(),
+            Some("a \"b\"\n")
+        );
+        assert_eq!(json.to_string(), text);
+        assert!(Json::parse("{\"a\":1.5}").is_err());
+        assert!(Json::parse("[1,2").is_err());
+    }
+}
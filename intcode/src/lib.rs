@@ -0,0 +1,55 @@
+mod ascii;
+mod bigint;
+mod cell;
+pub mod cli;
+pub mod compiler;
+mod computer;
+pub mod conformance;
+pub mod dap;
+mod decode;
+mod device;
+mod diagnostic;
+pub mod equivalence;
+mod fault;
+mod fork;
+pub mod fuzz;
+mod iter;
+mod json;
+mod load;
+mod machine;
+pub mod optimize;
+mod patch;
+pub mod remote;
+mod rng;
+mod scheduler;
+mod search;
+mod snapshot;
+pub mod solver;
+pub mod strings;
+mod threaded;
+mod topology;
+
+pub use ascii::{AsciiOutput, PromptError};
+pub use bigint::{BigInt, ParseBigIntError};
+pub use cell::Cell;
+pub use computer::{IntCodeComputer, ResultCode};
+pub use decode::{reachable, reachable_from, Instruction, Mode, Param};
+pub use device::{
+    Arcade, Detach, Device, Droid, Exchange, PaintingRobot, Position, Recorder, Replay, Terminal,
+    Terrain, Tile,
+};
+pub use diagnostic::{diagnose, BoostReport, Diagnostic, TestReport};
+pub use fault::{Arithmetic, Fault};
+pub use fork::Prologue;
+pub use iter::{Chunks, Outputs};
+pub use load::{
+    decode_binary, encode_binary, load_program, parse_program, parse_program_lenient, save_binary,
+    LoadError, BINARY_MAGIC,
+};
+pub use machine::Machine;
+pub use patch::{Edit, Patch, PatchError, PatchSet};
+pub use scheduler::{Message, Policy, Routing, Scheduler, Stats};
+pub use search::{memory_key, Node, Search, Space, Strategy};
+pub use snapshot::{diff, Change, History, Scanner, Snapshot};
+pub use threaded::{RunningNetwork, ThreadedNetwork};
+pub use topology::{Amplifier, Merge, Report, Topology, TopologyError, Traffic};
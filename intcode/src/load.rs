@@ -0,0 +1,267 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::cell::Cell;
+
+/// Binary programs start with this magic, then a little-endian `u32` format version and a
+/// little-endian `u64` cell count, followed by the cells as little-endian `i64`s.
+pub const BINARY_MAGIC: &[u8; 4] = b"INTC";
+const BINARY_VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// A token that is not an integer. `offset` is the byte offset of the token in the text,
+    /// `address` the cell it would have been loaded into.
+    InvalidToken {
+        token: String,
+        offset: usize,
+        line: usize,
+        address: usize,
+    },
+    /// Two commas with no value between them.
+    MissingValue {
+        offset: usize,
+        line: usize,
+        address: usize,
+    },
+    /// Binary data that does not start with `BINARY_MAGIC`.
+    NotBinary,
+    UnsupportedVersion(u32),
+    /// The header promises more cells than the data holds, or the data has trailing bytes.
+    BadLength {
+        cells: u64,
+        bytes: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::InvalidToken {
+                token,
+                offset,
+                line,
+                address,
+            } => write!(
+                f,
+                "line {}, offset {}: invalid value {:?} for address {}",
+                line, offset, token, address
+            ),
+            LoadError::MissingValue {
+                offset,
+                line,
+                address,
+            } => write!(
+                f,
+                "line {}, offset {}: missing value for address {}",
+                line, offset, address
+            ),
+            LoadError::NotBinary => write!(f, "not a binary program"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported binary program version {}", version)
+            }
+            LoadError::BadLength { cells, bytes } => write!(
+                f,
+                "binary program header says {} cells, but {} bytes of cells follow",
+                cells, bytes
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<LoadError> for io::Error {
+    fn from(err: LoadError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+// Splits a program into `(offset, line, token)`. Values are separated by commas, whitespace or
+// newlines; `#` starts a comment that runs to the end of the line. A comma with no value before
+// it yields an empty token, so that the missing value can be reported.
+fn tokens(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut expect_value = true;
+    let mut line_start = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let code = line.split('#').next().unwrap();
+        let mut start = None;
+        for (pos, ch) in code.char_indices() {
+            if ch != ',' && !ch.is_whitespace() {
+                start.get_or_insert(pos);
+                continue;
+            }
+            if let Some(begin) = start.take() {
+                tokens.push((line_start + begin, i + 1, &code[begin..pos]));
+                expect_value = false;
+            }
+            if ch == ',' {
+                if expect_value {
+                    tokens.push((line_start + pos, i + 1, ""));
+                }
+                expect_value = true;
+            }
+        }
+        if let Some(begin) = start {
+            tokens.push((line_start + begin, i + 1, &code[begin..]));
+            expect_value = false;
+        }
+        line_start += line.len() + 1;
+    }
+    tokens
+}
+
+/// Parses a program written as comma-separated values, rejecting anything that is not an
+/// integer instead of skipping it.
+pub fn parse_program<C: Cell + FromStr>(text: &str) -> Result<Vec<C>, LoadError> {
+    tokens(text)
+        .into_iter()
+        .enumerate()
+        .map(|(address, (offset, line, token))| {
+            if token.is_empty() {
+                return Err(LoadError::MissingValue {
+                    offset,
+                    line,
+                    address,
+                });
+            }
+            token.parse().map_err(|_| LoadError::InvalidToken {
+                token: token.to_string(),
+                offset,
+                line,
+                address,
+            })
+        })
+        .collect()
+}
+
+/// Like `parse_program`, but drops tokens that are not integers, as the per-day loaders used to.
+pub fn parse_program_lenient<C: Cell + FromStr>(text: &str) -> Vec<C> {
+    tokens(text)
+        .into_iter()
+        .filter_map(|(_, _, token)| token.parse().ok())
+        .collect()
+}
+
+pub fn encode_binary(program: &[i64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * program.len());
+    bytes.extend_from_slice(BINARY_MAGIC);
+    bytes.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(program.len() as u64).to_le_bytes());
+    for val in program {
+        bytes.extend_from_slice(&val.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes a program written by `encode_binary`.
+pub fn decode_binary(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    if !bytes.starts_with(BINARY_MAGIC) {
+        return Err(LoadError::NotBinary);
+    }
+    let header = bytes.get(..HEADER_LEN).ok_or(LoadError::BadLength {
+        cells: 0,
+        bytes: bytes.len(),
+    })?;
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != BINARY_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    let cells = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let data = &bytes[HEADER_LEN..];
+    if data.len() as u64 != cells.saturating_mul(8) {
+        return Err(LoadError::BadLength {
+            cells,
+            bytes: data.len(),
+        });
+    }
+    Ok(data
+        .chunks_exact(8)
+        .map(|cell| i64::from_le_bytes(cell.try_into().unwrap()))
+        .collect())
+}
+
+/// Loads a program from a text file, or from a binary file if it starts with `BINARY_MAGIC`.
+pub fn load_program<C: Cell + FromStr>(filepath: &Path) -> io::Result<Vec<C>> {
+    let bytes = fs::read(filepath)?;
+    if bytes.starts_with(BINARY_MAGIC) {
+        return Ok(decode_binary(&bytes)?.into_iter().map(C::from).collect());
+    }
+    let text =
+        String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(parse_program(&text)?)
+}
+
+pub fn save_binary(filepath: &Path, program: &[i64]) -> io::Result<()> {
+    fs::write(filepath, encode_binary(program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigInt;
+
+    #[test]
+    fn programs_may_span_lines_and_have_comments() {
+        let text = "# Outputs 1 and halts.\n104, 1,\n99\n\n# Data\n-5,1125899906842624\n";
+        assert_eq!(
+            parse_program::<i64>(text),
+            Ok(vec![104, 1, 99, -5, 1_125_899_906_842_624])
+        );
+        // The value before the final newline is kept.
+        assert_eq!(
+            parse_program::<i64>("1,0,0,0,99\n"),
+            Ok(vec![1, 0, 0, 0, 99])
+        );
+        let big = parse_program::<BigInt>("99999999999999999999999").unwrap();
+        assert_eq!(big[0].to_string(), "99999999999999999999999");
+    }
+
+    #[test]
+    fn malformed_tokens_are_located() {
+        assert_eq!(
+            parse_program::<i64>("1,2,3\n4,5x,6"),
+            Err(LoadError::InvalidToken {
+                token: "5x".to_string(),
+                offset: 8,
+                line: 2,
+                address: 4,
+            })
+        );
+        assert_eq!(
+            parse_program::<i64>("1,,2"),
+            Err(LoadError::MissingValue {
+                offset: 2,
+                line: 1,
+                address: 1,
+            })
+        );
+        assert_eq!(parse_program_lenient::<i64>("1,5x,2"), [1, 2]);
+    }
+
+    #[test]
+    fn binary_programs_round_trip() {
+        let program = [109, 1, 204, -1, 1001, 100, 1, 100, i64::MIN, i64::MAX, 99];
+        let bytes = encode_binary(&program);
+        assert_eq!(decode_binary(&bytes), Ok(program.to_vec()));
+        assert_eq!(
+            decode_binary(&bytes[..bytes.len() - 1]),
+            Err(LoadError::BadLength {
+                cells: 11,
+                bytes: 87
+            })
+        );
+
+        let mut bytes = bytes;
+        bytes[4] = 2;
+        assert_eq!(decode_binary(&bytes), Err(LoadError::UnsupportedVersion(2)));
+        assert_eq!(decode_binary(b"1,2,3"), Err(LoadError::NotBinary));
+    }
+}
@@ -0,0 +1,157 @@
+use std::mem;
+
+use crate::ascii::{to_ascii, AsciiOutput, PromptError};
+use crate::cell::Cell;
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::device::{Detach, Device};
+
+/// What solutions need from an Intcode machine, so that the same code can drive the computer in
+/// this process or one running elsewhere, such as a `remote::Remote`.
+///
+/// The high-level helpers are built on `run_one_turn` and, like it, panic if the program
+/// faults.
+pub trait Machine<C: Cell = i64> {
+    /// Queues a value for the program to read. Inputs are consumed in the order they are added.
+    fn add_input(&mut self, val: C);
+
+    /// Runs until the program outputs a value, needs input that has not been queued, or halts.
+    fn run_one_turn(&mut self) -> ResultCode<C>;
+
+    /// Every value the program has output so far.
+    fn output(&self) -> &[C];
+
+    fn is_halted(&self) -> bool;
+
+    /// Runs until the program halts or blocks on input, returning everything it has output.
+    ///
+    /// Panics if the program faults.
+    fn run_program(&mut self) -> Vec<C> {
+        loop {
+            match self.run_one_turn() {
+                ResultCode::Output(_) => {}
+                ResultCode::Fault(fault) => panic!("{}", fault),
+                ResultCode::Input | ResultCode::Terminated => break,
+            }
+        }
+        self.output().to_vec()
+    }
+
+    /// Every output so far as text. Values that are not ASCII characters are left out.
+    fn get_output_as_ascii(&self) -> String {
+        self.output().iter().filter_map(to_ascii).collect()
+    }
+
+    /// Runs the program with `device` on the other end of its input and output.
+    fn attach(&mut self, device: &mut dyn Device<C>) -> Detach {
+        loop {
+            if device.is_done() {
+                return Detach::Done;
+            }
+            match self.run_one_turn() {
+                ResultCode::Output(val) => device.on_output(val),
+                ResultCode::Input => match device.next_input() {
+                    Some(val) => self.add_input(val),
+                    None => return Detach::Blocked,
+                },
+                ResultCode::Terminated => return Detach::Halted,
+                ResultCode::Fault(fault) => panic!("{}", fault),
+            }
+        }
+    }
+
+    /// Queues `line` as character codes, followed by a newline.
+    fn send_line(&mut self, line: &str) {
+        for ch in line.bytes() {
+            self.add_input(C::from(i64::from(ch)));
+        }
+        self.add_input(C::from(i64::from(b'\n')));
+    }
+
+    fn send_lines(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.send_line(line);
+        }
+    }
+
+    /// Runs until the program halts or blocks on input, returning what it printed.
+    fn read_ascii(&mut self) -> AsciiOutput<C> {
+        read_ascii_until(self, &mut |_| false).0
+    }
+
+    /// Runs until the program prints a line ending with `prompt`, returning everything printed
+    /// up to and including that line.
+    fn wait_for_prompt(&mut self, prompt: &str) -> Result<AsciiOutput<C>, PromptError<C>> {
+        match read_ascii_until(self, &mut |line| line.trim_end().ends_with(prompt)) {
+            (output, true) => Ok(output),
+            (output, false) => Err(PromptError {
+                prompt: prompt.to_string(),
+                output,
+            }),
+        }
+    }
+
+    /// Waits for `prompt`, then answers it with `lines`.
+    fn respond(&mut self, prompt: &str, lines: &[&str]) -> Result<AsciiOutput<C>, PromptError<C>> {
+        let output = self.wait_for_prompt(prompt)?;
+        self.send_lines(lines);
+        Ok(output)
+    }
+}
+
+fn read_ascii_until<C: Cell, M: Machine<C> + ?Sized>(
+    machine: &mut M,
+    is_prompt: &mut dyn FnMut(&str) -> bool,
+) -> (AsciiOutput<C>, bool) {
+    let mut output = AsciiOutput::default();
+    let mut line = String::new();
+    loop {
+        match machine.run_one_turn() {
+            ResultCode::Output(val) => match to_ascii(&val) {
+                Some('\n') => {
+                    let found = is_prompt(&line);
+                    output.lines.push(mem::take(&mut line));
+                    if found {
+                        return (output, true);
+                    }
+                }
+                Some(ch) => line.push(ch),
+                None => output.values.push(val),
+            },
+            ResultCode::Fault(fault) => panic!("{}", fault),
+            ResultCode::Input | ResultCode::Terminated => {
+                // Prompts may be left on an unterminated line while the program waits.
+                let found = !line.is_empty() && is_prompt(&line);
+                if !line.is_empty() {
+                    output.lines.push(line);
+                }
+                return (output, found);
+            }
+        }
+    }
+}
+
+impl<C: Cell> Machine<C> for IntCodeComputer<C> {
+    fn add_input(&mut self, val: C) {
+        IntCodeComputer::add_input(self, val)
+    }
+
+    fn run_one_turn(&mut self) -> ResultCode<C> {
+        IntCodeComputer::run_one_turn(self)
+    }
+
+    fn output(&self) -> &[C] {
+        IntCodeComputer::output(self)
+    }
+
+    fn is_halted(&self) -> bool {
+        IntCodeComputer::is_halted(self)
+    }
+
+    fn run_program(&mut self) -> Vec<C> {
+        IntCodeComputer::run_program(self)
+    }
+
+    fn get_output_as_ascii(&self) -> String {
+        IntCodeComputer::get_output_as_ascii(self)
+    }
+}
@@ -0,0 +1,692 @@
+//! Rewrites a program into a faster equivalent.
+//!
+//! Every instruction stays at its address, so jumps, return addresses and data need no
+//! relocating. The optimizer folds arithmetic and comparisons on constants, including cells and
+//! stack slots given constants earlier in the same straight-line block; decides conditional
+//! jumps on known conditions; points jumps and return addresses that lead to an unconditional
+//! jump at where that jump goes; and clears the instructions that can no longer run.
+//!
+//! Instructions whose cells the program writes, or reads as data, are left as they are. The
+//! analysis relies on conventions that the puzzle programs and `compiler` output follow: the
+//! relative base is set past the end of the program before it is used, so the stack never
+//! overlaps the program; and constants stored in stack slots that hold the address after a
+//! jump are return addresses. A program whose stack may overlap it, or that writes through
+//! patched addresses, which may hit any code, is refused as a whole.
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter;
+use std::ops::Range;
+
+use crate::decode::{reachable_from, Instruction, Mode, Param};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Optimized {
+    pub program: Vec<i64>,
+    /// How many instructions could run before optimizing and after.
+    pub before: usize,
+    pub after: usize,
+    /// Instructions whose operands or result were replaced by constants.
+    pub folded: usize,
+    /// Conditional jumps whose condition became a constant.
+    pub decided: usize,
+    /// Jumps and return addresses pointed past an unconditional jump.
+    pub threaded: usize,
+    /// Instructions cleared because nothing reaches them any more.
+    pub removed: usize,
+    /// The code left alone because the program modifies it or reads it as data.
+    pub refused: Vec<Range<usize>>,
+    /// Jumps whose targets change at runtime. Since any instruction may follow them, constants
+    /// are not carried from one instruction to the next while there are any, and no code is
+    /// removed.
+    pub unknown_jumps: Vec<usize>,
+}
+
+impl fmt::Display for Optimized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "instructions: {} -> {}", self.before, self.after)?;
+        writeln!(f, "folded: {}", self.folded)?;
+        writeln!(f, "decided: {}", self.decided)?;
+        writeln!(f, "threaded: {}", self.threaded)?;
+        writeln!(f, "removed: {}", self.removed)?;
+        let refused: Vec<_> = self
+            .refused
+            .iter()
+            .map(|range| format!("{}..{}", range.start, range.end))
+            .collect();
+        writeln!(f, "refused: {}", refused.join(", "))?;
+        let jumps: Vec<_> = self.unknown_jumps.iter().map(|ip| ip.to_string()).collect();
+        writeln!(f, "unknown jumps: {}", jumps.join(", "))
+    }
+}
+
+fn addr(val: i64) -> Option<usize> {
+    usize::try_from(val).ok()
+}
+
+/// Whether `inst` is a jump that can be taken.
+fn may_jump(inst: &Instruction) -> bool {
+    match (inst.opcode, inst.params.first()) {
+        (
+            5,
+            Some(Param {
+                mode: Mode::Immediate,
+                value,
+            }),
+        ) => *value != 0,
+        (
+            6,
+            Some(Param {
+                mode: Mode::Immediate,
+                value,
+            }),
+        ) => *value == 0,
+        (5, _) | (6, _) => true,
+        _ => false,
+    }
+}
+
+struct Analysis {
+    code: Vec<Instruction>,
+    /// Cells that instructions write, or read as data, through position mode.
+    written: BTreeSet<usize>,
+    read: BTreeSet<usize>,
+    /// Where jumps through cells that are never written go.
+    targets: Vec<usize>,
+    unknown_jumps: Vec<usize>,
+    /// Instructions whose destinations are patched at runtime.
+    patched_stores: Vec<usize>,
+}
+
+/// Finds the code reachable from the start and from the targets of jumps through constant
+/// cells, which may lead to more such jumps.
+fn analyze(program: &[i64]) -> Analysis {
+    let mut targets = Vec::new();
+    loop {
+        let entries: Vec<_> = iter::once(0).chain(targets.iter().copied()).collect();
+        let code = reachable_from(program, &entries);
+        // Where instructions with patched operands read and write is only known at runtime.
+        let patched: BTreeSet<_> = code
+            .iter()
+            .filter_map(|inst| match inst.write() {
+                Some(Param {
+                    mode: Mode::Position,
+                    value,
+                }) => addr(value),
+                _ => None,
+            })
+            .collect();
+        let (mut written, mut read) = (BTreeSet::new(), BTreeSet::new());
+        for inst in &code {
+            for (i, param) in inst.params.iter().enumerate() {
+                if param.mode != Mode::Position || patched.contains(&(inst.addr + 1 + i)) {
+                    continue;
+                }
+                let cells = if inst.write().is_some() && i == inst.params.len() - 1 {
+                    &mut written
+                } else {
+                    &mut read
+                };
+                cells.extend(addr(param.value));
+            }
+        }
+
+        let mut found = Vec::new();
+        let mut unknown_jumps = Vec::new();
+        for inst in &code {
+            if written.contains(&inst.addr) {
+                // The instruction itself may be replaced by a jump.
+                unknown_jumps.push(inst.addr);
+                continue;
+            }
+            if !may_jump(inst) {
+                continue;
+            }
+            let target = inst.params[1];
+            if written.contains(&(inst.addr + 2)) {
+                unknown_jumps.push(inst.addr);
+            } else if target.mode == Mode::Position {
+                match addr(target.value) {
+                    Some(cell) if written.contains(&cell) => unknown_jumps.push(inst.addr),
+                    Some(cell) => found.extend(addr(program.get(cell).copied().unwrap_or(0))),
+                    None => {}
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        if found == targets {
+            let patched_stores = code
+                .iter()
+                .filter(|inst| {
+                    inst.write().is_some() && written.contains(&(inst.addr + inst.params.len()))
+                })
+                .map(|inst| inst.addr)
+                .collect();
+            return Analysis {
+                code,
+                written,
+                read,
+                targets,
+                unknown_jumps,
+                patched_stores,
+            };
+        }
+        targets = found;
+    }
+}
+
+/// Whether every path from the start sets the relative base past the end of the program with
+/// an immediate before anything uses it, so that the stack cannot overlap the program.
+fn stack_is_separate(program: &[i64], code: &[Instruction]) -> bool {
+    let is_relative =
+        |inst: &Instruction| inst.params.iter().any(|param| param.mode == Mode::Relative);
+    if !code
+        .iter()
+        .any(|inst| inst.opcode == 9 || is_relative(inst))
+    {
+        return true;
+    }
+    let mut seen = BTreeSet::new();
+    let mut queue = vec![0];
+    while let Some(addr) = queue.pop() {
+        if !seen.insert(addr) {
+            continue;
+        }
+        let inst = match Instruction::decode(program, addr) {
+            Some(inst) => inst,
+            None => continue,
+        };
+        if let (
+            9,
+            Some(Param {
+                mode: Mode::Immediate,
+                value,
+            }),
+        ) = (inst.opcode, inst.params.first())
+        {
+            if *value >= program.len() as i64 {
+                continue;
+            }
+        }
+        if inst.opcode == 9 || is_relative(&inst) {
+            return false;
+        }
+        if may_jump(&inst) {
+            match inst.jump_target() {
+                Some(target) => queue.push(target),
+                None => return false,
+            }
+        }
+        if inst.falls_through() {
+            queue.push(inst.next());
+        }
+    }
+    true
+}
+
+/// The addresses an instruction other than a jump holds as immediates, which may be where a
+/// jump through memory or the stack goes later.
+fn pointers(inst: &Instruction) -> impl Iterator<Item = usize> + '_ {
+    let params = if inst.opcode == 5 || inst.opcode == 6 {
+        &[]
+    } else {
+        inst.reads()
+    };
+    params
+        .iter()
+        .filter(|param| param.mode == Mode::Immediate)
+        .filter_map(|param| addr(param.value))
+}
+
+/// The instructions that can run: those reachable from the start and from `targets`, and the
+/// ones in `starts` whose addresses reachable instructions hold as immediates.
+fn live(program: &[i64], starts: &BTreeSet<usize>, targets: &[usize]) -> Vec<Instruction> {
+    let mut entries: BTreeSet<_> = iter::once(0).chain(targets.iter().copied()).collect();
+    loop {
+        let code = reachable_from(program, &entries.iter().copied().collect::<Vec<_>>());
+        let count = entries.len();
+        for inst in &code {
+            entries.extend(pointers(inst).filter(|addr| starts.contains(addr)));
+        }
+        if entries.len() == count {
+            return code;
+        }
+    }
+}
+
+fn encode(inst: &Instruction) -> Vec<i64> {
+    let modes = inst.params.iter().rev().fold(0, |modes, param| {
+        let digit = match param.mode {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        };
+        modes * 10 + digit
+    });
+    iter::once(modes * 100 + inst.opcode)
+        .chain(inst.params.iter().map(|param| param.value))
+        .collect()
+}
+
+fn store(program: &mut [i64], inst: &Instruction) {
+    let cells = encode(inst);
+    program[inst.addr..inst.next()].copy_from_slice(&cells);
+}
+
+fn immediate(value: i64) -> Param {
+    Param {
+        mode: Mode::Immediate,
+        value,
+    }
+}
+
+/// A cell of the program, or a stack slot at an offset from the current relative base.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Loc {
+    Abs(usize),
+    Rel(i64),
+}
+
+fn loc(param: Param, len: usize) -> Option<Loc> {
+    match param.mode {
+        Mode::Position => addr(param.value).filter(|&addr| addr < len).map(Loc::Abs),
+        Mode::Relative => Some(Loc::Rel(param.value)),
+        Mode::Immediate => None,
+    }
+}
+
+/// The value an arithmetic or comparison instruction computes from constants, unless it
+/// overflows.
+fn evaluate(opcode: i64, fst: i64, snd: i64) -> Option<i64> {
+    match opcode {
+        1 => fst.checked_add(snd),
+        2 => fst.checked_mul(snd),
+        7 => Some((fst < snd) as i64),
+        8 => Some((fst == snd) as i64),
+        _ => None,
+    }
+}
+
+struct Optimizer<'a> {
+    analysis: &'a Analysis,
+    /// Addresses of the instructions that are left alone.
+    refused: BTreeSet<usize>,
+    /// Addresses of reachable instructions.
+    starts: BTreeSet<usize>,
+    /// Where execution can arrive other than from the instruction before.
+    entries: BTreeSet<usize>,
+    optimized: Optimized,
+}
+
+impl Optimizer<'_> {
+    fn fold(&mut self) {
+        let len = self.optimized.program.len();
+        let mut known = HashMap::new();
+        let mut prev: Option<&Instruction> = None;
+        for inst in &self.analysis.code {
+            let continues =
+                prev.is_some_and(|prev| prev.next() == inst.addr && prev.falls_through());
+            if !continues
+                || self.entries.contains(&inst.addr)
+                || !self.analysis.unknown_jumps.is_empty()
+            {
+                known.clear();
+            }
+            prev = Some(inst);
+            if self.refused.contains(&inst.addr) {
+                // Its operands may have been patched to write anywhere.
+                known.clear();
+                continue;
+            }
+
+            let mut rewritten = inst.clone();
+            let reads = inst.reads().len();
+            for param in &mut rewritten.params[..reads] {
+                if let Some(&val) = loc(*param, len).and_then(|loc| known.get(&loc)) {
+                    *param = immediate(val);
+                }
+            }
+            let mut result = None;
+            if let [fst, snd, dst] = rewritten.params[..] {
+                if fst.mode == Mode::Immediate && snd.mode == Mode::Immediate {
+                    result = evaluate(inst.opcode, fst.value, snd.value);
+                }
+                // Adding two immediates is how programs copy a constant already.
+                let is_copy = inst.opcode == 1
+                    && inst
+                        .reads()
+                        .iter()
+                        .all(|param| param.mode == Mode::Immediate);
+                if let (Some(val), false) = (result, is_copy) {
+                    rewritten.opcode = 1;
+                    rewritten.params = vec![immediate(val), immediate(0), dst];
+                }
+            }
+
+            if let Some(dst) = inst.write() {
+                match loc(dst, len) {
+                    Some(loc) => {
+                        known.remove(&loc);
+                        known.extend(result.map(|val| (loc, val)));
+                    }
+                    // Cells past the program may be stack slots.
+                    None => known.retain(|loc, _| matches!(loc, Loc::Abs(_))),
+                }
+            }
+            if inst.opcode == 9 {
+                known.retain(|loc, _| matches!(loc, Loc::Abs(_)));
+            }
+
+            if rewritten != *inst {
+                if (inst.opcode == 5 || inst.opcode == 6) && rewritten.params[0] != inst.params[0] {
+                    self.optimized.decided += 1;
+                } else {
+                    self.optimized.folded += 1;
+                }
+                store(&mut self.optimized.program, &rewritten);
+            }
+        }
+    }
+
+    /// Where a jump to `target` ends up after following unconditional jumps.
+    fn follow(&self, mut target: usize) -> usize {
+        let mut seen = BTreeSet::new();
+        while seen.insert(target)
+            && self.starts.contains(&target)
+            && !self.refused.contains(&target)
+        {
+            match Instruction::decode(&self.optimized.program, target) {
+                Some(inst) if !inst.falls_through() => match inst.jump_target() {
+                    Some(next) => target = next,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        target
+    }
+
+    fn thread(&mut self, returns: &BTreeSet<usize>) {
+        for inst in &self.analysis.code {
+            if self.refused.contains(&inst.addr) {
+                continue;
+            }
+            let current = Instruction::decode(&self.optimized.program, inst.addr).unwrap();
+            let mut rewritten = current.clone();
+            if may_jump(&current) {
+                if let Some(target) = current.jump_target() {
+                    let end = self.follow(target);
+                    if end != target {
+                        rewritten.params[1] = immediate(end as i64);
+                    }
+                }
+            } else if let (
+                Some(val),
+                Some(Param {
+                    mode: Mode::Relative,
+                    ..
+                }),
+            ) = (current.constant(), current.write())
+            {
+                // A return address that leads to a jump. Only stack slots are taken to hold
+                // return addresses, since a constant stored anywhere else may be data that
+                // happens to equal one.
+                match addr(val) {
+                    Some(target) if returns.contains(&target) => {
+                        let end = self.follow(target);
+                        if end != target {
+                            rewritten.params[..2]
+                                .copy_from_slice(&[immediate(end as i64), immediate(0)]);
+                            rewritten.opcode = 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if rewritten != current {
+                self.optimized.threaded += 1;
+                store(&mut self.optimized.program, &rewritten);
+            }
+        }
+    }
+
+    fn remove(&mut self) {
+        let program = &self.optimized.program;
+        let live: BTreeSet<_> = live(program, &self.starts, &self.analysis.targets)
+            .iter()
+            .flat_map(|inst| inst.addr..inst.next())
+            .collect();
+        for inst in &self.analysis.code {
+            let cells = inst.addr..inst.next();
+            if self.refused.contains(&inst.addr) || cells.clone().any(|cell| live.contains(&cell)) {
+                continue;
+            }
+            self.optimized.program[cells].fill(0);
+            self.optimized.removed += 1;
+        }
+    }
+}
+
+/// Optimizes `program`, reporting what was done.
+pub fn optimize(program: &[i64]) -> Optimized {
+    let analysis = analyze(program);
+    let mut optimized = Optimized {
+        program: program.to_vec(),
+        before: analysis.code.len(),
+        after: analysis.code.len(),
+        folded: 0,
+        decided: 0,
+        threaded: 0,
+        removed: 0,
+        refused: Vec::new(),
+        unknown_jumps: analysis.unknown_jumps.clone(),
+    };
+    if !stack_is_separate(program, &analysis.code) || !analysis.patched_stores.is_empty() {
+        optimized.refused.push(0..program.len());
+        return optimized;
+    }
+
+    let mut refused = BTreeSet::new();
+    for inst in &analysis.code {
+        let mut cells = inst.addr..inst.next();
+        if cells.any(|cell| analysis.written.contains(&cell) || analysis.read.contains(&cell)) {
+            refused.insert(inst.addr);
+            match optimized.refused.last_mut() {
+                Some(range) if range.end >= inst.addr => range.end = range.end.max(inst.next()),
+                _ => optimized.refused.push(inst.addr..inst.next()),
+            }
+        }
+    }
+    let starts: BTreeSet<_> = analysis.code.iter().map(|inst| inst.addr).collect();
+    let mut entries: BTreeSet<_> = iter::once(0)
+        .chain(analysis.targets.iter().copied())
+        .collect();
+    for inst in &analysis.code {
+        entries.extend(inst.jump_target());
+        entries.extend(pointers(inst).filter(|addr| starts.contains(addr)));
+    }
+    // Return addresses are only looked for if some jump takes its target from the stack.
+    let returns_through_stack = analysis
+        .code
+        .iter()
+        .any(|inst| may_jump(inst) && inst.params[1].mode == Mode::Relative);
+    let returns = analysis
+        .code
+        .iter()
+        .filter(|inst| returns_through_stack && !inst.falls_through())
+        .map(Instruction::next)
+        .filter(|addr| starts.contains(addr))
+        .collect();
+
+    let mut optimizer = Optimizer {
+        analysis: &analysis,
+        refused,
+        starts,
+        entries,
+        optimized,
+    };
+    optimizer.fold();
+    optimizer.thread(&returns);
+    if analysis.unknown_jumps.is_empty() {
+        optimizer.remove();
+    }
+    let mut optimized = optimizer.optimized;
+    optimized.after = live(&optimized.program, &optimizer.starts, &analysis.targets).len();
+    optimized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::computer::{IntCodeComputer, ResultCode};
+    use crate::conformance::{certify, Execution};
+    use crate::equivalence::{check, Domain, Values};
+    use crate::load::load_program;
+    use std::path::Path;
+
+    /// How many instructions `program` runs on `inputs` before it halts or blocks.
+    fn steps(program: &[i64], inputs: &[i64]) -> u64 {
+        let mut computer = IntCodeComputer::new(program);
+        for &val in inputs {
+            computer.add_input(val);
+        }
+        let mut steps = 0;
+        while let None | Some(ResultCode::Output(_)) = computer.step() {
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn optimizes_compiled_code() {
+        // The condition is always true, and the end of the if jumps to the jump back to the
+        // top of the loop.
+        let program = compile(
+            "fn main() {
+                var n = input();
+                var total = 0;
+                while n > 0 {
+                    n = n - 1;
+                    if 2 * 3 < 7 { total = total + n; } else { total = total - n; }
+                }
+                output(total);
+            }",
+        )
+        .unwrap();
+        let optimized = optimize(&program);
+        assert_eq!(
+            optimized.to_string(),
+            "instructions: 25 -> 21\nfolded: 4\ndecided: 1\nthreaded: 1\nremoved: 4\n\
+             refused: \nunknown jumps: \n"
+        );
+        let domain = Domain::Exhaustive(vec![Values::Range(-5..=20)]);
+        let report = check(&program, &optimized.program, &domain, 10_000);
+        assert!(report.is_equivalent(), "{}", report);
+        assert_eq!(steps(&program, &[10]), 131);
+        assert_eq!(steps(&optimized.program, &[10]), 121);
+    }
+
+    #[test]
+    fn leaves_self_modifying_code_alone() {
+        let program = compile(
+            "var squares[10];
+            fn main() {
+                var i = 0;
+                while i < 10 { squares[i] = i * i; i = i + 1; }
+                output(squares[input()]);
+            }",
+        )
+        .unwrap();
+        let optimized = optimize(&program);
+        // The stores into the array have their addresses patched in, so they may write
+        // anywhere in the program.
+        assert_eq!(optimized.refused, vec![0..program.len()]);
+        assert_eq!(optimized.program, program);
+        assert!(optimized.unknown_jumps.is_empty());
+        let domain = Domain::Exhaustive(vec![Values::Range(0..=9)]);
+        assert!(check(&program, &optimized.program, &domain, 10_000).is_equivalent());
+
+        // Points the jump at 4 to 9 before taking it, so the output at 9 is not dead after all.
+        let program = [1101, 0, 9, 6, 1105, 1, 11, 99, 0, 104, 1, 99];
+        let optimized = optimize(&program);
+        assert_eq!(optimized.refused, vec![4..7]);
+        assert_eq!(optimized.unknown_jumps, [4]);
+        assert_eq!(optimized.program, program);
+
+        // The store at 4 writes into the second operand of the multiplication at 8, so it
+        // outputs 9 * 3 rather than 2 * 3.
+        let program = [1101, 0, 9, 7, 1101, 0, 5, 0, 1102, 2, 3, 20, 4, 20, 99];
+        let optimized = optimize(&program);
+        assert_eq!(optimized.refused, vec![0..program.len()]);
+        assert_eq!(optimized.program, program);
+        assert_eq!(IntCodeComputer::new(&optimized.program).run_program(), [15]);
+    }
+
+    #[test]
+    fn leaves_data_that_looks_like_return_addresses_alone() {
+        // Stores 10, the address after the halt at 9, as data and outputs it when given 1.
+        let mut program = vec![
+            1101, 0, 10, 30, 3, 31, 1005, 31, 13, 99, 1105, 1, 9, 4, 30, 99,
+        ];
+        program.resize(31, 0);
+        let optimized = optimize(&program);
+        assert_eq!(optimized.program[..4], program[..4]);
+        let mut computer = IntCodeComputer::new(&optimized.program);
+        computer.add_input(1);
+        assert_eq!(computer.run_program(), [10]);
+    }
+
+    #[test]
+    fn speeds_up_the_beam_scan() {
+        let program = load_program(Path::new("../day19/input/input.txt")).unwrap();
+        let optimized = optimize(&program);
+        assert_eq!(
+            optimized.to_string(),
+            "instructions: 119 -> 117\nfolded: 8\ndecided: 0\nthreaded: 2\nremoved: 0\n\
+             refused: 22..24, 107..111, 130..133, 247..250\nunknown jumps: 130, 247\n"
+        );
+        let domain = Domain::Exhaustive(vec![Values::Range(0..=49), Values::Range(0..=49)]);
+        let report = check(&program, &optimized.program, &domain, 100_000);
+        assert!(report.is_equivalent(), "{}", report);
+
+        let scan = |program: &[i64]| -> u64 {
+            (0..50)
+                .flat_map(|x| (0..50).map(move |y| [x, y]))
+                .map(|inputs| steps(program, &inputs))
+                .sum()
+        };
+        assert_eq!(
+            (scan(&program), scan(&optimized.program)),
+            (806_560, 792_666)
+        );
+    }
+
+    fn run(program: &[i64], inputs: &[i64]) -> Execution {
+        let optimized = optimize(program).program;
+        let mut computer = IntCodeComputer::new(&optimized);
+        for &val in inputs {
+            computer.add_input(val);
+        }
+        let outputs = computer.run_program();
+        // Cells the optimizer rewrote hold its code rather than the program's data, unless the
+        // program wrote them itself.
+        let mut memory = computer.memory().to_vec();
+        for (addr, (&before, &after)) in program.iter().zip(&optimized).enumerate() {
+            if memory[addr] == after {
+                memory[addr] = before;
+            }
+        }
+        Execution { outputs, memory }
+    }
+
+    const EXPECTED: &[&str] = &[];
+
+    #[test]
+    fn conformance() {
+        let report = certify(&run, 9).unwrap();
+        assert_eq!(report.failed_names(), EXPECTED, "{}", report);
+    }
+}
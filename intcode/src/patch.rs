@@ -0,0 +1,330 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+
+/// A single change to a program. Every edit names the value it expects to overwrite, so a patch
+/// meant for one program cannot silently corrupt another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Set {
+        addr: usize,
+        expected: i64,
+        value: i64,
+    },
+    Replace {
+        addr: usize,
+        expected: Vec<i64>,
+        values: Vec<i64>,
+    },
+    /// Turns the instruction at `addr`, whose first cell is `expected`, into one that does
+    /// nothing and falls through to the next instruction.
+    Nop { addr: usize, expected: i64 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub name: String,
+    pub edits: Vec<Edit>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    Mismatch {
+        patch: String,
+        addr: usize,
+        expected: i64,
+        found: Option<i64>,
+    },
+    CannotNop {
+        patch: String,
+        addr: usize,
+        inst: i64,
+    },
+    UnknownPatch(String),
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Mismatch {
+                patch,
+                addr,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "patch {}: expected {} at address {}, found {}",
+                patch, expected, addr, found
+            ),
+            PatchError::Mismatch {
+                patch,
+                addr,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "patch {}: expected {} at address {}, which is past the end of the program",
+                patch, expected, addr
+            ),
+            PatchError::CannotNop { patch, addr, inst } => write!(
+                f,
+                "patch {}: cannot turn instruction {} at address {} into a no-op",
+                patch, inst, addr
+            ),
+            PatchError::UnknownPatch(name) => write!(f, "no patch named {}", name),
+            PatchError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+impl From<PatchError> for io::Error {
+    fn from(err: PatchError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+// Intcode has no no-op instruction, so a removed instruction becomes one that has no effect:
+// a relative base adjustment by 0 for 2-cell instructions, or a jump to the next instruction.
+fn nop_for(inst: i64, addr: usize) -> Option<Vec<i64>> {
+    match inst % 100 {
+        3 | 4 | 9 => Some(vec![109, 0]),
+        5 | 6 => Some(vec![1106, 0, addr as i64 + 3]),
+        1 | 2 | 7 | 8 => Some(vec![1106, 0, addr as i64 + 4]),
+        _ => None,
+    }
+}
+
+impl Patch {
+    /// Applies every edit, or none of them if any expected value does not match.
+    pub fn apply(&self, program: &mut [i64]) -> Result<(), PatchError> {
+        let mut writes = Vec::new();
+        for edit in &self.edits {
+            match edit {
+                Edit::Set {
+                    addr,
+                    expected,
+                    value,
+                } => {
+                    self.check(program, *addr, &[*expected])?;
+                    writes.push((*addr, vec![*value]));
+                }
+                Edit::Replace {
+                    addr,
+                    expected,
+                    values,
+                } => {
+                    self.check(program, *addr, expected)?;
+                    writes.push((*addr, values.clone()));
+                }
+                Edit::Nop { addr, expected } => {
+                    self.check(program, *addr, &[*expected])?;
+                    let cannot_nop = || PatchError::CannotNop {
+                        patch: self.name.clone(),
+                        addr: *addr,
+                        inst: *expected,
+                    };
+                    let nop = nop_for(*expected, *addr).ok_or_else(cannot_nop)?;
+                    if addr + nop.len() > program.len() {
+                        return Err(cannot_nop());
+                    }
+                    writes.push((*addr, nop));
+                }
+            }
+        }
+
+        for (addr, values) in writes {
+            program[addr..addr + values.len()].copy_from_slice(&values);
+        }
+        Ok(())
+    }
+
+    fn check(&self, program: &[i64], addr: usize, expected: &[i64]) -> Result<(), PatchError> {
+        for (i, &expected) in expected.iter().enumerate() {
+            let found = program.get(addr + i).copied();
+            if found != Some(expected) {
+                return Err(PatchError::Mismatch {
+                    patch: self.name.clone(),
+                    addr: addr + i,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Named patches, usually loaded from a file next to the puzzle input:
+///
+/// ```text
+/// # Insert two quarters.
+/// [free-play]
+/// set 0 1 -> 2
+/// replace 10 1,2,3 -> 4,5,6
+/// nop 20 1002
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchSet {
+    patches: Vec<Patch>,
+}
+
+fn parse_value(token: &str, line: usize) -> Result<i64, PatchError> {
+    token.parse().map_err(|_| PatchError::Parse {
+        line,
+        message: format!("invalid value {:?}", token),
+    })
+}
+
+fn parse_values(token: &str, line: usize) -> Result<Vec<i64>, PatchError> {
+    token.split(',').map(|s| parse_value(s, line)).collect()
+}
+
+fn parse_edit(tokens: &[&str], line: usize) -> Result<Edit, PatchError> {
+    let parse_addr = |token: &str| {
+        token.parse::<usize>().map_err(|_| PatchError::Parse {
+            line,
+            message: format!("invalid address {:?}", token),
+        })
+    };
+
+    match tokens {
+        ["set", addr, expected, "->", value] => Ok(Edit::Set {
+            addr: parse_addr(addr)?,
+            expected: parse_value(expected, line)?,
+            value: parse_value(value, line)?,
+        }),
+        ["replace", addr, expected, "->", values] => {
+            let (expected, values) = (parse_values(expected, line)?, parse_values(values, line)?);
+            if expected.len() != values.len() {
+                return Err(PatchError::Parse {
+                    line,
+                    message: "replace needs as many new values as expected ones".to_string(),
+                });
+            }
+            Ok(Edit::Replace {
+                addr: parse_addr(addr)?,
+                expected,
+                values,
+            })
+        }
+        ["nop", addr, expected] => Ok(Edit::Nop {
+            addr: parse_addr(addr)?,
+            expected: parse_value(expected, line)?,
+        }),
+        _ => Err(PatchError::Parse {
+            line,
+            message: format!("unrecognised edit {:?}", tokens.join(" ")),
+        }),
+    }
+}
+
+impl PatchSet {
+    pub fn parse(text: &str) -> Result<Self, PatchError> {
+        let mut patches: Vec<Patch> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                patches.push(Patch {
+                    name: line[1..line.len() - 1].trim().to_string(),
+                    edits: Vec::new(),
+                });
+                continue;
+            }
+
+            let tokens: Vec<_> = line.split_whitespace().collect();
+            let edit = parse_edit(&tokens, line_no)?;
+            match patches.last_mut() {
+                Some(patch) => patch.edits.push(edit),
+                None => {
+                    return Err(PatchError::Parse {
+                        line: line_no,
+                        message: "edit outside of a [patch] section".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(Self { patches })
+    }
+
+    pub fn load(filepath: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&read_to_string(filepath)?)?)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Patch> {
+        self.patches.iter().find(|patch| patch.name == name)
+    }
+
+    pub fn apply(&self, name: &str, program: &mut [i64]) -> Result<(), PatchError> {
+        self.get(name)
+            .ok_or_else(|| PatchError::UnknownPatch(name.to_string()))?
+            .apply(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::IntCodeComputer;
+
+    const PATCHES: &str = "
+# Skips printing the first value and doubles the second.
+[quiet]
+nop 0 4  # first output
+replace 2 104,7 -> 104,14
+
+[wrong]
+set 0 1 -> 2
+";
+
+    #[test]
+    fn patches_apply_named_edits() {
+        let patches = PatchSet::parse(PATCHES).unwrap();
+        let mut program = vec![4, 0, 104, 7, 99];
+        patches.apply("quiet", &mut program).unwrap();
+        assert_eq!(IntCodeComputer::new(&program).run_program(), [14]);
+    }
+
+    #[test]
+    fn patches_check_original_values() {
+        let patches = PatchSet::parse(PATCHES).unwrap();
+        let mut program = vec![4, 0, 104, 7, 99];
+        assert_eq!(
+            patches.apply("wrong", &mut program),
+            Err(PatchError::Mismatch {
+                patch: "wrong".to_string(),
+                addr: 0,
+                expected: 1,
+                found: Some(4),
+            })
+        );
+        assert_eq!(
+            patches.apply("missing", &mut program),
+            Err(PatchError::UnknownPatch("missing".to_string()))
+        );
+        assert_eq!(program, [4, 0, 104, 7, 99]);
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        assert_eq!(
+            PatchSet::parse("[a]\nset 0 1 2"),
+            Err(PatchError::Parse {
+                line: 2,
+                message: "unrecognised edit \"set 0 1 2\"".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Runs Intcode machines in other processes over a line protocol on their stdin and stdout, so
+//! that solutions written against `Machine` can drive another implementation unchanged.
+//!
+//! The client sends one command per line:
+//!
+//! ```text
+//! LOAD 1,0,0,3,99     replaces the program, starting it afresh with no input queued
+//! INPUT 7             queues an input
+//! RUN                 runs until the next output, wait for input, halt or fault
+//! ```
+//!
+//! `LOAD` and `INPUT` are not answered. Every `RUN` is answered with one line:
+//!
+//! ```text
+//! OUTPUT 42                 the program output 42
+//! NEED_INPUT                the program wants input and none is queued
+//! HALT                      the program halted
+//! FAULT invalid-opcode 4 42 the program faulted; see below
+//! ```
+//!
+//! A fault is its kind and instruction pointer followed by its other fields, in the order they
+//! are declared in `Fault`: `overflow IP OPCODE FST SND`, `invalid-opcode IP OPCODE`,
+//! `invalid-mode IP INST`, `invalid-address IP ADDR` or `immediate-write IP INST`. Halts and
+//! faults are sticky: running again answers the same way.
+//!
+//! A server that cannot make sense of a line answers `ERROR` followed by a message and exits.
+//! The server in this crate is the `intcode-server` binary.
+
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::fault::Fault;
+use crate::load::parse_program;
+use crate::machine::Machine;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn format_fault(fault: &Fault) -> String {
+    match fault {
+        Fault::Overflow {
+            ip,
+            opcode,
+            operands: [fst, snd],
+        } => format!("overflow {} {} {} {}", ip, opcode, fst, snd),
+        Fault::InvalidOpcode { ip, opcode } => format!("invalid-opcode {} {}", ip, opcode),
+        Fault::InvalidMode { ip, inst } => format!("invalid-mode {} {}", ip, inst),
+        Fault::InvalidAddress { ip, addr } => format!("invalid-address {} {}", ip, addr),
+        Fault::ImmediateWrite { ip, inst } => format!("immediate-write {} {}", ip, inst),
+    }
+}
+
+fn parse_fault(text: &str) -> Option<Fault> {
+    let mut words = text.split_whitespace();
+    let kind = words.next()?;
+    let ip = words.next()?.parse().ok()?;
+    let fields = words
+        .map(|word| word.parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    let fault = match (kind, &fields[..]) {
+        ("overflow", &[opcode, fst, snd]) => Fault::Overflow {
+            ip,
+            opcode,
+            operands: [fst, snd],
+        },
+        ("invalid-opcode", &[opcode]) => Fault::InvalidOpcode { ip, opcode },
+        ("invalid-mode", &[inst]) => Fault::InvalidMode { ip, inst },
+        ("invalid-address", &[addr]) => Fault::InvalidAddress { ip, addr },
+        ("immediate-write", &[inst]) => Fault::ImmediateWrite { ip, inst },
+        _ => return None,
+    };
+    Some(fault)
+}
+
+fn handle<W: Write>(
+    line: &str,
+    computer: &mut Option<IntCodeComputer>,
+    output: &mut W,
+) -> Result<(), String> {
+    let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+        "LOAD" => {
+            let program = parse_program(arg).map_err(|err| err.to_string())?;
+            *computer = Some(IntCodeComputer::new(&program));
+            return Ok(());
+        }
+        "INPUT" | "RUN" => {}
+        _ => return Err(format!("unknown command {:?}", line)),
+    }
+    let computer = computer.as_mut().ok_or("no program is loaded")?;
+    if command == "INPUT" {
+        let val = arg
+            .trim()
+            .parse()
+            .map_err(|_| format!("INPUT expects a number, got {:?}", arg))?;
+        computer.add_input(val);
+        return Ok(());
+    }
+    let reply = match computer.run_one_turn() {
+        ResultCode::Output(val) => format!("OUTPUT {}", val),
+        ResultCode::Input => "NEED_INPUT".to_string(),
+        ResultCode::Terminated => "HALT".to_string(),
+        ResultCode::Fault(fault) => format!("FAULT {}", format_fault(&fault)),
+    };
+    writeln!(output, "{}", reply).map_err(|err| err.to_string())
+}
+
+/// Serves the protocol with the shared computer until `input` ends or a line cannot be
+/// understood, which is answered with `ERROR` and returned as an error.
+pub fn serve<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut computer = None;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let handled = handle(line, &mut computer, &mut output);
+        if line == "RUN" || handled.is_err() {
+            if let Err(message) = &handled {
+                writeln!(output, "ERROR {}", message)?;
+            }
+            output.flush()?;
+        }
+        handled.map_err(invalid_data)?;
+    }
+    Ok(())
+}
+
+/// A machine on the other end of the protocol, usually a child process.
+///
+/// `Machine` has no room for errors, so like the high-level helpers, a client panics if the
+/// server goes away or answers something it does not understand.
+pub struct Remote<R = BufReader<ChildStdout>, W = BufWriter<ChildStdin>> {
+    reader: R,
+    writer: W,
+    output: Vec<i64>,
+    is_halted: bool,
+    child: Option<Child>,
+}
+
+impl Remote {
+    /// Starts `command` as a server and loads `program` into it. The child is killed when the
+    /// client is dropped.
+    pub fn spawn(command: &mut Command, program: &[i64]) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        let writer = BufWriter::new(child.stdin.take().unwrap());
+        let mut remote = Self::new(reader, writer, program)?;
+        remote.child = Some(child);
+        Ok(remote)
+    }
+}
+
+impl<R: BufRead, W: Write> Remote<R, W> {
+    /// Talks to a server that reads `writer` and writes `reader`, loading `program` into it.
+    pub fn new(reader: R, mut writer: W, program: &[i64]) -> io::Result<Self> {
+        let program: Vec<_> = program.iter().map(|val| val.to_string()).collect();
+        writeln!(writer, "LOAD {}", program.join(","))?;
+        Ok(Self {
+            reader,
+            writer,
+            output: Vec::new(),
+            is_halted: false,
+            child: None,
+        })
+    }
+
+    fn run(&mut self) -> io::Result<ResultCode> {
+        writeln!(self.writer, "RUN")?;
+        self.writer.flush()?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed its output",
+            ));
+        }
+        let line = line.trim();
+        let (reply, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let result = match reply {
+            "OUTPUT" => arg.parse().ok().map(ResultCode::Output),
+            "NEED_INPUT" => Some(ResultCode::Input),
+            "HALT" => Some(ResultCode::Terminated),
+            "FAULT" => parse_fault(arg).map(ResultCode::Fault),
+            "ERROR" => return Err(invalid_data(format!("the server failed: {}", arg))),
+            _ => None,
+        };
+        result.ok_or_else(|| invalid_data(format!("unexpected reply {:?}", line)))
+    }
+}
+
+impl<R: BufRead, W: Write> Machine for Remote<R, W> {
+    fn add_input(&mut self, val: i64) {
+        writeln!(self.writer, "INPUT {}", val).expect("could not send input to the server");
+    }
+
+    fn run_one_turn(&mut self) -> ResultCode {
+        let result = self.run().expect("could not run the remote machine");
+        match result {
+            ResultCode::Output(val) => self.output.push(val),
+            ResultCode::Terminated => self.is_halted = true,
+            _ => {}
+        }
+        result
+    }
+
+    fn output(&self) -> &[i64] {
+        &self.output
+    }
+
+    fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+}
+
+impl<R, W> Drop for Remote<R, W> {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn serve_script(script: &str) -> (Vec<String>, io::Result<()>) {
+        let mut output = Vec::new();
+        let result = serve(Cursor::new(script), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        (output.lines().map(String::from).collect(), result)
+    }
+
+    #[test]
+    fn serves_the_protocol() {
+        let (replies, result) =
+            serve_script("LOAD 3,0,4,0,99\nRUN\nINPUT 7\nRUN\nRUN\nRUN\nLOAD 1,0,0,0,42\nRUN\n");
+        assert!(result.is_ok());
+        assert_eq!(
+            replies,
+            [
+                "NEED_INPUT",
+                "OUTPUT 7",
+                "HALT",
+                "HALT",
+                "FAULT invalid-opcode 4 42"
+            ]
+        );
+
+        let (replies, result) = serve_script("RUN\nLOAD 99\n");
+        assert_eq!(replies, ["ERROR no program is loaded"]);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let (replies, _) = serve_script("LOAD 3,0,99\nINPUT x\n");
+        assert_eq!(replies, [r#"ERROR INPUT expects a number, got "x""#]);
+    }
+
+    #[test]
+    fn faults_round_trip() {
+        let faults = [
+            Fault::Overflow {
+                ip: 4,
+                opcode: 2,
+                operands: [i64::MAX, -3],
+            },
+            Fault::InvalidOpcode { ip: 0, opcode: 42 },
+            Fault::InvalidMode { ip: 1, inst: 301 },
+            Fault::InvalidAddress { ip: 2, addr: -1 },
+            Fault::ImmediateWrite { ip: 3, inst: 11101 },
+        ];
+        for fault in &faults {
+            assert_eq!(parse_fault(&format_fault(fault)).as_ref(), Some(fault));
+        }
+        assert_eq!(parse_fault("invalid-opcode 4"), None);
+    }
+}
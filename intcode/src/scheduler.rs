@@ -0,0 +1,350 @@
+use std::convert::TryFrom;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::rng::Rng;
+
+/// Where the outputs of each machine go.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Routing {
+    /// Every output of machine `from` is queued as input of machine `to`. A machine with several
+    /// pipes copies its outputs to each of them.
+    Pipes(Vec<(usize, usize)>),
+    /// Outputs are grouped into packets of `size` values. The first value is the id of the
+    /// receiving machine and the rest are queued as its input.
+    Packets { size: usize },
+    /// Every output is queued as input of every other machine.
+    Broadcast,
+}
+
+/// How machines take turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Each machine runs at most `slice` instructions per round.
+    RoundRobin { slice: usize },
+    /// Each machine runs until it halts or waits for input.
+    UntilBlocked,
+    /// Each round runs one instruction on as many randomly picked machines as there are machines.
+    Random { seed: u64 },
+}
+
+/// Outputs that no machine receives: values without a pipe, or packets for unknown addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub from: usize,
+    pub values: Vec<i64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub instructions: u64,
+    pub turns: u64,
+    pub received: u64,
+    pub sent: u64,
+    pub fallback_reads: u64,
+}
+
+struct Machine {
+    computer: IntCodeComputer,
+    fallback: Option<i64>,
+    starved: bool,
+    packet: Vec<i64>,
+    stats: Stats,
+}
+
+impl Machine {
+    // A machine is idle when it has stopped, is waiting for input, or when it polled for input, got
+    // the fallback and has not sent or received anything since.
+    fn is_idle(&self) -> bool {
+        self.computer.is_halted()
+            || self.computer.fault().is_some()
+            || self.computer.is_blocked()
+            || self.starved
+    }
+}
+
+pub struct Scheduler {
+    machines: Vec<Machine>,
+    routing: Routing,
+    policy: Policy,
+    rng: Rng,
+    outbox: Vec<Message>,
+}
+
+impl Scheduler {
+    pub fn new(routing: Routing, policy: Policy) -> Self {
+        let seed = match policy {
+            Policy::Random { seed } => seed,
+            _ => 0,
+        };
+        Self {
+            machines: Vec::new(),
+            routing,
+            policy,
+            rng: Rng::new(seed),
+            outbox: Vec::new(),
+        }
+    }
+
+    /// Registers a machine and returns its id. Ids are handed out from 0 in order.
+    pub fn add_machine(&mut self, computer: IntCodeComputer) -> usize {
+        self.machines.push(Machine {
+            computer,
+            fallback: None,
+            starved: false,
+            packet: Vec::new(),
+            stats: Stats::default(),
+        });
+        self.machines.len() - 1
+    }
+
+    /// Registers a machine that reads `fallback` instead of waiting when it has no input, like the
+    /// -1 that the day 23 network cards read.
+    pub fn add_machine_with_fallback(&mut self, computer: IntCodeComputer, fallback: i64) -> usize {
+        let id = self.add_machine(computer);
+        self.machines[id].fallback = Some(fallback);
+        id
+    }
+
+    /// Queues input for a machine from outside the network.
+    pub fn send(&mut self, id: usize, val: i64) {
+        self.deliver(id, val)
+    }
+
+    /// A machine that faulted stays stopped; check `fault` on its computer.
+    pub fn computer(&self, id: usize) -> &IntCodeComputer {
+        &self.machines[id].computer
+    }
+
+    pub fn stats(&self, id: usize) -> Stats {
+        self.machines[id].stats
+    }
+
+    pub fn outbox(&self) -> &[Message] {
+        &self.outbox
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// True when every machine has halted or faulted, is waiting for input, or last polled for
+    /// input and got nothing but its fallback. Machines with a fallback may still make progress on
+    /// their own, e.g. by counting polls, so this is "idle" in the day 23 sense.
+    pub fn is_quiescent(&self) -> bool {
+        self.machines.iter().all(Machine::is_idle)
+    }
+
+    pub fn run_round(&mut self) {
+        match self.policy {
+            Policy::RoundRobin { slice } => {
+                for id in 0..self.machines.len() {
+                    self.run_turn(id, slice);
+                }
+            }
+            Policy::UntilBlocked => {
+                for id in 0..self.machines.len() {
+                    self.run_turn(id, usize::MAX);
+                }
+            }
+            Policy::Random { .. } => {
+                for _ in 0..self.machines.len() {
+                    let id = self.rng.below(self.machines.len() as u64) as usize;
+                    self.run_turn(id, 1);
+                }
+            }
+        }
+    }
+
+    /// Runs rounds until the network is quiescent.
+    pub fn run(&mut self) {
+        while !self.is_quiescent() {
+            self.run_round();
+        }
+    }
+
+    // Runs a machine for up to `budget` instructions, stopping early when it halts or has to wait
+    // for input.
+    fn run_turn(&mut self, id: usize, budget: usize) {
+        let machine = &mut self.machines[id];
+        if machine.computer.is_halted() || machine.computer.fault().is_some() {
+            return;
+        }
+        machine.stats.turns += 1;
+
+        for _ in 0..budget {
+            let machine = &mut self.machines[id];
+            match machine.computer.step() {
+                None => machine.stats.instructions += 1,
+                Some(ResultCode::Output(val)) => {
+                    machine.stats.instructions += 1;
+                    machine.stats.sent += 1;
+                    machine.starved = false;
+                    self.route(id, val);
+                }
+                Some(ResultCode::Input) => {
+                    // A machine that polls for input without getting any ends its turn, otherwise
+                    // it could spin on the fallback forever.
+                    if let Some(fallback) = machine.fallback {
+                        machine.computer.add_input(fallback);
+                        machine.computer.step();
+                        machine.starved = true;
+                        machine.stats.instructions += 1;
+                        machine.stats.fallback_reads += 1;
+                    }
+                    return;
+                }
+                Some(ResultCode::Terminated) | Some(ResultCode::Fault(_)) => return,
+            }
+        }
+    }
+
+    fn deliver(&mut self, to: usize, val: i64) {
+        let machine = &mut self.machines[to];
+        machine.computer.add_input(val);
+        machine.starved = false;
+        machine.stats.received += 1;
+    }
+
+    fn route(&mut self, from: usize, val: i64) {
+        let count = self.machines.len();
+        match self
+            .routing
+            .route(from, count, &mut self.machines[from].packet, val)
+        {
+            Route::Deliver(targets, values) => {
+                for to in targets {
+                    for &val in &values {
+                        self.deliver(to, val);
+                    }
+                }
+            }
+            Route::Unrouted(values) => self.outbox.push(Message { from, values }),
+            Route::Pending => {}
+        }
+    }
+}
+
+// Where a value output by a machine ends up.
+pub(crate) enum Route {
+    Deliver(Vec<usize>, Vec<i64>),
+    Unrouted(Vec<i64>),
+    Pending,
+}
+
+impl Routing {
+    // `packet` holds the outputs of machine `from` that are not yet part of a full packet.
+    pub(crate) fn route(
+        &self,
+        from: usize,
+        count: usize,
+        packet: &mut Vec<i64>,
+        val: i64,
+    ) -> Route {
+        match *self {
+            Routing::Pipes(ref pipes) => {
+                let targets: Vec<_> = pipes
+                    .iter()
+                    .filter(|&&(src, _)| src == from)
+                    .map(|&(_, dst)| dst)
+                    .collect();
+                if targets.is_empty() {
+                    Route::Unrouted(vec![val])
+                } else {
+                    Route::Deliver(targets, vec![val])
+                }
+            }
+            Routing::Packets { size } => {
+                packet.push(val);
+                if packet.len() < size {
+                    return Route::Pending;
+                }
+                let packet = std::mem::take(packet);
+                match usize::try_from(packet[0]) {
+                    Ok(to) if to < count => Route::Deliver(vec![to], packet[1..].to_vec()),
+                    _ => Route::Unrouted(packet),
+                }
+            }
+            Routing::Broadcast => {
+                Route::Deliver((0..count).filter(|&to| to != from).collect(), vec![val])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a value and outputs it plus one, forever.
+    const INCREMENT: [i64; 11] = [3, 20, 101, 1, 20, 20, 4, 20, 1105, 1, 0];
+
+    // Reads a value, outputs it plus one and halts.
+    const INCREMENT_ONCE: [i64; 9] = [3, 20, 101, 1, 20, 20, 4, 20, 99];
+
+    fn increment() -> IntCodeComputer {
+        IntCodeComputer::new(&INCREMENT)
+    }
+
+    #[test]
+    fn pipes_chain_machines() {
+        for policy in [
+            Policy::UntilBlocked,
+            Policy::RoundRobin { slice: 1 },
+            Policy::Random { seed: 7 },
+        ]
+        .iter()
+        {
+            let mut scheduler =
+                Scheduler::new(Routing::Pipes(vec![(0, 1), (1, 2)]), policy.clone());
+            for _ in 0..3 {
+                scheduler.add_machine(increment());
+            }
+            scheduler.send(0, 10);
+            scheduler.send(0, 20);
+            scheduler.run();
+
+            let values: Vec<_> = scheduler
+                .take_outbox()
+                .into_iter()
+                .map(|m| m.values[0])
+                .collect();
+            assert_eq!(values, [13, 23]);
+            assert_eq!(scheduler.stats(1).received, 2);
+            assert_eq!(scheduler.stats(1).sent, 2);
+        }
+    }
+
+    #[test]
+    fn packets_go_to_their_address() {
+        // Outputs the packet [1, 5, 6], then [9, 7, 8] to an address with no machine.
+        let sender = IntCodeComputer::new(&[104, 1, 104, 5, 104, 6, 104, 9, 104, 7, 104, 8, 99]);
+        // Reads two values and outputs their sum back to machine 0.
+        let adder = IntCodeComputer::new(&[3, 20, 3, 21, 104, 0, 1, 20, 21, 22, 4, 22, 104, 0, 99]);
+
+        let mut scheduler = Scheduler::new(Routing::Packets { size: 3 }, Policy::UntilBlocked);
+        scheduler.add_machine_with_fallback(sender, -1);
+        scheduler.add_machine(adder);
+        scheduler.run();
+
+        assert!(scheduler.is_quiescent());
+        assert_eq!(scheduler.outbox()[0].values, [9, 7, 8]);
+        assert_eq!(scheduler.stats(1).received, 2);
+        assert_eq!(scheduler.stats(1).sent, 3);
+        assert_eq!(scheduler.computer(1).output(), [0, 11, 0]);
+    }
+
+    #[test]
+    fn broadcast_reaches_every_other_machine() {
+        let mut scheduler = Scheduler::new(Routing::Broadcast, Policy::UntilBlocked);
+        scheduler.add_machine(IntCodeComputer::new(&[104, 41, 99]));
+        scheduler.add_machine(IntCodeComputer::new(&INCREMENT_ONCE));
+        scheduler.add_machine(IntCodeComputer::new(&INCREMENT_ONCE));
+        scheduler.run();
+
+        assert_eq!(scheduler.computer(1).output(), [42]);
+        assert_eq!(scheduler.computer(2).output(), [42]);
+        // Machine 2 also hears machine 1, but halts before reading it.
+        assert_eq!(scheduler.stats(2).received, 2);
+        assert_eq!(scheduler.stats(0).received, 2);
+    }
+}
@@ -0,0 +1,374 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use crate::computer::IntCodeComputer;
+
+/// A puzzle explored by giving a program moves, such as a maze the program knows the walls of.
+///
+/// Each node of the search pairs a computer with the state the puzzle is in, and its children
+/// are copies of that computer, each given one move. Nothing is run again from the start.
+pub trait Space {
+    type State;
+    type Move: Clone;
+    type Key: Eq + Hash;
+
+    /// The moves to try from `state`.
+    fn moves(&self, state: &Self::State) -> Vec<Self::Move>;
+
+    /// Gives `mv` to `computer`, which is a copy of the one `state` was reached with, and runs
+    /// it. Returns the state it leads to, or `None` if the move goes nowhere.
+    fn apply(
+        &self,
+        computer: &mut IntCodeComputer,
+        state: &Self::State,
+        mv: &Self::Move,
+    ) -> Option<Self::State>;
+
+    /// What makes states the same, so that each is only expanded once. `memory_key` is the key
+    /// when only the program knows the state.
+    fn key(&self, computer: &IntCodeComputer, state: &Self::State) -> Self::Key;
+
+    fn cost(&self, _state: &Self::State, _mv: &Self::Move) -> u64 {
+        1
+    }
+
+    /// A lower bound on the cost from `state` to the goal, for `Strategy::AStar`.
+    fn estimate(&self, _state: &Self::State) -> u64 {
+        0
+    }
+}
+
+/// A hash of everything that decides what a computer does next: its memory, instruction
+/// pointer and relative base. Trailing zeros are left out, since untouched memory reads as 0.
+pub fn memory_key(computer: &IntCodeComputer) -> u64 {
+    let memory = computer.memory();
+    let len = memory
+        .iter()
+        .rposition(|&val| val != 0)
+        .map_or(0, |pos| pos + 1);
+    let mut hasher = DefaultHasher::new();
+    memory[..len].hash(&mut hasher);
+    computer.inst_pointer().hash(&mut hasher);
+    computer.relative_base().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The order nodes are expanded in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Fewest moves first.
+    BreadthFirst,
+    /// Newest node first. Each state is still only expanded once, the first time it is reached.
+    DepthFirst,
+    /// Lowest cost plus `Space::estimate` first, so the first node reached in a state has the
+    /// lowest cost to it if the estimates never overshoot.
+    AStar,
+}
+
+/// A state reached by the search, with the computer that reached it.
+#[derive(Clone, Debug)]
+pub struct Node<T> {
+    pub computer: IntCodeComputer,
+    pub state: T,
+    /// The sum of the costs of the moves to the node.
+    pub cost: u64,
+    /// The number of moves to the node.
+    pub depth: usize,
+    id: usize,
+}
+
+/// A node waiting to be expanded, ordered for `BinaryHeap` so the lowest priority comes out
+/// first, and the oldest among equals.
+struct Pending<T> {
+    node: Node<T>,
+    priority: u64,
+}
+
+impl<T> PartialEq for Pending<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Pending<T> {}
+
+impl<T> PartialOrd for Pending<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Pending<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.priority, other.node.id).cmp(&(self.priority, self.node.id))
+    }
+}
+
+enum Frontier<T> {
+    Queue(VecDeque<Node<T>>),
+    Stack(Vec<Node<T>>),
+    Heap(BinaryHeap<Pending<T>>),
+}
+
+/// Searches a `Space`, yielding each node as it is expanded.
+///
+/// Nodes are deduplicated by `Space::key`: breadth- and depth-first searches skip states that
+/// have been reached before, and A* skips those that have been reached more cheaply.
+pub struct Search<S: Space> {
+    space: S,
+    frontier: Frontier<S::State>,
+    /// The lowest cost each state has been reached with.
+    best: HashMap<S::Key, u64>,
+    /// How each node was reached, by its id.
+    parents: Vec<Option<(usize, S::Move)>>,
+    expanded: usize,
+}
+
+impl<S: Space> Search<S> {
+    /// Starts a search from `computer` in `state`.
+    pub fn new(space: S, computer: IntCodeComputer, state: S::State, strategy: Strategy) -> Self {
+        let mut best = HashMap::new();
+        best.insert(space.key(&computer, &state), 0);
+        let root = Node {
+            computer,
+            state,
+            cost: 0,
+            depth: 0,
+            id: 0,
+        };
+        let frontier = match strategy {
+            Strategy::BreadthFirst => Frontier::Queue(VecDeque::from(vec![root])),
+            Strategy::DepthFirst => Frontier::Stack(vec![root]),
+            Strategy::AStar => {
+                let priority = space.estimate(&root.state);
+                Frontier::Heap(BinaryHeap::from(vec![Pending {
+                    node: root,
+                    priority,
+                }]))
+            }
+        };
+        Self {
+            space,
+            frontier,
+            best,
+            parents: vec![None],
+            expanded: 0,
+        }
+    }
+
+    /// The moves from the start to `node`.
+    pub fn path(&self, node: &Node<S::State>) -> Vec<S::Move> {
+        let mut path = Vec::new();
+        let mut id = node.id;
+        while let Some((parent, mv)) = &self.parents[id] {
+            path.push(mv.clone());
+            id = *parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// How many nodes have been expanded, which is how many times a computer has been copied
+    /// for each move.
+    pub fn expanded(&self) -> usize {
+        self.expanded
+    }
+
+    fn pop(&mut self) -> Option<Node<S::State>> {
+        loop {
+            let node = match &mut self.frontier {
+                Frontier::Queue(queue) => queue.pop_front()?,
+                Frontier::Stack(stack) => stack.pop()?,
+                Frontier::Heap(heap) => {
+                    let node = heap.pop()?.node;
+                    // A cheaper way to the same state was found after this one was queued.
+                    let key = self.space.key(&node.computer, &node.state);
+                    if self.best[&key] < node.cost {
+                        continue;
+                    }
+                    node
+                }
+            };
+            return Some(node);
+        }
+    }
+
+    /// Queues `node`, reached from `parent` with `mv`, unless its state has been reached as
+    /// cheaply before.
+    fn push(&mut self, mut node: Node<S::State>, parent: usize, mv: S::Move) {
+        let key = self.space.key(&node.computer, &node.state);
+        match (self.best.entry(key), &mut self.frontier) {
+            (Entry::Occupied(mut best), Frontier::Heap(_)) if node.cost < *best.get() => {
+                best.insert(node.cost);
+            }
+            (Entry::Occupied(_), _) => return,
+            (Entry::Vacant(best), _) => {
+                best.insert(node.cost);
+            }
+        }
+        node.id = self.parents.len();
+        self.parents.push(Some((parent, mv)));
+        match &mut self.frontier {
+            Frontier::Queue(queue) => queue.push_back(node),
+            Frontier::Stack(stack) => stack.push(node),
+            Frontier::Heap(heap) => {
+                let priority = node.cost + self.space.estimate(&node.state);
+                heap.push(Pending { node, priority });
+            }
+        }
+    }
+}
+
+impl<S: Space> Iterator for Search<S> {
+    type Item = Node<S::State>;
+
+    fn next(&mut self) -> Option<Node<S::State>> {
+        let node = self.pop()?;
+        self.expanded += 1;
+        for mv in self.space.moves(&node.state) {
+            let mut computer = node.computer.clone();
+            let state = match self.space.apply(&mut computer, &node.state, &mv) {
+                Some(state) => state,
+                None => continue,
+            };
+            let child = Node {
+                computer,
+                cost: node.cost + self.space.cost(&node.state, &mv),
+                state,
+                depth: node.depth + 1,
+                // Given when the node is queued.
+                id: 0,
+            };
+            self.push(child, node.id, mv);
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::ResultCode;
+    use crate::device::{Droid, Position};
+    use crate::load::load_program;
+    use crate::machine::Machine;
+    use std::path::Path;
+
+    const MOVES: [(i64, Position); 4] = [(1, (0, -1)), (2, (0, 1)), (3, (-1, 0)), (4, (1, 0))];
+
+    /// The repair droid's maze, with the oxygen system as the goal.
+    struct Maze {
+        oxygen: Position,
+    }
+
+    impl Space for Maze {
+        type State = Position;
+        type Move = i64;
+        type Key = Position;
+
+        fn moves(&self, _: &Position) -> Vec<i64> {
+            MOVES.iter().map(|&(mv, _)| mv).collect()
+        }
+
+        fn apply(
+            &self,
+            computer: &mut IntCodeComputer,
+            pos: &Position,
+            &mv: &i64,
+        ) -> Option<Position> {
+            computer.add_input(mv);
+            let (dx, dy) = MOVES[mv as usize - 1].1;
+            match computer.run_one_turn() {
+                ResultCode::Output(0) => None,
+                ResultCode::Output(_) => Some((pos.0 + dx, pos.1 + dy)),
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+
+        fn key(&self, _: &IntCodeComputer, &pos: &Position) -> Position {
+            pos
+        }
+
+        fn estimate(&self, pos: &Position) -> u64 {
+            ((pos.0 - self.oxygen.0).abs() + (pos.1 - self.oxygen.1).abs()) as u64
+        }
+    }
+
+    #[test]
+    fn strategies_agree_with_the_droid() {
+        let program = load_program(Path::new("../day15/input/input.txt")).unwrap();
+        let mut droid = Droid::new();
+        IntCodeComputer::new(&program).attach(&mut droid);
+        let oxygen = droid.oxygen().unwrap();
+        let distances = droid.distances((0, 0));
+
+        let search = |strategy| {
+            let maze = Maze { oxygen };
+            Search::new(maze, IntCodeComputer::new(&program), (0, 0), strategy)
+        };
+        let mut bfs = search(Strategy::BreadthFirst);
+        let found = bfs.find(|node| node.state == oxygen).unwrap();
+        assert_eq!(found.depth, distances[&oxygen]);
+        assert_eq!(bfs.path(&found).len(), found.depth);
+
+        let mut astar = search(Strategy::AStar);
+        let found = astar.find(|node| node.state == oxygen).unwrap();
+        assert_eq!(found.cost, distances[&oxygen] as u64);
+        assert!(astar.expanded() < bfs.expanded());
+
+        // Every open position is expanded exactly once, whatever the order.
+        let mut dfs = search(Strategy::DepthFirst);
+        let mut visited: Vec<_> = dfs.by_ref().map(|node| node.state).collect();
+        visited.sort_unstable();
+        let mut open: Vec<_> = distances.keys().copied().collect();
+        open.sort_unstable();
+        assert_eq!(visited, open);
+        assert_eq!(dfs.expanded(), open.len());
+    }
+
+    /// Adds inputs of 1 or 2 to a running total, so different moves can lead to the same
+    /// memory.
+    struct Sums;
+
+    impl Space for Sums {
+        type State = i64;
+        type Move = i64;
+        type Key = u64;
+
+        fn moves(&self, _: &i64) -> Vec<i64> {
+            vec![1, 2]
+        }
+
+        fn apply(&self, computer: &mut IntCodeComputer, _: &i64, &mv: &i64) -> Option<i64> {
+            computer.add_input(mv);
+            match computer.run_one_turn() {
+                ResultCode::Output(total) => Some(total),
+                _ => None,
+            }
+        }
+
+        fn key(&self, computer: &IntCodeComputer, _: &i64) -> u64 {
+            memory_key(computer)
+        }
+    }
+
+    #[test]
+    fn deduplicates_by_memory() {
+        // Reads into 20, adds it to 21, outputs 21 and loops.
+        let program = [3, 20, 1, 20, 21, 21, 4, 21, 1105, 1, 0];
+        let mut search = Search::new(
+            Sums,
+            IntCodeComputer::new(&program),
+            0,
+            Strategy::BreadthFirst,
+        );
+        let found = search.find(|node| node.state == 7).unwrap();
+        assert_eq!(found.depth, 4);
+        assert_eq!(search.path(&found), [1, 2, 2, 2]);
+        // The memory holds the last input and the total, so paths that only differ in the
+        // order of their moves meet again.
+        assert_eq!(search.expanded(), 13);
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crate::cell::Cell;
+use crate::computer::IntCodeComputer;
+
+/// A copy of a computer's state, taken so that it can be compared with others.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot<C = i64> {
+    pub label: String,
+    pub inst_pointer: usize,
+    pub relative_base: C,
+    pub memory: Vec<C>,
+}
+
+impl<C: Cell> Snapshot<C> {
+    /// The value at `addr`. Memory past the end reads as 0, as it does for the program.
+    pub fn get(&self, addr: usize) -> C {
+        self.memory.get(addr).cloned().unwrap_or_default()
+    }
+}
+
+impl<C: Cell> IntCodeComputer<C> {
+    pub fn snapshot(&self, label: &str) -> Snapshot<C> {
+        Snapshot {
+            label: label.to_string(),
+            inst_pointer: self.inst_pointer(),
+            relative_base: self.relative_base(),
+            memory: self.memory().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change<C = i64> {
+    pub addr: usize,
+    pub before: C,
+    pub after: C,
+}
+
+impl<C: Cell> fmt::Display for Change<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.addr, self.before, self.after)
+    }
+}
+
+/// Every cell whose value differs between the two snapshots, in address order.
+pub fn diff<C: Cell>(before: &Snapshot<C>, after: &Snapshot<C>) -> Vec<Change<C>> {
+    let len = before.memory.len().max(after.memory.len());
+    (0..len)
+        .map(|addr| Change {
+            addr,
+            before: before.get(addr),
+            after: after.get(addr),
+        })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+/// The values of one cell across a series of snapshots, one per snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct History<C = i64> {
+    pub addr: usize,
+    pub values: Vec<C>,
+}
+
+impl<C: Cell> History<C> {
+    /// The values the cell went through, leaving out snapshots where it did not change.
+    pub fn transitions(&self) -> Vec<C> {
+        let mut transitions: Vec<C> = Vec::new();
+        for val in &self.values {
+            if transitions.last() != Some(val) {
+                transitions.push(val.clone());
+            }
+        }
+        transitions
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.values.windows(2).all(|pair| pair[0] == pair[1])
+    }
+
+    /// Whether the cell went through `values` one after another, such as 3 -> 4 -> 5, at any
+    /// point in the series.
+    pub fn went(&self, values: &[C]) -> bool {
+        values.is_empty()
+            || self
+                .transitions()
+                .windows(values.len())
+                .any(|window| window == values)
+    }
+}
+
+impl<C: Cell> fmt::Display for History<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.addr)?;
+        for (i, val) in self.transitions().iter().enumerate() {
+            if i > 0 {
+                write!(f, " ->")?;
+            }
+            write!(f, " {}", val)?;
+        }
+        Ok(())
+    }
+}
+
+/// A series of snapshots that can be searched for the cells holding some piece of a program's
+/// state, the way a game memory scanner looks for the cell holding the score.
+#[derive(Clone, Debug, Default)]
+pub struct Scanner<C = i64> {
+    snapshots: Vec<Snapshot<C>>,
+}
+
+impl<C: Cell> Scanner<C> {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot<C>) {
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot<C>] {
+        &self.snapshots
+    }
+
+    fn len(&self) -> usize {
+        self.snapshots
+            .iter()
+            .map(|snapshot| snapshot.memory.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn history(&self, addr: usize) -> History<C> {
+        History {
+            addr,
+            values: self
+                .snapshots
+                .iter()
+                .map(|snapshot| snapshot.get(addr))
+                .collect(),
+        }
+    }
+
+    /// The history of every cell that matches `predicate`, in address order.
+    pub fn search<P: Fn(&History<C>) -> bool>(&self, predicate: P) -> Vec<History<C>> {
+        (0..self.len())
+            .map(|addr| self.history(addr))
+            .filter(|history| predicate(history))
+            .collect()
+    }
+
+    /// The history of every cell that did not hold the same value in every snapshot.
+    pub fn changed(&self) -> Vec<History<C>> {
+        self.search(|history| !history.is_constant())
+    }
+
+    /// The addresses of the cells that went through `values` one after another.
+    pub fn went(&self, values: &[C]) -> Vec<usize> {
+        self.search(|history| history.went(values))
+            .iter()
+            .map(|history| history.addr)
+            .collect()
+    }
+}
+
+impl<C: Cell> fmt::Display for Scanner<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for history in self.changed() {
+            writeln!(f, "{}", history)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::ResultCode;
+    use crate::device::{Arcade, Detach, Device, Tile};
+    use crate::load::load_program;
+    use crate::machine::Machine;
+    use crate::patch::PatchSet;
+    use std::path::Path;
+
+    // Counts from 3 to 5 in cell 15, outputting after each increment, with cell 14 holding
+    // whether to go on.
+    const COUNTER: [i64; 16] = [
+        1001, 15, 1, 15, 4, 15, 1007, 15, 5, 14, 1005, 14, 0, 99, 0, 3,
+    ];
+
+    #[test]
+    fn diffs_and_histories() {
+        let mut computer = IntCodeComputer::new(&COUNTER);
+        let mut scanner = Scanner::new();
+        scanner.push(computer.snapshot("start"));
+        while let ResultCode::Output(_) = computer.run_one_turn() {
+            scanner.push(computer.snapshot("output"));
+        }
+
+        let first = diff(&scanner.snapshots()[0], &scanner.snapshots()[1]);
+        let first: Vec<_> = first.iter().map(|change| change.to_string()).collect();
+        assert_eq!(first, ["15: 3 -> 4"]);
+        assert_eq!(scanner.went(&[3, 4, 5]), [15]);
+        assert_eq!(scanner.went(&[0, 1]), [14]);
+        assert_eq!(scanner.to_string(), "14: 0 -> 1\n15: 3 -> 4 -> 5\n");
+        assert_eq!(scanner.history(14).values, [0, 0, 1]);
+        assert_eq!(scanner.history(100).values, [0, 0, 0]);
+    }
+
+    /// Plays the arcade game, pausing after every move of the joystick.
+    struct Paused {
+        arcade: Arcade,
+        moved: bool,
+    }
+
+    impl Device for Paused {
+        fn on_output(&mut self, val: i64) {
+            self.arcade.on_output(val);
+        }
+
+        fn next_input(&mut self) -> Option<i64> {
+            self.moved = true;
+            self.arcade.next_input()
+        }
+
+        fn is_done(&self) -> bool {
+            self.moved
+        }
+    }
+
+    #[test]
+    fn finds_the_arcade_score_and_ball() {
+        let mut program = load_program(Path::new("../day13/input/input.txt")).unwrap();
+        let patches = PatchSet::load(Path::new("../day13/input/patches.txt")).unwrap();
+        patches.apply("free-play", &mut program).unwrap();
+
+        let mut computer = IntCodeComputer::new(&program);
+        let mut device = Paused {
+            arcade: Arcade::new(),
+            moved: false,
+        };
+        let (mut scores, mut scanner) = (Vec::new(), Scanner::new());
+        let mut balls = Vec::new();
+        loop {
+            let detach = computer.attach(&mut device);
+            device.moved = false;
+            let score = device.arcade.score();
+            if scores.last() != Some(&score) {
+                scores.push(score);
+                scanner.push(computer.snapshot("score"));
+            }
+            if balls.len() < 50 {
+                let screen = device.arcade.screen();
+                let ball = screen.iter().find(|(_, &tile)| tile == Tile::Ball);
+                balls.push(ball.unwrap().0 .0);
+            }
+            if detach == Detach::Halted {
+                break;
+            }
+        }
+        assert_eq!(scores.last(), Some(&15909));
+        let score_cells = scanner.went(&scores);
+        assert_eq!(score_cells.len(), 1);
+
+        // The ball moves every frame, so rerun the start of the game to snapshot every frame.
+        let mut computer = IntCodeComputer::new(&program);
+        device.arcade = Arcade::new();
+        let mut scanner = Scanner::new();
+        for _ in 0..balls.len() {
+            computer.attach(&mut device);
+            device.moved = false;
+            scanner.push(computer.snapshot("frame"));
+        }
+        let ball_cells = scanner.search(|history| history.values == balls);
+        assert!(!ball_cells.is_empty());
+        assert!(!ball_cells
+            .iter()
+            .any(|history| score_cells.contains(&history.addr)));
+    }
+}
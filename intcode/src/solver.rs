@@ -0,0 +1,277 @@
+//! Finds the values of chosen cells that make a program leave a target value behind, the way
+//! day 2 looks for the noun and verb that produce 19690720.
+//!
+//! `solve` tries every assignment, split across threads. `solve_affine` fits the output as an
+//! affine function of the inputs first, which day 2's programs are, and only runs the program
+//! to confirm the assignments the fit predicts.
+
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::thread;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::rng::Rng;
+
+/// How many points an affine fit is checked on, besides the ones it was fitted on.
+const SAMPLES: usize = 32;
+
+/// A program whose output cell depends on the cells it is given.
+#[derive(Clone, Debug)]
+pub struct Problem<'a> {
+    program: &'a [i64],
+    inputs: Vec<(usize, RangeInclusive<i64>)>,
+    output: usize,
+    max_steps: u64,
+    threads: usize,
+}
+
+/// The output as `constant` plus the sum of each input times its coefficient.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Affine {
+    pub constant: i64,
+    pub coefficients: Vec<i64>,
+}
+
+impl Affine {
+    fn eval(&self, values: &[i64]) -> Option<i64> {
+        let mut sum = i128::from(self.constant);
+        for (&coefficient, &val) in self.coefficients.iter().zip(values) {
+            sum += i128::from(coefficient) * i128::from(val);
+        }
+        i64::try_from(sum).ok()
+    }
+}
+
+fn len(range: &RangeInclusive<i64>) -> u64 {
+    if range.is_empty() {
+        return 0;
+    }
+    let len = i128::from(*range.end()) - i128::from(*range.start()) + 1;
+    u64::try_from(len).expect("too many values to try")
+}
+
+impl<'a> Problem<'a> {
+    /// A problem that reads `output` once `program` halts. Runs are limited to 100000
+    /// instructions, and spread over as many threads as the machine has.
+    pub fn new(program: &'a [i64], output: usize) -> Self {
+        Self {
+            program,
+            inputs: Vec::new(),
+            output,
+            max_steps: 100_000,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    /// Adds a cell to set before the program runs, and the values to try in it.
+    pub fn input(mut self, addr: usize, values: RangeInclusive<i64>) -> Self {
+        self.inputs.push((addr, values));
+        self
+    }
+
+    /// Runs that take more than `max_steps` instructions are taken to have no output.
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The output cell after running the program with `values` in the input cells, in the
+    /// order they were added, or `None` if the program faults, wants input or runs too long.
+    pub fn eval(&self, values: &[i64]) -> Option<i64> {
+        let mut program = self.program.to_vec();
+        for (&(addr, _), &val) in self.inputs.iter().zip(values) {
+            if addr >= program.len() {
+                program.resize(addr + 1, 0);
+            }
+            program[addr] = val;
+        }
+        let mut computer = IntCodeComputer::new(&program);
+        for _ in 0..self.max_steps {
+            match computer.step() {
+                None | Some(ResultCode::Output(_)) => {}
+                Some(ResultCode::Terminated) => {
+                    return Some(computer.memory().get(self.output).copied().unwrap_or(0))
+                }
+                Some(ResultCode::Input) | Some(ResultCode::Fault(_)) => return None,
+            }
+        }
+        None
+    }
+
+    fn count(&self) -> u64 {
+        self.inputs.iter().fold(1, |count, (_, range)| {
+            count
+                .checked_mul(len(range))
+                .expect("too many assignments to try")
+        })
+    }
+
+    /// The `index`th assignment, with the last input varying fastest.
+    fn assignment(&self, mut index: u64) -> Vec<i64> {
+        let mut values = vec![0; self.inputs.len()];
+        for (val, (_, range)) in values.iter_mut().zip(&self.inputs).rev() {
+            let len = len(range);
+            *val = (i128::from(*range.start()) + i128::from(index % len)) as i64;
+            index /= len;
+        }
+        values
+    }
+
+    /// Every assignment that leaves `target` in the output cell, in order, with the last input
+    /// varying fastest.
+    pub fn solve(&self, target: i64) -> Vec<Vec<i64>> {
+        let count = self.count();
+        let threads = self.threads as u64;
+        let chunk = count.div_ceil(threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|i| {
+                    let indices = (i * chunk).min(count)..((i + 1) * chunk).min(count);
+                    scope.spawn(move || {
+                        indices
+                            .map(|index| self.assignment(index))
+                            .filter(|values| self.eval(values) == Some(target))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        })
+    }
+
+    /// Fits the output as an affine function of the inputs from the lowest values and one step
+    /// up in each input, then checks the fit on the highest values and on random ones. Returns
+    /// `None` if the fit fails or the program has no output somewhere it is tried.
+    pub fn affine(&self) -> Option<Affine> {
+        if self.count() == 0 {
+            return None;
+        }
+        let base: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|(_, range)| *range.start())
+            .collect();
+        let constant = self.eval(&base)?;
+        let mut coefficients = Vec::new();
+        for (i, (_, range)) in self.inputs.iter().enumerate() {
+            if len(range) < 2 {
+                coefficients.push(0);
+                continue;
+            }
+            let mut values = base.clone();
+            values[i] += 1;
+            coefficients.push(self.eval(&values)?.checked_sub(constant)?);
+        }
+        let fit = Affine {
+            constant: constant.checked_sub(fit_offset(&coefficients, &base)?)?,
+            coefficients,
+        };
+
+        let mut rng = Rng::new(0);
+        let highest = self.assignment(self.count() - 1);
+        let samples = (0..SAMPLES).map(|_| self.assignment(rng.below(self.count())));
+        for values in std::iter::once(highest).chain(samples) {
+            if self.eval(&values)? != fit.eval(&values)? {
+                return None;
+            }
+        }
+        Some(fit)
+    }
+
+    /// Like `solve`, but through the affine fit: every input but the last one that matters is
+    /// tried, and the value of that one is worked out. Every assignment returned is confirmed
+    /// by running the program, but assignments are missed if the program is only affine on
+    /// the points the fit was checked on. Returns `None` if there is no fit.
+    pub fn solve_affine(&self, target: i64) -> Option<Vec<Vec<i64>>> {
+        let fit = self.affine()?;
+        let pivot = match fit
+            .coefficients
+            .iter()
+            .rposition(|&coefficient| coefficient != 0)
+        {
+            Some(pivot) => pivot,
+            None if fit.constant == target => return Some(self.solve(target)),
+            None => return Some(Vec::new()),
+        };
+        let mut rest = self.clone();
+        rest.inputs[pivot].1 = 0..=0;
+        let mut solutions = Vec::new();
+        for index in 0..rest.count() {
+            let mut values = rest.assignment(index);
+            // `values[pivot]` is 0, so this is everything but the pivot's term.
+            let remainder = match fit.eval(&values) {
+                Some(rest) => i128::from(target) - i128::from(rest),
+                None => continue,
+            };
+            let coefficient = i128::from(fit.coefficients[pivot]);
+            if remainder % coefficient != 0 {
+                continue;
+            }
+            let val = match i64::try_from(remainder / coefficient) {
+                Ok(val) if self.inputs[pivot].1.contains(&val) => val,
+                _ => continue,
+            };
+            values[pivot] = val;
+            if self.eval(&values) == Some(target) {
+                solutions.push(values);
+            }
+        }
+        Some(solutions)
+    }
+}
+
+/// The sum of the coefficients times the values the fit was made at.
+fn fit_offset(coefficients: &[i64], values: &[i64]) -> Option<i64> {
+    coefficients
+        .iter()
+        .zip(values)
+        .try_fold(0i64, |sum, (&coefficient, &val)| {
+            sum.checked_add(coefficient.checked_mul(val)?)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::load_program;
+    use std::path::Path;
+
+    #[test]
+    fn solves_day2() {
+        let program = load_program(Path::new("../day2/input/input.txt")).unwrap();
+        let problem = Problem::new(&program, 0).input(1, 0..=99).input(2, 0..=99);
+        let fit = problem.affine().unwrap();
+        assert_eq!(fit.eval(&[12, 2]), problem.eval(&[12, 2]));
+
+        let target = problem.eval(&[12, 2]).unwrap();
+        let solutions = problem.solve(target);
+        assert!(solutions.contains(&vec![12, 2]));
+        assert_eq!(problem.solve_affine(target), Some(solutions.clone()));
+        assert_eq!(problem.clone().with_threads(1).solve(target), solutions);
+    }
+
+    #[test]
+    fn refuses_programs_that_are_not_affine() {
+        // Multiplies cell 9 by itself into cell 0.
+        let program = [2, 9, 9, 0, 99, 0, 0, 0, 0, 0];
+        let problem = Problem::new(&program, 0).input(9, -10..=10);
+        assert_eq!(problem.affine(), None);
+        assert_eq!(problem.solve_affine(49), None);
+        assert_eq!(problem.solve(49), [[-7], [7]]);
+
+        // Adds cells 9 and 10 into cell 0, so there is a solution for every value of cell 9.
+        let program = [1, 9, 10, 0, 99, 0, 0, 0, 0, 0, 0];
+        let problem = Problem::new(&program, 0).input(9, 0..=5).input(10, 0..=5);
+        let solutions = problem.solve_affine(5).unwrap();
+        assert_eq!(solutions.len(), 6);
+        assert_eq!(solutions, problem.solve(5));
+    }
+}
@@ -0,0 +1,437 @@
+//! Finds the messages and constant tables embedded in a program, the way `strings` does for
+//! binaries, along with the instructions that refer to them.
+//!
+//! Only cells that are not reachable code are looked at, and the analysis is static: tables
+//! are the data no instruction writes to directly, so cells written through pointers or the
+//! relative base can still show up as constant.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+use crate::decode::{reachable, Instruction, Mode};
+
+/// What to look for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The fewest characters a string is reported with. Encoded strings need twice as many.
+    pub min_len: usize,
+    /// The strides tried for encoded strings, alongside plain ones.
+    pub strides: Vec<i64>,
+    /// Encoded strings are tried with every offset up to this far from 0. 0 only finds plain
+    /// strings.
+    pub max_offset: i64,
+    /// The fewest cells a constant table is reported with.
+    pub min_table_len: usize,
+}
+
+impl Default for Config {
+    /// Strings of at least 4 characters, encoded with strides of up to 1 and offsets of up to
+    /// 128, and tables of at least 16 cells.
+    fn default() -> Self {
+        Self {
+            min_len: 4,
+            strides: vec![-1, 0, 1],
+            max_offset: 128,
+            min_table_len: 16,
+        }
+    }
+}
+
+/// How a string's characters are stored: character `i` is held as its code plus
+/// `offset + stride * i`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Encoding {
+    pub offset: i64,
+    pub stride: i64,
+}
+
+impl Encoding {
+    pub const PLAIN: Self = Self {
+        offset: 0,
+        stride: 0,
+    };
+
+    fn decode(&self, i: usize, cell: i64) -> Option<char> {
+        let code = cell
+            .checked_sub(self.offset)?
+            .checked_sub(self.stride.checked_mul(i as i64)?)?;
+        match code {
+            10 | 32..=126 => Some(code as u8 as char),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.offset, self.stride) {
+            (0, 0) => write!(f, "plain"),
+            (offset, 0) => write!(f, "offset {}", offset),
+            (offset, stride) => write!(f, "offset {}, stride {}", offset, stride),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// The address is an immediate operand, such as a string passed to a print routine.
+    Pointer,
+}
+
+/// An instruction that refers to a string or table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Xref {
+    pub inst: usize,
+    pub access: Access,
+}
+
+impl fmt::Display for Xref {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => "read",
+            Access::Write => "written",
+            Access::Pointer => "pointed to",
+        };
+        write!(f, "{} at {}", access, self.inst)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Text {
+    /// The address of the first character.
+    pub addr: usize,
+    /// Whether the cell before the first character holds the string's length.
+    pub prefixed: bool,
+    pub encoding: Encoding,
+    pub text: String,
+    pub xrefs: Vec<Xref>,
+}
+
+impl Text {
+    /// The cells holding the string, including its length.
+    pub fn cells(&self) -> Range<usize> {
+        let start = if self.prefixed {
+            self.addr - 1
+        } else {
+            self.addr
+        };
+        start..self.addr + self.text.chars().count()
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.addr, self.text)?;
+        match (self.prefixed, self.encoding) {
+            (false, Encoding::PLAIN) => {}
+            (true, Encoding::PLAIN) => write!(f, " (length-prefixed)")?,
+            (false, encoding) => write!(f, " ({})", encoding)?,
+            (true, encoding) => write!(f, " (length-prefixed, {})", encoding)?,
+        }
+        write_xrefs(f, &self.xrefs)
+    }
+}
+
+/// A run of data cells that no instruction writes to directly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table {
+    pub addr: usize,
+    pub values: Vec<i64>,
+    pub xrefs: Vec<Xref>,
+}
+
+impl Table {
+    pub fn cells(&self) -> Range<usize> {
+        self.addr..self.addr + self.values.len()
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: table of {} cells:", self.addr, self.values.len())?;
+        for val in self.values.iter().take(8) {
+            write!(f, " {}", val)?;
+        }
+        if self.values.len() > 8 {
+            write!(f, " ...")?;
+        }
+        write_xrefs(f, &self.xrefs)
+    }
+}
+
+fn write_xrefs(f: &mut fmt::Formatter, xrefs: &[Xref]) -> fmt::Result {
+    let mut first = true;
+    for access in [Access::Read, Access::Write, Access::Pointer] {
+        let mut insts = xrefs.iter().filter(|xref| xref.access == access);
+        if let Some(xref) = insts.next() {
+            write!(f, "{}{}", if first { ", " } else { "; " }, xref)?;
+            for xref in insts {
+                write!(f, ", {}", xref.inst)?;
+            }
+            first = false;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// In address order.
+    pub strings: Vec<Text>,
+    /// In address order.
+    pub tables: Vec<Table>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for text in &self.strings {
+            writeln!(f, "{}", text)?;
+        }
+        for table in &self.tables {
+            writeln!(f, "{}", table)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a run reads like a message rather than numbers that happen to decode to
+/// characters: mostly lowercase letters and spaces, with some variety.
+fn looks_like_text(text: &str) -> bool {
+    let words = text
+        .chars()
+        .filter(|&c| c.is_ascii_lowercase() || c == ' ')
+        .count();
+    let mut letters: Vec<_> = text.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    letters.sort_unstable();
+    letters.dedup();
+    text.contains(' ') && letters.len() >= 3 && words * 4 >= text.len() * 3
+}
+
+struct Extractor<'a> {
+    program: &'a [i64],
+    config: &'a Config,
+    /// Whether each cell is code or already part of a string.
+    taken: Vec<bool>,
+    strings: Vec<Text>,
+}
+
+impl<'a> Extractor<'a> {
+    fn decode(&self, start: usize, encoding: Encoding, max_len: usize) -> String {
+        self.program[start..]
+            .iter()
+            .take(max_len)
+            .enumerate()
+            .take_while(|&(i, _)| !self.taken[start + i])
+            .map_while(|(i, &cell)| encoding.decode(i, cell))
+            .collect()
+    }
+
+    fn take(&mut self, addr: usize, prefixed: bool, encoding: Encoding, text: String) -> usize {
+        let text = Text {
+            addr,
+            prefixed,
+            encoding,
+            text,
+            xrefs: Vec::new(),
+        };
+        let cells = text.cells();
+        self.taken[cells.clone()].fill(true);
+        self.strings.push(text);
+        cells.end
+    }
+
+    fn scan(&mut self, encoding: Encoding) {
+        let plain = encoding == Encoding::PLAIN;
+        let min_len = self.config.min_len.max(1);
+        let mut addr = 0;
+        while addr < self.program.len() {
+            if self.taken[addr] {
+                addr += 1;
+                continue;
+            }
+            let len = self.program[addr];
+            if len >= min_len as i64 && (len as usize) < self.program.len() - addr {
+                let text = self.decode(addr + 1, encoding, len as usize);
+                if text.len() == len as usize && (plain || looks_like_text(&text)) {
+                    addr = self.take(addr + 1, true, encoding, text);
+                    continue;
+                }
+            }
+            let text = self.decode(addr, encoding, usize::MAX);
+            let min_len = if plain { min_len } else { min_len * 2 };
+            if text.len() >= min_len && looks_like_text(&text) {
+                addr = self.take(addr, false, encoding, text);
+            } else {
+                addr += 1;
+            }
+        }
+    }
+}
+
+/// Finds the strings and constant tables in `program`'s data.
+///
+/// Plain strings are found first, then encoded ones in order of offset and stride, closest to
+/// 0 first. Strings whose length is stored in the cell before them are recognized as such.
+/// Other strings, and every encoded one, must read like text, since short runs of printable
+/// values are common in data and with enough offsets most runs of small numbers decode to
+/// something printable.
+pub fn extract(program: &[i64], config: &Config) -> Report {
+    let code = reachable(program);
+    let mut taken = vec![false; program.len()];
+    for inst in &code {
+        taken[inst.addr..inst.next()].fill(true);
+    }
+    let mut extractor = Extractor {
+        program,
+        config,
+        taken,
+        strings: Vec::new(),
+    };
+    extractor.scan(Encoding::PLAIN);
+    let mut offsets: Vec<_> = (-config.max_offset..=config.max_offset).collect();
+    offsets.sort_by_key(|offset: &i64| offset.abs());
+    for offset in offsets {
+        for &stride in &config.strides {
+            let encoding = Encoding { offset, stride };
+            if encoding != Encoding::PLAIN {
+                extractor.scan(encoding);
+            }
+        }
+    }
+    let Extractor {
+        mut taken,
+        mut strings,
+        ..
+    } = extractor;
+    strings.sort_by_key(|text| text.addr);
+
+    for inst in &code {
+        if let Some(write) = inst.write() {
+            if let (Mode::Position, Ok(addr)) = (write.mode, usize::try_from(write.value)) {
+                if let Some(cell) = taken.get_mut(addr) {
+                    *cell = true;
+                }
+            }
+        }
+    }
+    let mut tables = Vec::new();
+    let mut addr = 0;
+    while addr < program.len() {
+        let len = taken[addr..].iter().take_while(|&&taken| !taken).count();
+        let values = &program[addr..addr + len];
+        if len >= config.min_table_len.max(1) && values.iter().any(|&val| val != 0) {
+            tables.push(Table {
+                addr,
+                values: values.to_vec(),
+                xrefs: Vec::new(),
+            });
+        }
+        addr += len.max(1);
+    }
+
+    for inst in &code {
+        for text in &mut strings {
+            text.xrefs.extend(xrefs(inst, text.cells(), false));
+        }
+        for table in &mut tables {
+            table.xrefs.extend(xrefs(inst, table.cells(), true));
+        }
+    }
+    Report { strings, tables }
+}
+
+/// How `inst` refers to `cells`. Only pointers to the first cell count, unless `anywhere`.
+fn xrefs(inst: &Instruction, cells: Range<usize>, anywhere: bool) -> Vec<Xref> {
+    let jump_target = match inst.opcode {
+        5 | 6 => Some(1),
+        _ => None,
+    };
+    let write = inst.write();
+    let mut xrefs = Vec::new();
+    for (i, param) in inst.params.iter().enumerate() {
+        let addr = match usize::try_from(param.value) {
+            Ok(addr) if cells.contains(&addr) => addr,
+            _ => continue,
+        };
+        let access = match param.mode {
+            Mode::Position if write == Some(*param) && i + 1 == inst.params.len() => Access::Write,
+            Mode::Position => Access::Read,
+            Mode::Immediate if Some(i) != jump_target && (anywhere || addr == cells.start) => {
+                Access::Pointer
+            }
+            _ => continue,
+        };
+        let xref = Xref {
+            inst: inst.addr,
+            access,
+        };
+        if !xrefs.contains(&xref) {
+            xrefs.push(xref);
+        }
+    }
+    xrefs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::load_program;
+    use std::path::Path;
+
+    #[test]
+    fn finds_encoded_strings_and_tables() {
+        // Outputs the first character of an encoded string, then writes to the cell that splits
+        // the two tables after it.
+        let mut program = vec![4, 9, 1101, 1, 2, 26, 99, 0, 0];
+        let encoding = Encoding {
+            offset: 5,
+            stride: 1,
+        };
+        for (i, c) in "hello there".bytes().enumerate() {
+            program.push(i64::from(c) + encoding.offset + encoding.stride * i as i64);
+        }
+        program.extend((1..=6).chain(Some(0)).chain(1..=20));
+        let config = Config {
+            min_table_len: 6,
+            ..Config::default()
+        };
+        let report = extract(&program, &config);
+        assert_eq!(
+            report.to_string(),
+            "9: \"hello there\" (offset 5, stride 1), read at 0\n\
+             20: table of 6 cells: 1 2 3 4 5 6\n\
+             27: table of 20 cells: 1 2 3 4 5 6 7 8 ...\n"
+        );
+    }
+
+    #[test]
+    fn finds_the_ascii_program_messages() {
+        let program = load_program(Path::new("../day21/input/input.txt")).unwrap();
+        let report = extract(&program, &Config::default());
+        let strings: Vec<_> = report.strings.iter().map(|text| text.to_string()).collect();
+        assert_eq!(strings.len(), 8, "{}", report);
+        assert_eq!(
+            strings[..2],
+            [
+                r#"967: "Input instructions:\n" (length-prefixed), pointed to at 2"#,
+                r#"988: "\nWalking...\n\n" (length-prefixed), pointed to at 570"#,
+            ]
+        );
+        let invalid = &report.strings[5];
+        assert!(invalid.text.starts_with("Invalid first argument"));
+        assert_eq!(invalid.xrefs.len(), 3);
+
+        let program = load_program(Path::new("../day17/input/input.txt")).unwrap();
+        let report = extract(&program, &Config::default());
+        assert_eq!(report.strings.len(), 9, "{}", report);
+        assert!(report.strings.iter().all(|text| text.prefixed));
+        assert_eq!(report.strings[0].text, "Main:\n");
+        // The scaffolding, stored as run lengths.
+        let table = report.tables.last().unwrap();
+        assert_eq!(table.addr, 1260);
+        assert_eq!(table.values[..3], [24, 1, 1]);
+    }
+}
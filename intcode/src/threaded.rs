@@ -0,0 +1,359 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::scheduler::{Message, Route, Routing};
+
+// How often a thread blocked on input checks whether the network is shutting down.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(10);
+
+// Senders hold the lock while they put a value in a channel and machines hold it while they stop,
+// so `in_flight` always matches what is sitting in the channels of running machines.
+#[derive(Default)]
+struct Activity {
+    idle: Vec<bool>,
+    halted: Vec<bool>,
+    in_flight: usize,
+    idle_generation: u64,
+    shutdown: bool,
+}
+
+impl Activity {
+    fn is_quiescent(&self) -> bool {
+        self.in_flight == 0 && self.idle.iter().all(|&idle| idle)
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    activity: Mutex<Activity>,
+    became_idle: Condvar,
+}
+
+impl Shared {
+    fn update<T, F: FnOnce(&mut Activity) -> T>(&self, f: F) -> T {
+        let mut activity = self.activity.lock().unwrap();
+        let was_quiescent = activity.is_quiescent();
+        let res = f(&mut activity);
+        if !was_quiescent && activity.is_quiescent() {
+            activity.idle_generation += 1;
+            self.became_idle.notify_all();
+        }
+        res
+    }
+}
+
+/// Builds a network like `Scheduler`, except that every machine runs on its own thread and
+/// machines talk through channels.
+pub struct ThreadedNetwork {
+    routing: Routing,
+    machines: Vec<(IntCodeComputer, Option<i64>)>,
+}
+
+impl ThreadedNetwork {
+    pub fn new(routing: Routing) -> Self {
+        Self {
+            routing,
+            machines: Vec::new(),
+        }
+    }
+
+    pub fn add_machine(&mut self, computer: IntCodeComputer) -> usize {
+        self.machines.push((computer, None));
+        self.machines.len() - 1
+    }
+
+    /// Registers a machine that reads `fallback` instead of waiting when its channel is empty.
+    pub fn add_machine_with_fallback(&mut self, computer: IntCodeComputer, fallback: i64) -> usize {
+        self.machines.push((computer, Some(fallback)));
+        self.machines.len() - 1
+    }
+
+    pub fn start(self) -> RunningNetwork {
+        let count = self.machines.len();
+        let (inputs, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| mpsc::channel()).unzip();
+        let (outbox_sender, outbox) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        {
+            let mut activity = shared.activity.lock().unwrap();
+            activity.idle = vec![false; count];
+            activity.halted = vec![false; count];
+        }
+        let routing = Arc::new(self.routing);
+
+        let handles = self
+            .machines
+            .into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(id, ((computer, fallback), receiver))| {
+                let thread = MachineThread {
+                    id,
+                    computer,
+                    fallback,
+                    receiver,
+                    inputs: inputs.clone(),
+                    outbox: outbox_sender.clone(),
+                    routing: Arc::clone(&routing),
+                    shared: Arc::clone(&shared),
+                };
+                thread::spawn(move || thread.run())
+            })
+            .collect();
+
+        RunningNetwork {
+            inputs,
+            outbox,
+            shared,
+            handles,
+            seen_generation: 0,
+        }
+    }
+}
+
+struct MachineThread {
+    id: usize,
+    computer: IntCodeComputer,
+    fallback: Option<i64>,
+    receiver: Receiver<i64>,
+    inputs: Vec<Sender<i64>>,
+    outbox: Sender<Message>,
+    routing: Arc<Routing>,
+    shared: Arc<Shared>,
+}
+
+impl MachineThread {
+    fn run(mut self) -> IntCodeComputer {
+        let mut packet = Vec::new();
+        loop {
+            match self.computer.run_one_turn() {
+                ResultCode::Output(val) => {
+                    let id = self.id;
+                    self.shared.update(|activity| activity.idle[id] = false);
+                    let count = self.inputs.len();
+                    match self.routing.route(self.id, count, &mut packet, val) {
+                        Route::Deliver(targets, values) => self.deliver(&targets, &values),
+                        Route::Unrouted(values) => {
+                            let message = Message {
+                                from: self.id,
+                                values,
+                            };
+                            // The network may already have been dropped; nobody is listening then.
+                            let _ = self.outbox.send(message);
+                        }
+                        Route::Pending => {}
+                    }
+                }
+                ResultCode::Input => match self.next_input() {
+                    Some(val) => self.computer.add_input(val),
+                    None => break,
+                },
+                ResultCode::Terminated | ResultCode::Fault(_) => break,
+            }
+        }
+        let id = self.id;
+        let receiver = &self.receiver;
+        self.shared.update(|activity| {
+            activity.idle[id] = true;
+            activity.halted[id] = true;
+            activity.in_flight -= receiver.try_iter().count();
+        });
+        self.computer
+    }
+
+    fn deliver(&self, targets: &[usize], values: &[i64]) {
+        self.shared.update(|activity| {
+            for &to in targets {
+                for &val in values {
+                    send(activity, &self.inputs[to], to, val);
+                }
+            }
+        });
+    }
+
+    fn received(&self) {
+        let id = self.id;
+        self.shared.update(|activity| {
+            activity.idle[id] = false;
+            activity.in_flight -= 1;
+        });
+    }
+
+    // Returns `None` when the network is shutting down.
+    fn next_input(&self) -> Option<i64> {
+        let id = self.id;
+        if let Some(fallback) = self.fallback {
+            return match self.receiver.try_recv() {
+                Ok(val) => {
+                    self.received();
+                    Some(val)
+                }
+                Err(TryRecvError::Empty) => {
+                    let shutdown = self.shared.update(|activity| {
+                        activity.idle[id] = true;
+                        activity.shutdown
+                    });
+                    thread::yield_now();
+                    if shutdown {
+                        None
+                    } else {
+                        Some(fallback)
+                    }
+                }
+                Err(TryRecvError::Disconnected) => None,
+            };
+        }
+
+        self.shared.update(|activity| activity.idle[id] = true);
+        loop {
+            match self.receiver.recv_timeout(SHUTDOWN_POLL) {
+                Ok(val) => {
+                    self.received();
+                    return Some(val);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shared.activity.lock().unwrap().shutdown {
+                        return None;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+fn send(activity: &mut Activity, input: &Sender<i64>, to: usize, val: i64) {
+    if !activity.halted[to] && input.send(val).is_ok() {
+        activity.in_flight += 1;
+    }
+}
+
+/// Handle to a network whose machines are running on their own threads.
+pub struct RunningNetwork {
+    inputs: Vec<Sender<i64>>,
+    outbox: Receiver<Message>,
+    shared: Arc<Shared>,
+    handles: Vec<JoinHandle<IntCodeComputer>>,
+    seen_generation: u64,
+}
+
+impl RunningNetwork {
+    /// Queues input for a machine from outside the network. Input for a machine that has
+    /// already stopped is dropped.
+    pub fn send(&self, id: usize, val: i64) {
+        self.shared
+            .update(|activity| send(activity, &self.inputs[id], id, val));
+    }
+
+    /// Waits for the next message that no machine receives. Returns `None` once every machine
+    /// has stopped and the outbox is empty.
+    pub fn recv_outbox(&self) -> Option<Message> {
+        self.outbox.recv().ok()
+    }
+
+    /// Messages that no machine received, without waiting for more.
+    pub fn take_outbox(&self) -> Vec<Message> {
+        self.outbox.try_iter().collect()
+    }
+
+    /// Waits until every thread is idle, in the same sense as `Scheduler::is_quiescent`, and
+    /// nothing is left in the channels. Each call waits for the network to go idle again after
+    /// the previous call returned, unless every machine has stopped.
+    pub fn wait_until_idle(&mut self) {
+        let mut activity = self.shared.activity.lock().unwrap();
+        while activity.idle_generation == self.seen_generation
+            && !activity.halted.iter().all(|&halted| halted)
+        {
+            activity = self.shared.became_idle.wait(activity).unwrap();
+        }
+        self.seen_generation = activity.idle_generation;
+    }
+
+    /// Stops every thread and returns the machines in the order they were added. Machines that
+    /// faulted stopped early and report the fault through `IntCodeComputer::fault`.
+    pub fn shutdown(self) -> Vec<IntCodeComputer> {
+        self.shared.activity.lock().unwrap().shutdown = true;
+        self.handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::{Policy, Scheduler};
+
+    // Day 7's second feedback loop example.
+    const FEEDBACK: [i64; 29] = [
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    fn amplifiers() -> Vec<IntCodeComputer> {
+        [9, 8, 7, 6, 5]
+            .iter()
+            .map(|&phase| {
+                let mut computer = IntCodeComputer::new(&FEEDBACK);
+                computer.add_input(phase);
+                computer
+            })
+            .collect()
+    }
+
+    fn feedback_loop() -> Routing {
+        Routing::Pipes((0..5).map(|i| (i, (i + 1) % 5)).collect())
+    }
+
+    #[test]
+    fn threads_match_the_scheduler() {
+        let mut scheduler = Scheduler::new(feedback_loop(), Policy::UntilBlocked);
+        for computer in amplifiers() {
+            scheduler.add_machine(computer);
+        }
+        scheduler.send(0, 0);
+        scheduler.run();
+
+        let mut network = ThreadedNetwork::new(feedback_loop());
+        for computer in amplifiers() {
+            network.add_machine(computer);
+        }
+        let mut network = network.start();
+        network.send(0, 0);
+        network.wait_until_idle();
+        let computers = network.shutdown();
+
+        assert_eq!(computers[4].output().last(), Some(&139_629_729));
+        assert_eq!(computers[4].output(), scheduler.computer(4).output());
+        assert!(computers.iter().all(IntCodeComputer::is_halted));
+    }
+
+    #[test]
+    fn polling_threads_go_idle() {
+        // Polls until it reads something other than -1, then sends it to address 9.
+        let poller = [
+            3, 20, 1008, 20, -1, 21, 1005, 21, 0, 104, 9, 4, 20, 1105, 1, 0,
+        ];
+        let mut network = ThreadedNetwork::new(Routing::Packets { size: 2 });
+        network.add_machine_with_fallback(IntCodeComputer::new(&poller), -1);
+        network.add_machine_with_fallback(IntCodeComputer::new(&poller), -1);
+        let mut network = network.start();
+
+        network.wait_until_idle();
+        assert!(network.take_outbox().is_empty());
+
+        network.send(1, 5);
+        assert_eq!(
+            network.recv_outbox(),
+            Some(Message {
+                from: 1,
+                values: vec![9, 5]
+            })
+        );
+        network.wait_until_idle();
+        assert_eq!(network.shutdown().len(), 2);
+    }
+}
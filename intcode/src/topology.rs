@@ -0,0 +1,582 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::computer::{IntCodeComputer, ResultCode};
+use crate::fault::Fault;
+
+/// How an amplifier with several inputs combines the signals that arrive on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Merge {
+    /// Every signal is passed on as it arrives.
+    Interleave,
+    /// Waits for a signal on every input, then passes on their sum.
+    Sum,
+    /// Waits for a signal on every input, then passes on the largest.
+    Max,
+    /// Waits for a signal on every input, then passes on the smallest.
+    Min,
+}
+
+impl Merge {
+    fn combine(self, values: &[i64]) -> i64 {
+        match self {
+            Merge::Interleave => unreachable!("interleaved signals are not combined"),
+            Merge::Sum => values.iter().sum(),
+            Merge::Max => *values.iter().max().unwrap(),
+            Merge::Min => *values.iter().min().unwrap(),
+        }
+    }
+}
+
+impl FromStr for Merge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interleave" => Ok(Merge::Interleave),
+            "sum" => Ok(Merge::Sum),
+            "max" => Ok(Merge::Max),
+            "min" => Ok(Merge::Min),
+            _ => Err(format!("unknown merge rule {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Amplifier {
+    pub name: String,
+    /// Read by the program before any signal, if set.
+    pub phase: Option<i64>,
+    pub merge: Merge,
+}
+
+/// A directed graph of amplifiers that all run the same program: chains, loops, or amplifiers
+/// that copy their outputs to several others and merge what several others send them.
+///
+/// A topology is built with `chain`, `ring` and `connect`, or parsed from lines such as:
+///
+/// ```text
+/// # Amplifiers in a loop.
+/// amp A phase 9
+/// amp B phase 8 merge sum
+/// A -> B -> A
+/// signal A 0
+/// output B
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    amplifiers: Vec<Amplifier>,
+    /// Every output of the first amplifier is a signal for the second.
+    edges: Vec<(usize, usize)>,
+    /// Signals queued before anything runs, after the phase settings. They are not merged.
+    signals: Vec<(usize, i64)>,
+    /// The amplifier whose last output is the final signal.
+    output: usize,
+    /// The most instructions each amplifier runs before it is cut off.
+    max_steps: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopologyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TopologyError {}
+
+impl From<TopologyError> for io::Error {
+    fn from(err: TopologyError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// The signals an amplifier received and sent during a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Traffic {
+    pub name: String,
+    /// Signals queued as input, after merging, not counting the phase setting.
+    pub received: u64,
+    pub sent: u64,
+    pub instructions: u64,
+    pub halted: bool,
+    pub fault: Option<Fault>,
+    /// Whether the amplifier ran out of instructions before it halted or waited for a signal.
+    pub cut_off: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// The last output of the output amplifier, if it sent any.
+    pub signal: Option<i64>,
+    pub traffic: Vec<Traffic>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.signal {
+            Some(signal) => writeln!(f, "signal: {}", signal)?,
+            None => writeln!(f, "signal: none")?,
+        }
+        for traffic in &self.traffic {
+            write!(
+                f,
+                "{}: received {}, sent {}, {} instructions",
+                traffic.name, traffic.received, traffic.sent, traffic.instructions
+            )?;
+            match &traffic.fault {
+                Some(fault) => writeln!(f, ", {}", fault)?,
+                None if traffic.cut_off => writeln!(f, ", cut off")?,
+                None if !traffic.halted => writeln!(f, ", waiting")?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Amplifiers are named A, B, C... as in day 7, and by number past Z.
+fn default_name(i: usize) -> String {
+    if i < 26 {
+        ((b'A' + i as u8) as char).to_string()
+    } else {
+        i.to_string()
+    }
+}
+
+impl Topology {
+    /// No amplifiers yet. Until `set_output` is called, the first amplifier is the output.
+    /// Each amplifier runs at most 1000000 instructions.
+    pub fn new() -> Self {
+        Self {
+            amplifiers: Vec::new(),
+            edges: Vec::new(),
+            signals: Vec::new(),
+            output: 0,
+            max_steps: 1_000_000,
+        }
+    }
+
+    fn check(&self, id: usize) {
+        assert!(
+            id < self.amplifiers.len(),
+            "there is no amplifier {}, only {}",
+            id,
+            self.amplifiers.len()
+        );
+    }
+
+    /// Adds an amplifier that interleaves its inputs and returns its id.
+    pub fn amplifier(&mut self, name: &str, phase: Option<i64>) -> usize {
+        self.amplifiers.push(Amplifier {
+            name: name.to_string(),
+            phase,
+            merge: Merge::Interleave,
+        });
+        self.amplifiers.len() - 1
+    }
+
+    /// Panics if either amplifier has not been added, as do the other methods that take ids.
+    pub fn connect(&mut self, from: usize, to: usize) -> &mut Self {
+        self.check(from);
+        self.check(to);
+        self.edges.push((from, to));
+        self
+    }
+
+    pub fn merge(&mut self, id: usize, merge: Merge) -> &mut Self {
+        self.check(id);
+        self.amplifiers[id].merge = merge;
+        self
+    }
+
+    pub fn signal(&mut self, id: usize, val: i64) -> &mut Self {
+        self.check(id);
+        self.signals.push((id, val));
+        self
+    }
+
+    pub fn set_output(&mut self, id: usize) -> &mut Self {
+        self.check(id);
+        self.output = id;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// One amplifier per phase setting, each feeding the next. The first gets a signal of 0 and
+    /// the last is the output.
+    pub fn chain(phases: &[i64]) -> Self {
+        let mut topology = Self::new();
+        for (i, &phase) in phases.iter().enumerate() {
+            let id = topology.amplifier(&default_name(i), Some(phase));
+            if id > 0 {
+                topology.connect(id - 1, id);
+            }
+        }
+        if !phases.is_empty() {
+            topology.signal(0, 0);
+            topology.set_output(phases.len() - 1);
+        }
+        topology
+    }
+
+    /// Like `chain`, with the last amplifier feeding back into the first.
+    pub fn ring(phases: &[i64]) -> Self {
+        let mut topology = Self::chain(phases);
+        if !phases.is_empty() {
+            topology.connect(phases.len() - 1, 0);
+        }
+        topology
+    }
+
+    /// Gives the amplifiers new phase settings, in order.
+    pub fn with_phases(mut self, phases: &[i64]) -> Self {
+        for (amplifier, &phase) in self.amplifiers.iter_mut().zip(phases) {
+            amplifier.phase = Some(phase);
+        }
+        self
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.amplifiers.iter().position(|amp| amp.name == name)
+    }
+
+    pub fn amplifiers(&self) -> &[Amplifier] {
+        &self.amplifiers
+    }
+
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    pub fn signals(&self) -> &[(usize, i64)] {
+        &self.signals
+    }
+
+    pub fn output(&self) -> usize {
+        self.output
+    }
+
+    pub fn max_steps(&self) -> u64 {
+        self.max_steps
+    }
+
+    /// Parses a topology, one statement per line, with `#` starting a comment:
+    ///
+    /// - `amp NAME [phase N] [merge interleave|sum|max|min]` adds an amplifier.
+    /// - `A -> B -> C` connects each amplifier to the next.
+    /// - `signal NAME VALUE` queues a signal for an amplifier.
+    /// - `output NAME` picks the output amplifier, which is otherwise the last one added.
+    /// - `max_steps N` sets how many instructions each amplifier may run.
+    ///
+    /// Amplifiers must be added before they are used.
+    pub fn parse(text: &str) -> Result<Self, TopologyError> {
+        let mut topology = Self::new();
+        let mut output = None;
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let err = |message: String| TopologyError {
+                line: line_no,
+                message,
+            };
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let lookup = |name: &str| {
+                topology
+                    .id(name)
+                    .ok_or_else(|| err(format!("unknown amplifier {}", name)))
+            };
+            let number = |word: Option<&str>| {
+                let word = word.ok_or_else(|| err("missing number".to_string()))?;
+                word.parse::<i64>()
+                    .map_err(|_| err(format!("{} is not a number", word)))
+            };
+
+            if line.contains("->") {
+                let ids = line
+                    .split("->")
+                    .map(|name| lookup(name.trim()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for pair in ids.windows(2) {
+                    topology.connect(pair[0], pair[1]);
+                }
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            match words.next().unwrap() {
+                "amp" => {
+                    let name = words
+                        .next()
+                        .ok_or_else(|| err("missing amplifier name".to_string()))?;
+                    if topology.id(name).is_some() {
+                        return Err(err(format!("amplifier {} is already defined", name)));
+                    }
+                    let (mut phase, mut merge) = (None, Merge::Interleave);
+                    while let Some(key) = words.next() {
+                        match key {
+                            "phase" => phase = Some(number(words.next())?),
+                            "merge" => {
+                                let rule = words
+                                    .next()
+                                    .ok_or_else(|| err("missing merge rule".to_string()))?;
+                                merge = rule.parse().map_err(err)?;
+                            }
+                            _ => return Err(err(format!("unknown setting {}", key))),
+                        }
+                    }
+                    let id = topology.amplifier(name, phase);
+                    topology.merge(id, merge);
+                }
+                "signal" => {
+                    let id = lookup(words.next().unwrap_or(""))?;
+                    let val = number(words.next())?;
+                    topology.signal(id, val);
+                }
+                "output" => output = Some(lookup(words.next().unwrap_or(""))?),
+                "max_steps" => {
+                    let max_steps = number(words.next())?;
+                    topology.max_steps = u64::try_from(max_steps)
+                        .map_err(|_| err(format!("{} is not a number of steps", max_steps)))?;
+                }
+                word => return Err(err(format!("unknown statement {}", word))),
+            }
+        }
+        topology.output = output.unwrap_or_else(|| topology.amplifiers.len().saturating_sub(1));
+        Ok(topology)
+    }
+
+    pub fn load(filepath: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&read_to_string(filepath)?)?)
+    }
+
+    /// Runs every amplifier on `program` until all of them have halted, faulted, are waiting
+    /// for a signal that will not come, or have run `max_steps` instructions.
+    pub fn run(&self, program: &[i64]) -> Report {
+        let mut computers: Vec<_> = self
+            .amplifiers
+            .iter()
+            .map(|amp| {
+                let mut computer = IntCodeComputer::new(program);
+                if let Some(phase) = amp.phase {
+                    computer.add_input(phase);
+                }
+                computer
+            })
+            .collect();
+        let mut traffic: Vec<_> = self
+            .amplifiers
+            .iter()
+            .map(|amp| Traffic {
+                name: amp.name.clone(),
+                received: 0,
+                sent: 0,
+                instructions: 0,
+                halted: false,
+                fault: None,
+                cut_off: false,
+            })
+            .collect();
+        // Signals waiting to be merged, by edge.
+        let mut pending = vec![VecDeque::new(); self.edges.len()];
+
+        for &(id, val) in &self.signals {
+            computers[id].add_input(val);
+            traffic[id].received += 1;
+        }
+        loop {
+            let mut progress = false;
+            for id in 0..computers.len() {
+                loop {
+                    if traffic[id].instructions == self.max_steps {
+                        break;
+                    }
+                    let result = computers[id].step();
+                    match result {
+                        None => {}
+                        Some(ResultCode::Output(val)) => {
+                            traffic[id].sent += 1;
+                            self.send(id, val, &mut pending, &mut computers, &mut traffic);
+                        }
+                        Some(_) => break,
+                    }
+                    traffic[id].instructions += 1;
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        for (traffic, computer) in traffic.iter_mut().zip(&computers) {
+            traffic.halted = computer.is_halted();
+            traffic.fault = computer.fault();
+            traffic.cut_off = !traffic.halted && traffic.fault.is_none() && !computer.is_blocked();
+        }
+        Report {
+            signal: computers
+                .get(self.output)
+                .and_then(|computer| computer.output().last().copied()),
+            traffic,
+        }
+    }
+
+    fn send(
+        &self,
+        from: usize,
+        val: i64,
+        pending: &mut [VecDeque<i64>],
+        computers: &mut [IntCodeComputer],
+        traffic: &mut [Traffic],
+    ) {
+        for (edge, &(_, to)) in self.edges.iter().enumerate() {
+            if self.edges[edge].0 != from {
+                continue;
+            }
+            let merge = self.amplifiers[to].merge;
+            if merge == Merge::Interleave {
+                computers[to].add_input(val);
+                traffic[to].received += 1;
+                continue;
+            }
+            pending[edge].push_back(val);
+            let inputs: Vec<_> = (0..self.edges.len())
+                .filter(|&edge| self.edges[edge].1 == to)
+                .collect();
+            while inputs.iter().all(|&edge| !pending[edge].is_empty()) {
+                let values: Vec<_> = inputs
+                    .iter()
+                    .map(|&edge| pending[edge].pop_front().unwrap())
+                    .collect();
+                computers[to].add_input(merge.combine(&values));
+                traffic[to].received += 1;
+            }
+        }
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a phase, then outputs the phase plus twice each signal it reads.
+    const DOUBLER: [i64; 23] = [
+        3, 20, 3, 21, 2, 21, 22, 21, 1, 20, 21, 21, 4, 21, 1105, 1, 2, 0, 0, 0, 0, 0, 2,
+    ];
+
+    #[test]
+    fn runs_chains_and_rings() {
+        let program = DOUBLER;
+        // 0 -> 2*0+1 = 1 -> 2*1+2 = 4 -> 2*4+3 = 11
+        let report = Topology::chain(&[1, 2, 3]).run(&program);
+        assert_eq!(report.signal, Some(11));
+        let sent: Vec<_> = report.traffic.iter().map(|t| t.sent).collect();
+        assert_eq!(sent, [1, 1, 1]);
+        assert!(report
+            .traffic
+            .iter()
+            .all(|t| !t.halted && t.fault.is_none()));
+
+        // The doubler never halts, so in a ring of one the signal grows until it overflows.
+        let report = Topology::ring(&[1]).run(&program);
+        assert_eq!(report.signal, Some(i64::MAX));
+        assert_eq!(report.traffic[0].sent, 63);
+        assert!(report.traffic[0].fault.is_some());
+    }
+
+    #[test]
+    fn merges_fan_in() {
+        let text = "
+            # A copies its output to B and C, and D adds what they send.
+            amp A phase 1
+            amp B phase 2
+            amp C phase 3
+            amp D phase 0 merge sum
+            A -> B -> D
+            A -> C -> D
+            signal A 5
+        ";
+        let topology = Topology::parse(text).unwrap();
+        assert_eq!(topology.output(), 3);
+        let report = topology.run(&DOUBLER);
+        // A sends 11, B 24 and C 25, so D gets 49.
+        assert_eq!(report.signal, Some(98));
+        let received: Vec<_> = report.traffic.iter().map(|t| t.received).collect();
+        assert_eq!(received, [1, 1, 1, 1]);
+        assert_eq!(report.to_string().lines().next(), Some("signal: 98"),);
+
+        let mut max = topology.clone();
+        max.merge(3, Merge::Max);
+        assert_eq!(max.run(&DOUBLER).signal, Some(50));
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let err = Topology::parse("amp A\nA -> B").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unknown amplifier B");
+        let err = Topology::parse("amp A merge mean").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(Topology::parse("amp A phase x").is_err());
+        assert!(Topology::parse("amp A\namp A").is_err());
+        assert!(Topology::parse("max_steps -1").is_err());
+    }
+
+    #[test]
+    fn cuts_off_amplifiers_that_run_too_long() {
+        // C sends each signal back to itself.
+        let text = "
+            amp A phase 1
+            amp B
+            amp C phase 0
+            A -> C -> C
+            signal A 1
+            max_steps 500
+        ";
+        let mut topology = Topology::parse(text).unwrap();
+        topology.set_output(1);
+        // Outputs 7 forever without reading anything.
+        let program = [104, 7, 1105, 1, 0];
+        let report = topology.clone().run(&program);
+        assert_eq!(report.signal, Some(7));
+        assert!(report.traffic.iter().all(|t| t.instructions <= 500));
+        assert!(report.traffic.iter().all(|t| t.cut_off));
+        assert!(report
+            .to_string()
+            .contains("B: received 0, sent 250, 500 instructions, cut off"));
+
+        // B gets no signal and waits, and C doubles its signal until it is cut off.
+        let report = topology.with_max_steps(200).run(&DOUBLER);
+        let cut_off: Vec<_> = report.traffic.iter().map(|t| t.cut_off).collect();
+        assert_eq!(cut_off, [false, false, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "there is no amplifier 2, only 2")]
+    fn refuses_unknown_amplifiers() {
+        let mut topology = Topology::chain(&[0, 1]);
+        topology.connect(1, 2);
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+use std::process::Command;
+
+use intcode::remote::Remote;
+use intcode::{load_program, Fault, IntCodeComputer, Machine, ResultCode};
+
+fn server(program: &[i64]) -> Remote {
+    Remote::spawn(
+        &mut Command::new(env!("CARGO_BIN_EXE_intcode-server")),
+        program,
+    )
+    .unwrap()
+}
+
+fn springdroid<M: Machine>(machine: &mut M) -> Option<i64> {
+    let script = ["NOT A J", "NOT C T", "AND D T", "OR T J", "WALK"];
+    machine.respond("Input instructions:", &script).unwrap();
+    machine.read_ascii().values.last().copied()
+}
+
+#[test]
+fn child_processes_run_like_local_machines() {
+    let program = load_program(Path::new("../day21/input/input.txt")).unwrap();
+    let mut local = IntCodeComputer::new(&program);
+    let mut remote = server(&program);
+    assert_eq!(springdroid(&mut remote), springdroid(&mut local));
+    assert!(remote.is_halted());
+    assert_eq!(Machine::output(&remote), local.output());
+
+    let mut remote = server(&[1, 0, 0, 0, 42]);
+    assert_eq!(
+        remote.run_one_turn(),
+        ResultCode::Fault(Fault::InvalidOpcode { ip: 4, opcode: 42 })
+    );
+}